@@ -0,0 +1,167 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use halo2_curves::bn256::{Bn256, Fr, G1Affine, G1};
+use halo2_proofs::{
+    plonk::{create_proof, keygen_pk, keygen_vk, Circuit},
+    poly::{
+        commitment::{CommitmentScheme, ParamsProver},
+        kzg::{commitment::KZGCommitmentScheme, multiopen::ProverSHPLONK},
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
+};
+use plonk_verifier::{
+    loader::native::NativeLoader,
+    protocol::halo2::{compile, Config},
+    scheme::kzg::{AccumulationScheme, SameCurveAccumulation, ShplonkAccumulationScheme},
+    util::{multi_scalar_multiplication, Group, PrimeCurveAffine},
+};
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+use std::iter;
+
+#[allow(dead_code)]
+#[path = "../src/protocol/halo2/test/circuit/maingate.rs"]
+mod maingate;
+#[allow(dead_code)]
+#[path = "../src/protocol/halo2/test/circuit/standard.rs"]
+mod standard;
+
+use maingate::MainGateWithRange;
+use standard::StandardPlonk;
+
+const K: u32 = 9;
+const LIMBS: usize = 4;
+const BITS: usize = 68;
+
+fn naive(scalars: &[Fr], bases: &[G1]) -> G1 {
+    scalars
+        .iter()
+        .zip(bases.iter())
+        .map(|(scalar, base)| base * scalar)
+        .fold(G1::identity(), |acc, value| acc + value)
+}
+
+/// Accumulate a proof of `circuits` by `ShplonkAccumulationScheme` with
+/// `NativeLoader`, and return pairs of scalars and bases of the msms of the
+/// accumulator.
+fn shplonk_msms<C: Circuit<Fr>, const ZK: bool>(
+    params: &<KZGCommitmentScheme<Bn256> as CommitmentScheme>::ParamsProver,
+    circuits: &[C],
+    instances: Vec<Vec<Vec<Fr>>>,
+) -> Vec<(Vec<Fr>, Vec<G1>)> {
+    let vk = keygen_vk::<KZGCommitmentScheme<_>, _, ZK>(params, &circuits[0]).unwrap();
+    let pk = keygen_pk::<KZGCommitmentScheme<_>, _, ZK>(params, vk, &circuits[0]).unwrap();
+    let protocol = compile::<G1>(pk.get_vk(), Config::new(ZK, false, circuits.len()));
+
+    let proof = {
+        let instances = instances
+            .iter()
+            .map(|instances| instances.iter().map(Vec::as_slice).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let instances = instances.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(Vec::new());
+        create_proof::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _, _, _, _, ZK>(
+            params,
+            &pk,
+            circuits,
+            &instances,
+            ChaCha20Rng::from_seed(Default::default()),
+            &mut transcript,
+        )
+        .unwrap();
+        transcript.finalize()
+    };
+
+    let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
+    ShplonkAccumulationScheme::accumulate(
+        &protocol,
+        &NativeLoader,
+        instances.into_iter().flatten().collect(),
+        &mut Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof.as_slice()),
+        &mut strategy,
+    )
+    .unwrap();
+
+    let g1 = params.get_g()[0].to_curve();
+    strategy
+        .accumulator
+        .unwrap()
+        .terms()
+        .iter()
+        .map(|(msm, _)| {
+            iter::empty()
+                .chain(msm.scalar.map(|scalar| (scalar, g1)))
+                .chain(
+                    msm.scalars()
+                        .iter()
+                        .cloned()
+                        .zip(msm.bases().iter().cloned()),
+                )
+                .unzip()
+        })
+        .collect()
+}
+
+fn bench_msm(c: &mut Criterion) {
+    let params =
+        KZGCommitmentScheme::<Bn256>::new_params(K, ChaCha20Rng::from_seed(Default::default()));
+    let standard_plonk = |num_proof: usize| {
+        let circuits = (0..num_proof)
+            .map(|idx| StandardPlonk::<Fr>::rand(ChaCha20Rng::from_seed([idx as u8; 32])))
+            .collect::<Vec<_>>();
+        let instances = circuits.iter().map(StandardPlonk::instances).collect();
+        (circuits, instances)
+    };
+    let main_gate_with_range = |num_proof: usize| {
+        let circuits = (0..num_proof)
+            .map(|idx| MainGateWithRange::<Fr>::rand(ChaCha20Rng::from_seed([idx as u8; 32])))
+            .collect::<Vec<_>>();
+        let instances = circuits.iter().map(MainGateWithRange::instances).collect();
+        (circuits, instances)
+    };
+    let cases = iter::empty()
+        .chain([1, 2, 4].map(|num_proof| {
+            let (circuits, instances) = standard_plonk(num_proof);
+            (
+                format!("standard_plonk_{}", num_proof),
+                shplonk_msms::<_, false>(&params, &circuits, instances),
+            )
+        }))
+        .chain([1, 2].map(|num_proof| {
+            let (circuits, instances) = standard_plonk(num_proof);
+            (
+                format!("zk_standard_plonk_{}", num_proof),
+                shplonk_msms::<_, true>(&params, &circuits, instances),
+            )
+        }))
+        .chain([1, 2].map(|num_proof| {
+            let (circuits, instances) = main_gate_with_range(num_proof);
+            (
+                format!("main_gate_with_range_{}", num_proof),
+                shplonk_msms::<_, false>(&params, &circuits, instances),
+            )
+        }))
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("msm");
+    for (name, msms) in cases.iter() {
+        for (idx, (scalars, bases)) in msms.iter().enumerate() {
+            assert_eq!(
+                naive(scalars, bases),
+                multi_scalar_multiplication(scalars, bases)
+            );
+
+            let parameter = format!("{}/{}/{}", name, idx, scalars.len());
+            group.bench_with_input(BenchmarkId::new("naive", &parameter), &(), |b, _| {
+                b.iter(|| naive(scalars, bases))
+            });
+            group.bench_with_input(BenchmarkId::new("pippenger", &parameter), &(), |b, _| {
+                b.iter(|| multi_scalar_multiplication(scalars, bases))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_msm);
+criterion_main!(benches);
@@ -1,5 +1,3 @@
-#![feature(int_log)]
-#![feature(int_roundings)]
 #![allow(clippy::type_complexity)]
 #![allow(clippy::too_many_arguments)]
 #![allow(clippy::upper_case_acronyms)]
@@ -12,7 +10,31 @@ pub mod util;
 #[derive(Clone, Debug)]
 pub enum Error {
     InvalidInstances,
+    InvalidProofs(Vec<usize>),
     MissingQuery(util::Query),
     MissingChallenge(usize),
     Transcript(std::io::ErrorKind, String),
+    /// Limb of an accumulator coordinate exceeds `BITS` bits, or sign of a
+    /// compressed accumulator is not a bit.
+    AccumulatorLimbOutOfRange,
+    /// Accumulator coordinate is not less than the base field modulus.
+    AccumulatorCoordinateOutOfRange,
+    AccumulatorNotOnCurve,
+    AccumulatorIsIdentity,
+    /// Strategy has no accumulator yet, e.g. checkpointing before any proof is
+    /// accumulated.
+    MissingAccumulator,
+    /// Accumulator has terms paired with G2 points other than `g2` and
+    /// `-s_g2`, so it can't be evaluated as a pair `(lhs, rhs)`.
+    AccumulatorNotPair,
+    /// Accumulator has a term paired with G2 point of the index, which is not
+    /// given when deciding it.
+    MissingG2(usize),
+    /// Data contract holding large constants has runtime code of the size,
+    /// which exceeds the limit of contract code size.
+    DataContractTooLarge(usize),
+    /// Nothing to aggregate, e.g. creating an aggregation circuit with no snark.
+    MissingSnark,
+    /// Keygen or proving with halo2 failed, with the error formatted.
+    Halo2(String),
 }
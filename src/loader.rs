@@ -1,5 +1,5 @@
 use crate::util::{Curve, FieldOps, GroupOps, PrimeField};
-use std::{fmt::Debug, iter};
+use std::{fmt::Debug, hash::Hash, iter};
 
 pub mod native;
 
@@ -11,9 +11,21 @@ pub mod halo2;
 
 pub trait LoadedEcPoint<C: Curve>: Clone + Debug + GroupOps + PartialEq {
     type Loader: Loader<C, LoadedEcPoint = Self>;
+    /// Canonical key to identify the same point, which is used by `MSM` for
+    /// deduplication.
+    type Key: Clone + Debug + Eq + Hash;
 
     fn loader(&self) -> &Self::Loader;
 
+    /// Returns the key of the point, or `None` if the loader doesn't need
+    /// `MSM` to deduplicate bases.
+    fn key(&self) -> Option<Self::Key>;
+
+    /// Returns the value if it's known at code generation time.
+    fn as_const(&self) -> Option<C> {
+        None
+    }
+
     fn multi_scalar_multiplication(
         pairs: impl IntoIterator<
             Item = (
@@ -29,6 +41,11 @@ pub trait LoadedScalar<F: PrimeField>: Clone + Debug + FieldOps {
 
     fn loader(&self) -> &Self::Loader;
 
+    /// Returns the value if it's known at code generation time.
+    fn as_const(&self) -> Option<F> {
+        None
+    }
+
     fn sum_with_coeff_and_constant(values: &[(F, Self)], constant: &F) -> Self {
         assert!(!values.is_empty());
 
@@ -125,7 +142,8 @@ pub trait LoadedScalar<F: PrimeField>: Clone + Debug + FieldOps {
 
     fn powers(&self, n: usize) -> Vec<Self> {
         iter::once(self.loader().load_one())
-            .chain( // chao: successors will run forever, use take to take first n-1 items
+            .chain(
+                // chao: successors will run forever, use take to take first n-1 items
                 iter::successors(Some(self.clone()), |power| Some(power.clone() * self))
                     .take(n - 1),
             )
@@ -161,8 +179,25 @@ pub trait ScalarLoader<F: PrimeField> {
     }
 }
 
+/// Operations saved by folding terms known at code generation time when
+/// evaluating `MSM`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MsmFolding {
+    pub scalar_muls: usize,
+    pub additions: usize,
+}
+
+impl MsmFolding {
+    pub fn merge(&mut self, other: MsmFolding) {
+        self.scalar_muls += other.scalar_muls;
+        self.additions += other.additions;
+    }
+}
+
 pub trait Loader<C: Curve>: EcPointLoader<C> + ScalarLoader<C::Scalar> + Clone {
     fn start_cost_metering(&self, _: &str) {}
 
     fn end_cost_metering(&self) {}
+
+    fn record_msm_folding(&self, _: MsmFolding) {}
 }
@@ -1,20 +1,43 @@
-use crate::util::PrimeField;
+use crate::{loader::evm::code::Code, util::PrimeField};
 use ethereum_types::U256;
+use sha3::{Digest, Keccak256};
 use std::iter;
 
 mod accumulation;
 mod code;
+mod curve;
+mod inversion;
+mod ir;
 mod loader;
+mod memory;
+mod profile;
+#[cfg(feature = "simulate")]
+mod simulate;
+mod solidity;
+mod split;
 mod transcript;
 
 #[cfg(test)]
 mod test;
 
-pub use loader::EvmLoader;
+pub use code::Precompiled;
+pub use curve::{EvmCurve, G2Encoding};
+pub use ir::{BinaryOp, Expr, Op, Program, UnaryOp};
+pub use loader::{CalldataLayout, EcPoint, EvmLoader, Scalar};
+pub use memory::MemoryReport;
+pub use profile::{GasProfile, GasSection};
+#[cfg(feature = "simulate")]
+pub use simulate::{simulate, ExecutionReport};
 pub use transcript::EvmTranscript;
 
 #[cfg(test)]
-pub use test::execute;
+pub use test::{
+    compile_solidity, deploy_and_call, deploy_with_data_contract_and_call, encode_verify_calldata,
+    execute,
+};
+
+/// Maximum size of runtime code of a contract, introduced by EIP-170.
+pub const CODE_SIZE_LIMIT: usize = 0x6000;
 
 // Assert F::Repr in little-endian
 pub fn field_to_u256<F>(f: &F) -> U256
@@ -58,3 +81,62 @@ where
         .chain(proof)
         .collect()
 }
+
+/// Signature of the function whose selector is expected by code generated with
+/// [`CalldataLayout::Abi`] when using [`verify_selector`].
+pub const VERIFY_SIGNATURE: &str = "verify(uint256[],bytes)";
+
+/// Returns selector of [`VERIFY_SIGNATURE`].
+pub fn verify_selector() -> [u8; 4] {
+    Keccak256::digest(VERIFY_SIGNATURE.as_bytes())[..4]
+        .try_into()
+        .unwrap()
+}
+
+/// Encode `instances` and `proof` as ABI encoded `(uint256[], bytes)` in
+/// canonical form, optionally prefixed by `selector`, which is the calldata
+/// expected by code generated with [`CalldataLayout::Abi`].
+pub fn encode_abi_calldata<F>(
+    selector: Option<[u8; 4]>,
+    instances: Vec<Vec<F>>,
+    proof: Vec<u8>,
+) -> Vec<u8>
+where
+    F: PrimeField<Repr = [u8; 32]>,
+{
+    let num_instances = instances.iter().map(Vec::len).sum::<usize>();
+    let word = |value: usize| {
+        let mut bytes = [0; 32];
+        U256::from(value).to_big_endian(&mut bytes);
+        bytes
+    };
+    let padding = (32 - proof.len() % 32) % 32;
+
+    iter::empty()
+        .chain(selector.into_iter().flatten())
+        .chain(word(0x40))
+        .chain(word(0x60 + num_instances * 0x20))
+        .chain(word(num_instances))
+        .chain(encode_calldata(instances, Vec::new()))
+        .chain(word(proof.len()))
+        .chain(proof)
+        .chain(iter::repeat(0).take(padding))
+        .collect()
+}
+
+/// Wrap `runtime_code` with init code which returns it, so it can be deployed
+/// by a contract creation transaction.
+pub fn deployment_code(runtime_code: Vec<u8>) -> Vec<u8> {
+    let mut code = Code::new([]);
+    code.push(runtime_code.len()).dup(0);
+    // Length of the remaining init code, which are 3 two-byte `PUSH1`s,
+    // `CODECOPY` and `RETURN`.
+    let offset = code.len() + 8;
+    code.push(offset).push(0).codecopy().push(0).r#return();
+    debug_assert_eq!(code.len(), offset);
+
+    iter::empty()
+        .chain(Vec::from(code))
+        .chain(runtime_code)
+        .collect()
+}
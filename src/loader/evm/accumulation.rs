@@ -1,11 +1,16 @@
 use crate::{
-    loader::evm::loader::{EvmLoader, Scalar},
+    loader::evm::{
+        curve::G2Encoding,
+        loader::{EcPoint, EvmLoader, Scalar},
+    },
     protocol::Protocol,
-    scheme::kzg::{AccumulationStrategy, Accumulator, SameCurveAccumulation, MSM},
-    util::{Curve, PrimeCurveAffine, PrimeField, Transcript, UncompressedEncoding},
+    scheme::kzg::{
+        AccumulationStrategy, Accumulator, AccumulatorEncoding, CompressedEncoding,
+        HashCommittedEncoding, LimbsEncoding, SameCurveAccumulation,
+    },
+    util::{Curve, GroupEncoding, PrimeField, Transcript, UncompressedEncoding},
     Error,
 };
-use ethereum_types::U256;
 use halo2_curves::{
     bn256::{G1Affine, G2Affine, G1},
     CurveAffine,
@@ -13,24 +18,79 @@ use halo2_curves::{
 use std::{ops::Neg, rc::Rc};
 
 impl<const LIMBS: usize, const BITS: usize> SameCurveAccumulation<G1, Rc<EvmLoader>, LIMBS, BITS> {
-    pub fn code(self, g1: G1Affine, g2: G2Affine, s_g2: G2Affine) -> Vec<u8> {
-        let (lhs, rhs) = self.accumulator.unwrap().evaluate(g1.to_curve());
-        let loader = lhs.loader();
+    pub fn code(self, g1: G1Affine, g2: G2Affine, s_g2: G2Affine) -> Result<Vec<u8>, Error> {
+        self.code_with_g2s(g1, &[g2, s_g2.neg()])
+    }
+}
 
-        let [g2, minus_s_g2] = [g2, s_g2.neg()].map(|ec_point| {
-            let coordinates = ec_point.coordinates().unwrap();
-            let x = coordinates.x().to_repr();
-            let y = coordinates.y().to_repr();
-            (
-                U256::from_little_endian(&x.as_ref()[32..]),
-                U256::from_little_endian(&x.as_ref()[..32]),
-                U256::from_little_endian(&y.as_ref()[32..]),
-                U256::from_little_endian(&y.as_ref()[..32]),
-            )
-        });
-        loader.pairing(&lhs, g2, &rhs, minus_s_g2);
+impl<C, const LIMBS: usize, const BITS: usize> SameCurveAccumulation<C, Rc<EvmLoader>, LIMBS, BITS>
+where
+    C: Curve + UncompressedEncoding,
+    C::Scalar: PrimeField<Repr = [u8; 32]>,
+{
+    /// Generate code with each term of accumulator paired with the G2 point in
+    /// `g2s` by its index, where G2 points are encoded by
+    /// [`EvmCurve::encode_g2`](crate::loader::evm::EvmCurve::encode_g2) with
+    /// halves of each coordinate's repr taken as its real and imaginary parts.
+    ///
+    /// It fails with [`Error::MissingG2`] if any index is out of range of
+    /// `g2s`.
+    pub fn code_with_g2s<G2: CurveAffine>(
+        self,
+        g1: impl Into<C>,
+        g2s: &[G2],
+    ) -> Result<Vec<u8>, Error> {
+        let accumulator = self.accumulator.ok_or(Error::MissingAccumulator)?;
+        accumulator.check_g2_indices(g2s.len())?;
+        let terms = accumulator.evaluate_terms(g1.into());
+        let loader = terms
+            .first()
+            .ok_or(Error::MissingAccumulator)?
+            .0
+            .loader()
+            .clone();
 
-        loader.code()
+        let g2s = g2s
+            .iter()
+            .map(|ec_point| {
+                let coordinates = ec_point.coordinates().unwrap();
+                let [x, y] = [coordinates.x().to_repr(), coordinates.y().to_repr()];
+                let be = |repr: &[u8]| {
+                    repr.chunks(repr.len() / 2)
+                        .map(|bytes| bytes.iter().rev().cloned().collect::<Vec<_>>())
+                        .collect::<Vec<_>>()
+                };
+                let [x, y] = [be(x.as_ref()), be(y.as_ref())];
+                loader.curve().encode_g2([&x[0], &x[1]], [&y[0], &y[1]])
+            })
+            .collect::<Vec<_>>();
+        let axes = match loader.curve().g2_encoding {
+            G2Encoding::ImaginaryFirst => ["X_1", "X_0", "Y_1", "Y_0"],
+            G2Encoding::RealFirst => ["X_0", "X_1", "Y_0", "Y_1"],
+        };
+        for (idx, g2) in g2s.iter().enumerate() {
+            let words = g2.chunks(g2.len() / axes.len());
+            for (axis, words) in axes.into_iter().zip(words) {
+                for (word_idx, value) in words.iter().enumerate() {
+                    let name = if words.len() == 1 {
+                        format!("G2_{}_{}", idx, axis)
+                    } else {
+                        format!("G2_{}_{}_{}", idx, axis, word_idx)
+                    };
+                    loader.name_constant(name, *value);
+                }
+            }
+        }
+        loader.start_gas_metering("pairing");
+        loader.pairing_n(
+            &terms
+                .iter()
+                .map(|(ec_point, g2_index)| (ec_point, g2s[*g2_index].as_slice()))
+                .collect::<Vec<_>>(),
+        );
+        loader.end_gas_metering();
+
+        Ok(loader.code())
     }
 }
 
@@ -39,60 +99,107 @@ impl<C: Curve, T, P, const LIMBS: usize, const BITS: usize>
     for SameCurveAccumulation<C, Rc<EvmLoader>, LIMBS, BITS>
 where
     C::Scalar: PrimeField<Repr = [u8; 32]>,
-    C: UncompressedEncoding<Uncompressed = [u8; 64]>,
+    C: GroupEncoding + UncompressedEncoding,
     T: Transcript<C, Rc<EvmLoader>>,
 {
     type Output = ();
 
     fn extract_accumulator(
-        &self,
+        &mut self,
         protocol: &Protocol<C>,
         loader: &Rc<EvmLoader>,
         transcript: &mut T,
         statements: &[Vec<Scalar>],
-    ) -> Option<Accumulator<C, Rc<EvmLoader>>> {
-        let accumulator_indices = protocol.accumulator_indices.as_ref()?;
-
-        let num_statements = statements
-            .iter()
-            .map(|statements| statements.len())
-            .collect::<Vec<_>>();
-
-        let challenges = transcript.squeeze_n_challenges(accumulator_indices.len());
-        let accumulators = accumulator_indices
-            .iter()
-            .map(|indices| {
-                assert_eq!(indices.len(), 4 * LIMBS);
-                assert!(indices
-                    .iter()
-                    .enumerate()
-                    .all(|(idx, index)| indices[0] == (index.0, index.1 - idx)));
-                let offset =
-                    (num_statements[..indices[0].0].iter().sum::<usize>() + indices[0].1) * 0x20;
-                let lhs = loader.calldataload_ec_point_from_limbs::<LIMBS, BITS>(offset);
-                let rhs = loader.calldataload_ec_point_from_limbs::<LIMBS, BITS>(offset + 0x100);
-                Accumulator::new(MSM::base(lhs), MSM::base(rhs))
-            })
-            .collect::<Vec<_>>();
-
-        Some(Accumulator::random_linear_combine(
-            challenges.into_iter().zip(accumulators),
-        ))
+    ) -> Result<Option<Accumulator<C, Rc<EvmLoader>>>, Error> {
+        self.decode_accumulators(protocol, loader, transcript, statements)
     }
 
     fn process(
         &mut self,
-        _: &Rc<EvmLoader>,
+        protocol: &Protocol<C>,
+        loader: &Rc<EvmLoader>,
         transcript: &mut T,
         _: P,
         accumulator: Accumulator<C, Rc<EvmLoader>>,
     ) -> Result<Self::Output, Error> {
-        self.accumulator = Some(match self.accumulator.take() {
-            Some(curr_accumulator) => {
-                accumulator + curr_accumulator * &transcript.squeeze_challenge()
-            }
-            None => accumulator,
-        });
-        Ok(())
+        loader.name_protocol_constants(protocol);
+        self.combine(protocol, loader, transcript, accumulator)
+    }
+}
+
+impl<C: Curve, const LIMBS: usize, const BITS: usize> AccumulatorEncoding<C, Rc<EvmLoader>>
+    for LimbsEncoding<LIMBS, BITS>
+where
+    C::Scalar: PrimeField<Repr = [u8; 32]>,
+    C: UncompressedEncoding,
+{
+    fn decode(
+        &self,
+        loader: &Rc<EvmLoader>,
+        _: usize,
+        statements: &[Scalar],
+    ) -> Result<(EcPoint, EcPoint), Error> {
+        if statements.len() != 4 * LIMBS {
+            return Err(Error::InvalidInstances);
+        }
+
+        let [lhs_x, lhs_y, rhs_x, rhs_y]: [_; 4] = statements
+            .chunks(LIMBS)
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        Ok((
+            loader.ec_point_from_limbs::<LIMBS, BITS>(lhs_x, lhs_y),
+            loader.ec_point_from_limbs::<LIMBS, BITS>(rhs_x, rhs_y),
+        ))
+    }
+}
+
+impl<C: Curve, const LIMBS: usize, const BITS: usize> AccumulatorEncoding<C, Rc<EvmLoader>>
+    for CompressedEncoding<LIMBS, BITS>
+where
+    C::Scalar: PrimeField<Repr = [u8; 32]>,
+    C: UncompressedEncoding,
+{
+    fn decode(
+        &self,
+        loader: &Rc<EvmLoader>,
+        _: usize,
+        statements: &[Scalar],
+    ) -> Result<(EcPoint, EcPoint), Error> {
+        if statements.len() != 2 * (LIMBS + 1) {
+            return Err(Error::InvalidInstances);
+        }
+
+        let (lhs, rhs) = statements.split_at(LIMBS + 1);
+        Ok((
+            loader.ec_point_from_compressed::<LIMBS, BITS>(&lhs[..LIMBS], &lhs[LIMBS]),
+            loader.ec_point_from_compressed::<LIMBS, BITS>(&rhs[..LIMBS], &rhs[LIMBS]),
+        ))
+    }
+}
+
+/// Witness of [`HashCommittedEncoding`] for `Rc<EvmLoader>` is the calldata
+/// offset of uncompressed `(lhs, rhs)`.
+impl<C: Curve, H> AccumulatorEncoding<C, Rc<EvmLoader>> for HashCommittedEncoding<usize, H>
+where
+    C::Scalar: PrimeField<Repr = [u8; 32]>,
+    C: UncompressedEncoding,
+    H: Fn(&EcPoint, &EcPoint) -> Result<Scalar, Error>,
+{
+    fn decode(
+        &self,
+        loader: &Rc<EvmLoader>,
+        idx: usize,
+        statements: &[Scalar],
+    ) -> Result<(EcPoint, EcPoint), Error> {
+        let [statement]: &[Scalar; 1] =
+            statements.try_into().map_err(|_| Error::InvalidInstances)?;
+        let offset = *self.witness(idx)?;
+        let lhs = loader.calldataload_ec_point(offset);
+        let rhs = loader.calldataload_ec_point(offset + loader.curve().ec_point_size());
+        let hash = (self.hash())(&lhs, &rhs)?;
+        loader.assert_scalar_eq(&hash, statement);
+        Ok((lhs, rhs))
     }
 }
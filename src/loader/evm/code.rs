@@ -1,11 +1,18 @@
 use ethereum_types::U256;
 use foundry_evm::{revm::opcode::*, HashMap};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Precompiled {
     BigModExp = 0x05,
     Bn254Add = 0x6,
     Bn254ScalarMul = 0x7,
     Bn254Pairing = 0x8,
+    /// BLS12-381 G1 addition of EIP-2537.
+    Bls12381G1Add = 0x0b,
+    /// BLS12-381 G1 MSM of EIP-2537.
+    Bls12381G1Msm = 0x0c,
+    /// BLS12-381 pairing of EIP-2537.
+    Bls12381Pairing = 0x0f,
 }
 
 #[derive(Clone, Debug)]
@@ -0,0 +1,136 @@
+use crate::loader::evm::code::Precompiled;
+use ethereum_types::U256;
+
+/// Order of coordinates of a G2 point in input of pairing precompile, where
+/// each coordinate is `c0 + c1 * u` in the quadratic extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum G2Encoding {
+    /// `x.c1, x.c0, y.c1, y.c0`, as EIP-197.
+    ImaginaryFirst,
+    /// `x.c0, x.c1, y.c0, y.c1`, as EIP-2537.
+    RealFirst,
+}
+
+/// Curve backend of [`EvmLoader`](super::EvmLoader), which describes the
+/// precompiles of a pairing-friendly curve and how its points are encoded for
+/// them.
+///
+/// A point is encoded as `x` followed by `y`, each in big-endian padded to
+/// `coordinate_size` bytes, and the point at infinity is encoded as zeros.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvmCurve {
+    /// Modulus of base field, which is `None` when it doesn't fit in a word,
+    /// so arithmetic over base field can't be done by `addmod` and `mulmod`.
+    pub base_modulus: Option<U256>,
+    pub scalar_modulus: U256,
+    /// `b` of curve equation `y^2 = x^3 + b`.
+    pub b: u64,
+    /// Size of a coordinate in precompile input, which is a multiple of 32.
+    pub coordinate_size: usize,
+    pub ec_add: Precompiled,
+    /// Precompile taking a point followed by a scalar and returning their
+    /// product, which is either a scalar multiplication or an MSM of a single
+    /// pair.
+    pub ec_mul: Precompiled,
+    pub pairing: Precompiled,
+    pub g2_encoding: G2Encoding,
+}
+
+impl EvmCurve {
+    /// BN254 with precompiles of EIP-196 and EIP-197.
+    pub const BN254: Self = Self {
+        base_modulus: Some(U256([
+            0x3c208c16d87cfd47,
+            0x97816a916871ca8d,
+            0xb85045b68181585d,
+            0x30644e72e131a029,
+        ])),
+        scalar_modulus: U256([
+            0x43e1f593f0000001,
+            0x2833e84879b97091,
+            0xb85045b68181585d,
+            0x30644e72e131a029,
+        ]),
+        b: 3,
+        coordinate_size: 0x20,
+        ec_add: Precompiled::Bn254Add,
+        ec_mul: Precompiled::Bn254ScalarMul,
+        pairing: Precompiled::Bn254Pairing,
+        g2_encoding: G2Encoding::ImaginaryFirst,
+    };
+
+    /// BLS12-381 with precompiles of EIP-2537, whose 48-byte coordinates are
+    /// padded to 64 bytes. Points are validated by the precompiles, which
+    /// fail on coordinates out of range or points not on curve.
+    ///
+    /// Only raw precompile calls are supported, i.e. addition, scalar
+    /// multiplication and pairing of points read from calldata or loaded by
+    /// [`EvmLoader::ec_point_load_coordinates`](super::EvmLoader::ec_point_load_coordinates).
+    /// `halo2_curves` has no BLS12-381, so no KZG verifier over it can be
+    /// generated yet.
+    pub const BLS12_381: Self = Self {
+        base_modulus: None,
+        scalar_modulus: U256([
+            0xffffffff00000001,
+            0x53bda402fffe5bfe,
+            0x3339d80809a1d805,
+            0x73eda753299d7d48,
+        ]),
+        b: 4,
+        coordinate_size: 0x40,
+        ec_add: Precompiled::Bls12381G1Add,
+        ec_mul: Precompiled::Bls12381G1Msm,
+        pairing: Precompiled::Bls12381Pairing,
+        g2_encoding: G2Encoding::RealFirst,
+    };
+
+    /// Returns size of an encoded G1 point.
+    pub fn ec_point_size(&self) -> usize {
+        2 * self.coordinate_size
+    }
+
+    /// Returns size of an encoded G2 point.
+    pub fn g2_size(&self) -> usize {
+        4 * self.coordinate_size
+    }
+
+    /// Returns whether coordinates fit in a word, which is required to
+    /// operate on them by EVM arithmetic instead of precompiles.
+    pub fn is_word_sized(&self) -> bool {
+        self.coordinate_size == 0x20
+    }
+
+    /// Returns `(cd_len, rd_len)` of calling `precompile` of this curve once.
+    pub(super) fn precompile_len(&self, precompile: Precompiled) -> (usize, usize) {
+        if precompile == self.ec_add {
+            (2 * self.ec_point_size(), self.ec_point_size())
+        } else if precompile == self.ec_mul {
+            (self.ec_point_size() + 0x20, self.ec_point_size())
+        } else if precompile == self.pairing {
+            (self.ec_point_size() + self.g2_size(), 0x20)
+        } else {
+            unreachable!("{:?} is not a precompile of the curve", precompile)
+        }
+    }
+
+    /// Encode G2 point with coordinates `x = x[0] + x[1] * u` and
+    /// `y = y[0] + y[1] * u` given in big-endian as words of pairing input.
+    pub fn encode_g2(&self, x: [&[u8]; 2], y: [&[u8]; 2]) -> Vec<U256> {
+        let coordinates = match self.g2_encoding {
+            G2Encoding::ImaginaryFirst => [x[1], x[0], y[1], y[0]],
+            G2Encoding::RealFirst => [x[0], x[1], y[0], y[1]],
+        };
+        coordinates
+            .iter()
+            .flat_map(|bytes| {
+                assert!(bytes.len() <= self.coordinate_size);
+                let mut padded = vec![0; self.coordinate_size];
+                padded[self.coordinate_size - bytes.len()..].copy_from_slice(bytes);
+                padded
+                    .chunks(0x20)
+                    .map(U256::from_big_endian)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
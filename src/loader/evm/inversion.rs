@@ -0,0 +1,162 @@
+use crate::loader::evm::{
+    code::Precompiled,
+    ir::{Expr, Op, Program},
+};
+use ethereum_types::U256;
+use std::ops::Range;
+
+fn reads(op: &Op) -> Vec<Range<usize>> {
+    let mut reads = Vec::new();
+    op.visit_memory_reads(&mut |ptr, len| reads.push(ptr..ptr + len));
+    reads
+}
+
+fn overlaps(lhs: &Range<usize>, rhs: &Range<usize>) -> bool {
+    lhs.start < rhs.end && rhs.start < lhs.end
+}
+
+/// Inversions deferred but not yet resolved, with memory they read and write.
+#[derive(Default)]
+struct Batch {
+    inversions: Vec<(Expr, usize)>,
+    reads: Vec<Range<usize>>,
+    writes: Vec<Range<usize>>,
+}
+
+impl Batch {
+    /// Returns whether `op` has to be placed after the batch is resolved,
+    /// which is when it reads an inverse, or writes memory read or written by
+    /// the batch.
+    fn conflicts(&self, op: &Op) -> bool {
+        let write = op.memory_write().map(|(ptr, len)| ptr..ptr + len);
+        reads(op)
+            .iter()
+            .any(|read| self.writes.iter().any(|write| overlaps(read, write)))
+            || write.is_some_and(|write| {
+                self.reads
+                    .iter()
+                    .chain(self.writes.iter())
+                    .any(|range| overlaps(range, &write))
+            })
+    }
+
+    fn push(&mut self, op: &Op, src: &Expr, dst: usize) {
+        self.reads.extend(reads(op));
+        self.writes.push(dst..dst + 0x20);
+        self.inversions.push((src.clone(), dst));
+    }
+
+    /// Resolve inversions with Montgomery's trick, which inverts the product
+    /// of all values with a single `BigModExp`, and folds into `success` that
+    /// the product is not zero, so inverting zero fails verification.
+    fn resolve(&mut self, modulus: U256, ptr: &mut usize, ops: &mut Vec<Op>) {
+        let inversions = std::mem::take(&mut self.inversions);
+        self.reads.clear();
+        self.writes.clear();
+        if inversions.is_empty() {
+            return;
+        }
+
+        let mut allocate = |size: usize| {
+            let allocated = *ptr;
+            *ptr += size;
+            allocated
+        };
+        let modulus_expr = || Expr::Constant(modulus);
+
+        let products = inversions
+            .iter()
+            .map(|_| allocate(0x20))
+            .collect::<Vec<_>>();
+        for (idx, (src, _)) in inversions.iter().enumerate() {
+            let product = match idx {
+                0 => src.clone(),
+                _ => Expr::mulmod(Expr::MLoad(products[idx - 1]), src.clone(), modulus_expr()),
+            };
+            ops.push(Op::MStore(products[idx], product));
+        }
+        let product = *products.last().unwrap();
+        ops.push(Op::Assert(Expr::iszero(Expr::iszero(Expr::MLoad(product)))));
+
+        let cd_ptr = allocate(0xc0);
+        let acc = allocate(0x20);
+        for (offset, value) in [
+            (0, Expr::constant(0x20)),
+            (0x20, Expr::constant(0x20)),
+            (0x40, Expr::constant(0x20)),
+            (0x60, Expr::MLoad(product)),
+            (0x80, Expr::Constant(modulus - 2)),
+            (0xa0, modulus_expr()),
+        ] {
+            ops.push(Op::MStore(cd_ptr + offset, value));
+        }
+        ops.push(Op::Precompile {
+            precompile: Precompiled::BigModExp,
+            cd_ptr,
+            cd_len: 0xc0,
+            rd_ptr: acc,
+            rd_len: 0x20,
+        });
+
+        // With `acc` as inverse of product of the first `idx + 1` values,
+        // inverse of the `idx`-th value is `acc` times product of the first
+        // `idx` values, which is kept in `products[idx]` when `dst` is read by
+        // `src` and has to be written after `acc` is updated.
+        for (idx, (src, dst)) in inversions.iter().enumerate().skip(1).rev() {
+            let inverse = Expr::mulmod(
+                Expr::MLoad(acc),
+                Expr::MLoad(products[idx - 1]),
+                modulus_expr(),
+            );
+            let mut dst_is_read = false;
+            src.visit_memory_reads(&mut |ptr, len| {
+                dst_is_read |= overlaps(&(ptr..ptr + len), &(*dst..*dst + 0x20))
+            });
+            let next_acc = Op::MStore(
+                acc,
+                Expr::mulmod(Expr::MLoad(acc), src.clone(), modulus_expr()),
+            );
+            if dst_is_read {
+                ops.push(Op::MStore(products[idx], inverse));
+                ops.push(next_acc);
+                ops.push(Op::MStore(*dst, Expr::MLoad(products[idx])));
+            } else {
+                ops.push(Op::MStore(*dst, inverse));
+                ops.push(next_acc);
+            }
+        }
+        ops.push(Op::MStore(inversions[0].1, Expr::MLoad(acc)));
+    }
+}
+
+impl Program {
+    /// Returns program with each [`Op::Invert`] resolved, where consecutive
+    /// ones are collected into a batch resolved by a single `BigModExp` right
+    /// before the first op depending on them, so there are as many `BigModExp`
+    /// as inversions whose values are needed before others are requested.
+    /// Scratch memory is allocated above memory touched by the program.
+    pub fn resolve_inversions(&self, modulus: U256) -> Program {
+        if !self.ops().iter().any(|op| matches!(op, Op::Invert { .. })) {
+            return self.clone();
+        }
+
+        let mut ptr = self.memory_size();
+
+        let mut program = self.clone();
+        let ops = program.ops_mut();
+        ops.clear();
+        let mut batch = Batch::default();
+        for op in self.ops() {
+            if batch.conflicts(op) {
+                batch.resolve(modulus, &mut ptr, ops);
+            }
+            match op {
+                Op::Invert { src, dst } => batch.push(op, src, *dst),
+                _ => ops.push(op.clone()),
+            }
+        }
+        batch.resolve(modulus, &mut ptr, ops);
+
+        program
+    }
+}
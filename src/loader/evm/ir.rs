@@ -0,0 +1,594 @@
+use crate::loader::evm::code::{Code, Precompiled};
+use ethereum_types::{Address, U256};
+use std::fmt::{self, Display};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    IsZero,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mod,
+    Lt,
+    Eq,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+/// Value computed on stack, which has no side effect. Operands are in the
+/// order of Yul, so `Binary(Sub, lhs, rhs)` is `sub(lhs, rhs)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Constant(U256),
+    MLoad(usize),
+    CalldataLoad(usize),
+    CalldataSize,
+    Keccak { ptr: usize, len: usize },
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    AddMod(Box<Expr>, Box<Expr>, Box<Expr>),
+    MulMod(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn unary(op: UnaryOp, value: Expr) -> Self {
+        Expr::Unary(op, Box::new(value))
+    }
+
+    fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Self {
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn constant(value: impl Into<U256>) -> Self {
+        Expr::Constant(value.into())
+    }
+
+    pub fn iszero(value: Expr) -> Self {
+        Self::unary(UnaryOp::IsZero, value)
+    }
+
+    pub fn not(value: Expr) -> Self {
+        Self::unary(UnaryOp::Not, value)
+    }
+
+    pub fn add(lhs: Expr, rhs: Expr) -> Self {
+        Self::binary(BinaryOp::Add, lhs, rhs)
+    }
+
+    pub fn sub(lhs: Expr, rhs: Expr) -> Self {
+        Self::binary(BinaryOp::Sub, lhs, rhs)
+    }
+
+    pub fn r#mod(lhs: Expr, rhs: Expr) -> Self {
+        Self::binary(BinaryOp::Mod, lhs, rhs)
+    }
+
+    pub fn lt(lhs: Expr, rhs: Expr) -> Self {
+        Self::binary(BinaryOp::Lt, lhs, rhs)
+    }
+
+    pub fn eq(lhs: Expr, rhs: Expr) -> Self {
+        Self::binary(BinaryOp::Eq, lhs, rhs)
+    }
+
+    pub fn and(lhs: Expr, rhs: Expr) -> Self {
+        Self::binary(BinaryOp::And, lhs, rhs)
+    }
+
+    pub fn or(lhs: Expr, rhs: Expr) -> Self {
+        Self::binary(BinaryOp::Or, lhs, rhs)
+    }
+
+    pub fn xor(lhs: Expr, rhs: Expr) -> Self {
+        Self::binary(BinaryOp::Xor, lhs, rhs)
+    }
+
+    /// Returns `value << shift`.
+    pub fn shl(shift: Expr, value: Expr) -> Self {
+        Self::binary(BinaryOp::Shl, shift, value)
+    }
+
+    /// Returns `value >> shift`.
+    pub fn shr(shift: Expr, value: Expr) -> Self {
+        Self::binary(BinaryOp::Shr, shift, value)
+    }
+
+    pub fn addmod(lhs: Expr, rhs: Expr, modulus: Expr) -> Self {
+        Expr::AddMod(Box::new(lhs), Box::new(rhs), Box::new(modulus))
+    }
+
+    pub fn mulmod(lhs: Expr, rhs: Expr, modulus: Expr) -> Self {
+        Expr::MulMod(Box::new(lhs), Box::new(rhs), Box::new(modulus))
+    }
+
+    /// Visit memory words read by this expression as `(ptr, len)`.
+    pub fn visit_memory_reads(&self, visit: &mut impl FnMut(usize, usize)) {
+        match self {
+            Expr::Constant(_) | Expr::CalldataLoad(_) | Expr::CalldataSize => {}
+            Expr::MLoad(ptr) => visit(*ptr, 0x20),
+            Expr::Keccak { ptr, len } => visit(*ptr, *len),
+            Expr::Unary(_, value) => value.visit_memory_reads(visit),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.visit_memory_reads(visit);
+                rhs.visit_memory_reads(visit);
+            }
+            Expr::AddMod(lhs, rhs, modulus) | Expr::MulMod(lhs, rhs, modulus) => {
+                lhs.visit_memory_reads(visit);
+                rhs.visit_memory_reads(visit);
+                modulus.visit_memory_reads(visit);
+            }
+        }
+    }
+
+    /// Replace each memory pointer `ptr` by `map(ptr)`.
+    pub fn map_ptrs(&mut self, map: &impl Fn(usize) -> usize) {
+        match self {
+            Expr::Constant(_) | Expr::CalldataLoad(_) | Expr::CalldataSize => {}
+            Expr::MLoad(ptr) | Expr::Keccak { ptr, .. } => *ptr = map(*ptr),
+            Expr::Unary(_, value) => value.map_ptrs(map),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.map_ptrs(map);
+                rhs.map_ptrs(map);
+            }
+            Expr::AddMod(lhs, rhs, modulus) | Expr::MulMod(lhs, rhs, modulus) => {
+                lhs.map_ptrs(map);
+                rhs.map_ptrs(map);
+                modulus.map_ptrs(map);
+            }
+        }
+    }
+
+    /// Replace each constant `value` by `map(value)` when it returns `Some`.
+    pub fn map_constants(&mut self, map: &mut impl FnMut(U256) -> Option<Expr>) {
+        match self {
+            Expr::Constant(value) => {
+                if let Some(expr) = map(*value) {
+                    *self = expr;
+                }
+            }
+            Expr::MLoad(_) | Expr::CalldataLoad(_) | Expr::CalldataSize | Expr::Keccak { .. } => {}
+            Expr::Unary(_, value) => value.map_constants(map),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.map_constants(map);
+                rhs.map_constants(map);
+            }
+            Expr::AddMod(lhs, rhs, modulus) | Expr::MulMod(lhs, rhs, modulus) => {
+                lhs.map_constants(map);
+                rhs.map_constants(map);
+                modulus.map_constants(map);
+            }
+        }
+    }
+
+    /// Push operands in reverse order so the first one is on top of stack,
+    /// then apply the opcode.
+    fn lower(&self, code: &mut Code) {
+        match self {
+            Expr::Constant(value) => {
+                code.push(*value);
+            }
+            Expr::MLoad(ptr) => {
+                code.push(*ptr).mload();
+            }
+            Expr::CalldataLoad(offset) => {
+                code.push(*offset).calldataload();
+            }
+            Expr::CalldataSize => {
+                code.calldatasize();
+            }
+            Expr::Keccak { ptr, len } => {
+                code.push(*len).push(*ptr).keccak256();
+            }
+            Expr::Unary(op, value) => {
+                value.lower(code);
+                match op {
+                    UnaryOp::IsZero => code.iszero(),
+                    UnaryOp::Not => code.not(),
+                };
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                rhs.lower(code);
+                lhs.lower(code);
+                match op {
+                    BinaryOp::Add => code.add(),
+                    BinaryOp::Sub => code.sub(),
+                    BinaryOp::Mod => code.r#mod(),
+                    BinaryOp::Lt => code.lt(),
+                    BinaryOp::Eq => code.eq(),
+                    BinaryOp::And => code.and(),
+                    BinaryOp::Or => code.or(),
+                    BinaryOp::Xor => code.xor(),
+                    BinaryOp::Shl => code.shl(),
+                    BinaryOp::Shr => code.shr(),
+                };
+            }
+            Expr::AddMod(lhs, rhs, modulus) => {
+                modulus.lower(code);
+                rhs.lower(code);
+                lhs.lower(code);
+                code.addmod();
+            }
+            Expr::MulMod(lhs, rhs, modulus) => {
+                modulus.lower(code);
+                rhs.lower(code);
+                lhs.lower(code);
+                code.mulmod();
+            }
+        }
+    }
+
+    /// Format as Yul, where a leaf is formatted by `leaf` instead when it
+    /// returns `Some`.
+    pub fn fmt_with(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        leaf: &impl Fn(&Expr) -> Option<String>,
+    ) -> fmt::Result {
+        let call = |f: &mut fmt::Formatter<'_>, name: &str, operands: &[&Expr]| {
+            write!(f, "{}(", name)?;
+            for (idx, operand) in operands.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                operand.fmt_with(f, leaf)?;
+            }
+            write!(f, ")")
+        };
+        if matches!(
+            self,
+            Expr::Constant(_) | Expr::CalldataLoad(_) | Expr::CalldataSize
+        ) {
+            if let Some(text) = leaf(self) {
+                return write!(f, "{}", text);
+            }
+        }
+        match self {
+            Expr::Constant(value) => write!(f, "{:#x}", value),
+            Expr::MLoad(ptr) => write!(f, "mload({:#x})", ptr),
+            Expr::CalldataLoad(offset) => write!(f, "calldataload({:#x})", offset),
+            Expr::CalldataSize => write!(f, "calldatasize()"),
+            Expr::Keccak { ptr, len } => write!(f, "keccak256({:#x}, {:#x})", ptr, len),
+            Expr::Unary(op, value) => call(
+                f,
+                match op {
+                    UnaryOp::IsZero => "iszero",
+                    UnaryOp::Not => "not",
+                },
+                &[&**value],
+            ),
+            Expr::Binary(op, lhs, rhs) => call(
+                f,
+                match op {
+                    BinaryOp::Add => "add",
+                    BinaryOp::Sub => "sub",
+                    BinaryOp::Mod => "mod",
+                    BinaryOp::Lt => "lt",
+                    BinaryOp::Eq => "eq",
+                    BinaryOp::And => "and",
+                    BinaryOp::Or => "or",
+                    BinaryOp::Xor => "xor",
+                    BinaryOp::Shl => "shl",
+                    BinaryOp::Shr => "shr",
+                },
+                &[&**lhs, &**rhs],
+            ),
+            Expr::AddMod(lhs, rhs, modulus) => call(f, "addmod", &[&**lhs, &**rhs, &**modulus]),
+            Expr::MulMod(lhs, rhs, modulus) => call(f, "mulmod", &[&**lhs, &**rhs, &**modulus]),
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, &|_| None)
+    }
+}
+
+/// Statement of a program, which either writes memory or folds a condition
+/// into `success`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    MStore(usize, Expr),
+    MStore8(usize, Expr),
+    /// Fold `expr` into `success` by bitwise and.
+    Assert(Expr),
+    /// Call `precompile` with memory `[cd_ptr, cd_ptr + cd_len)` as input
+    /// and `[rd_ptr, rd_ptr + rd_len)` as output, and fold whether it
+    /// succeeds into `success`.
+    Precompile {
+        precompile: Precompiled,
+        cd_ptr: usize,
+        cd_len: usize,
+        rd_ptr: usize,
+        rd_len: usize,
+    },
+    /// Store inverse of `src` at `dst`, which is deferred to be resolved in
+    /// batch by [`Program::resolve_inversions`] before lowering.
+    Invert {
+        src: Expr,
+        dst: usize,
+    },
+    /// Copy `len` bytes of code of contract at `address` from `offset` to
+    /// memory at `ptr`.
+    ExtCodeCopy {
+        address: Address,
+        ptr: usize,
+        offset: usize,
+        len: usize,
+    },
+    /// Start metering gas of a section, which keeps gas left right below
+    /// `success` until the section ends.
+    StartGasMetering,
+    /// End metering gas of the latest started section, and emit its cost as
+    /// topic of `LOG1`.
+    EndGasMetering,
+}
+
+impl Op {
+    /// Visit memory words read by this op as `(ptr, len)`.
+    pub fn visit_memory_reads(&self, visit: &mut impl FnMut(usize, usize)) {
+        match self {
+            Op::MStore(_, expr)
+            | Op::MStore8(_, expr)
+            | Op::Assert(expr)
+            | Op::Invert { src: expr, .. } => expr.visit_memory_reads(visit),
+            Op::Precompile { cd_ptr, cd_len, .. } => visit(*cd_ptr, *cd_len),
+            Op::ExtCodeCopy { .. } => {}
+            Op::StartGasMetering | Op::EndGasMetering => {}
+        }
+    }
+
+    /// Returns memory written by this op as `(ptr, len)`.
+    pub fn memory_write(&self) -> Option<(usize, usize)> {
+        match self {
+            Op::MStore(ptr, _) | Op::Invert { dst: ptr, .. } => Some((*ptr, 0x20)),
+            Op::MStore8(ptr, _) => Some((*ptr, 1)),
+            Op::Precompile { rd_ptr, rd_len, .. } => Some((*rd_ptr, *rd_len)),
+            Op::ExtCodeCopy { ptr, len, .. } => Some((*ptr, *len)),
+            Op::Assert(_) => None,
+            Op::StartGasMetering | Op::EndGasMetering => None,
+        }
+    }
+
+    /// Replace each memory pointer `ptr` by `map(ptr)`.
+    pub fn map_ptrs(&mut self, map: &impl Fn(usize) -> usize) {
+        match self {
+            Op::MStore(ptr, expr)
+            | Op::MStore8(ptr, expr)
+            | Op::Invert {
+                src: expr,
+                dst: ptr,
+            } => {
+                *ptr = map(*ptr);
+                expr.map_ptrs(map);
+            }
+            Op::Assert(expr) => expr.map_ptrs(map),
+            Op::Precompile { cd_ptr, rd_ptr, .. } => {
+                *cd_ptr = map(*cd_ptr);
+                *rd_ptr = map(*rd_ptr);
+            }
+            Op::ExtCodeCopy { ptr, .. } => *ptr = map(*ptr),
+            Op::StartGasMetering | Op::EndGasMetering => {}
+        }
+    }
+
+    /// Replace each constant `value` in expressions by `map(value)` when it
+    /// returns `Some`.
+    pub fn map_constants(&mut self, map: &mut impl FnMut(U256) -> Option<Expr>) {
+        match self {
+            Op::MStore(_, expr)
+            | Op::MStore8(_, expr)
+            | Op::Assert(expr)
+            | Op::Invert { src: expr, .. } => expr.map_constants(map),
+            Op::Precompile { .. } | Op::ExtCodeCopy { .. } => {}
+            Op::StartGasMetering | Op::EndGasMetering => {}
+        }
+    }
+
+    fn lower(&self, code: &mut Code) {
+        match self {
+            Op::MStore(ptr, expr) => {
+                expr.lower(code);
+                code.push(*ptr).mstore();
+            }
+            Op::MStore8(ptr, expr) => {
+                expr.lower(code);
+                code.push(*ptr).mstore8();
+            }
+            Op::Invert { .. } => {
+                unreachable!("Deferred inversion should be resolved before lowering")
+            }
+            Op::Assert(expr) => {
+                // [..., success]
+                expr.lower(code);
+                // [..., success, expr]
+                code.and();
+                // [..., success]
+            }
+            Op::Precompile {
+                precompile,
+                cd_ptr,
+                cd_len,
+                rd_ptr,
+                rd_len,
+            } => {
+                code.push(*rd_len)
+                    .push(*rd_ptr)
+                    .push(*cd_len)
+                    .push(*cd_ptr)
+                    .push(*precompile as usize)
+                    .gas()
+                    .staticcall()
+                    .and();
+            }
+            Op::ExtCodeCopy {
+                address,
+                ptr,
+                offset,
+                len,
+            } => {
+                code.push(*len)
+                    .push(*offset)
+                    .push(*ptr)
+                    .push(U256::from_big_endian(address.as_bytes()))
+                    .extcodecopy();
+            }
+            Op::StartGasMetering => {
+                // [..., success]
+                code.gas().swap(1);
+                // [..., gas, success]
+            }
+            Op::EndGasMetering => {
+                code
+                    // [..., gas, success]
+                    .swap(1)
+                    // [..., success, gas]
+                    .push(9)
+                    .gas()
+                    .swap(2)
+                    .sub()
+                    .sub()
+                    // [..., success, cost]
+                    .push(0)
+                    .push(0)
+                    .log1();
+                // [..., success]
+            }
+        }
+    }
+
+    /// Format as Yul statement with leaves of expressions formatted by `leaf`
+    /// as [`Expr::fmt_with`], where gas metering is omitted.
+    pub fn fmt_with(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        leaf: &impl Fn(&Expr) -> Option<String>,
+    ) -> fmt::Result {
+        match self {
+            Op::MStore(ptr, expr) | Op::MStore8(ptr, expr) => {
+                let name = if matches!(self, Op::MStore(..)) {
+                    "mstore"
+                } else {
+                    "mstore8"
+                };
+                write!(f, "{}({:#x}, ", name, ptr)?;
+                expr.fmt_with(f, leaf)?;
+                write!(f, ")")
+            }
+            Op::Invert { src, dst } => {
+                write!(f, "// mstore({:#x}, invert(", dst)?;
+                src.fmt_with(f, leaf)?;
+                write!(f, "))")
+            }
+            Op::Assert(expr) => {
+                write!(f, "success := and(success, ")?;
+                expr.fmt_with(f, leaf)?;
+                write!(f, ")")
+            }
+            Op::Precompile {
+                precompile,
+                cd_ptr,
+                cd_len,
+                rd_ptr,
+                rd_len,
+            } => write!(
+                f,
+                "success := and(success, staticcall(gas(), {:#x}, {:#x}, {:#x}, {:#x}, {:#x}))",
+                *precompile as usize, cd_ptr, cd_len, rd_ptr, rd_len
+            ),
+            Op::ExtCodeCopy {
+                address,
+                ptr,
+                offset,
+                len,
+            } => write!(
+                f,
+                "extcodecopy({:#x}, {:#x}, {:#x}, {:#x})",
+                address, ptr, offset, len
+            ),
+            Op::StartGasMetering | Op::EndGasMetering => Ok(()),
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, &|_| None)
+    }
+}
+
+/// Straight-line program of ops over memory, with a `success` flag which is
+/// initially `1`. It's lowered to bytecode with `constants` kept at the bottom
+/// of stack to be duplicated instead of pushed, then `success` on top of them.
+#[derive(Clone, Debug)]
+pub struct Program {
+    constants: Vec<U256>,
+    ops: Vec<Op>,
+}
+
+impl Program {
+    pub fn new(constants: impl IntoIterator<Item = U256>) -> Self {
+        Self {
+            constants: constants.into_iter().collect(),
+            ops: Vec::new(),
+        }
+    }
+
+    pub fn constants(&self) -> &[U256] {
+        &self.constants
+    }
+
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    pub fn ops_mut(&mut self) -> &mut Vec<Op> {
+        &mut self.ops
+    }
+
+    pub fn push(&mut self, op: Op) -> &mut Self {
+        self.ops.push(op);
+        self
+    }
+
+    /// Returns size of memory touched by ops, rounded up to words.
+    pub fn memory_size(&self) -> usize {
+        let end = self
+            .ops
+            .iter()
+            .flat_map(|op| {
+                let mut ends = Vec::new();
+                op.visit_memory_reads(&mut |ptr, len| ends.push(ptr + len));
+                ends.extend(op.memory_write().map(|(ptr, len)| ptr + len));
+                ends
+            })
+            .max()
+            .unwrap_or_default();
+        (end + 0x1f) & !0x1f
+    }
+
+    pub(super) fn lower(&self) -> Code {
+        let mut code = Code::new(self.constants.iter().copied());
+        code.push(1);
+        for op in self.ops.iter() {
+            op.lower(&mut code);
+        }
+        code
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for op in self.ops.iter() {
+            writeln!(f, "{}", op)?;
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,22 @@
+#[cfg(feature = "simulate")]
+use crate::loader::evm::{profile::GasProfile, simulate::simulate};
 use crate::{
     loader::evm::{
-        code::{Code, Precompiled},
+        code::Precompiled,
+        curve::EvmCurve,
+        field_to_u256,
+        ir::{Expr, Op, Program},
+        memory::MemoryReport,
         modulus,
+        solidity::solidity,
+        u256_to_field, verify_selector,
     },
-    loader::{EcPointLoader, LoadedEcPoint, LoadedScalar, Loader, ScalarLoader},
-    util::{Curve, FieldOps, PrimeField, UncompressedEncoding},
+    loader::{EcPointLoader, LoadedEcPoint, LoadedScalar, Loader, MsmFolding, ScalarLoader},
+    protocol::Protocol,
+    util::{Curve, FieldOps, GroupEncoding, PrimeField, UncompressedEncoding},
+    Error,
 };
-use ethereum_types::{U256, U512};
+use ethereum_types::{Address, U256, U512};
 use std::{
     cell::RefCell,
     fmt::{self, Debug},
@@ -15,55 +25,395 @@ use std::{
     rc::Rc,
 };
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Value<T> {
     Constant(T),
     Memory(usize),
 }
 
+/// Layout of calldata read by code generated by [`EvmLoader`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalldataLayout {
+    /// Instances followed by proof, as encoded by
+    /// [`encode_calldata`](super::encode_calldata). The code stops without
+    /// return data on success.
+    Raw,
+    /// ABI encoded `(uint256[] instances, bytes proof)` in canonical form with
+    /// `num_instances` instances, optionally prefixed by a 4-byte `selector`,
+    /// as encoded by [`encode_abi_calldata`](super::encode_abi_calldata). The
+    /// code returns ABI encoded `true` on success and `false` on failure,
+    /// including calldata with another selector or a malformed head.
+    Abi {
+        selector: Option<[u8; 4]>,
+        num_instances: usize,
+    },
+}
+
 #[derive(Clone, Debug)]
 pub struct EvmLoader {
-    base_modulus: U256,
+    curve: EvmCurve,
     scalar_modulus: U256,
-    code: RefCell<Code>,
+    calldata_layout: CalldataLayout,
+    calldata_len: RefCell<usize>,
+    program: RefCell<Program>,
     ptr: RefCell<usize>,
-    #[cfg(test)]
-    gas_metering_ids: RefCell<Vec<String>>,
+    msm_folding: RefCell<MsmFolding>,
+    deferred_inversion: RefCell<bool>,
+    constant_names: RefCell<Vec<(String, U256)>>,
+    named_protocols: RefCell<Vec<U256>>,
+    gas_profiling: RefCell<bool>,
+    gas_metering_paths: RefCell<Vec<Vec<String>>>,
+    gas_metering_stack: RefCell<Vec<usize>>,
+    gas_metering_ends: RefCell<Vec<usize>>,
 }
 
 impl EvmLoader {
+    /// Create a loader of [`EvmCurve::BN254`] with moduli of `Base` and
+    /// `Scalar`, whose code reads calldata in [`CalldataLayout::Raw`].
     pub fn new<Base, Scalar>() -> Rc<Self>
     where
         Base: PrimeField<Repr = [u8; 32]>,
         Scalar: PrimeField<Repr = [u8; 32]>,
     {
-        let base_modulus = modulus::<Base>();
-        let scalar_modulus = modulus::<Scalar>();
-        let code = Code::new([1.into(), base_modulus, scalar_modulus - 1, scalar_modulus])
-            .push(1)
-            .to_owned();
+        Self::new_with_calldata_layout::<Base, Scalar>(CalldataLayout::Raw)
+    }
+
+    /// Create a loader of [`EvmCurve::BN254`] with moduli of `Base` and
+    /// `Scalar`, whose code reads calldata in `calldata_layout`, while offsets
+    /// taken by `calldataload_*` are still the ones in [`CalldataLayout::Raw`].
+    /// Other curves are created by [`EvmLoader::new_with_curve`].
+    pub fn new_with_calldata_layout<Base, Scalar>(calldata_layout: CalldataLayout) -> Rc<Self>
+    where
+        Base: PrimeField<Repr = [u8; 32]>,
+        Scalar: PrimeField<Repr = [u8; 32]>,
+    {
+        Self::new_with_curve(
+            EvmCurve {
+                base_modulus: Some(modulus::<Base>()),
+                scalar_modulus: modulus::<Scalar>(),
+                ..EvmCurve::BN254
+            },
+            calldata_layout,
+        )
+    }
+
+    /// Create a loader whose code verifies over `curve` with its precompiles,
+    /// and reads calldata in `calldata_layout`.
+    ///
+    /// Converting limbs or compressed encoding to points, and validating
+    /// points without precompiles, require coordinates of `curve` to fit in a
+    /// word.
+    pub fn new_with_curve(curve: EvmCurve, calldata_layout: CalldataLayout) -> Rc<Self> {
+        let scalar_modulus = curve.scalar_modulus;
+        let program = Program::new(
+            iter::empty()
+                .chain([1.into()])
+                .chain(curve.base_modulus)
+                .chain([scalar_modulus - 1, scalar_modulus]),
+        );
         Rc::new(Self {
-            base_modulus,
+            curve,
             scalar_modulus,
-            code: RefCell::new(code),
+            calldata_layout,
+            calldata_len: RefCell::new(0),
+            program: RefCell::new(program),
             ptr: RefCell::new(0),
-            #[cfg(test)]
-            gas_metering_ids: RefCell::new(Vec::new()),
+            msm_folding: RefCell::new(MsmFolding::default()),
+            deferred_inversion: RefCell::new(false),
+            constant_names: RefCell::new(
+                iter::empty()
+                    .chain(
+                        curve
+                            .base_modulus
+                            .map(|base_modulus| ("BASE_MODULUS".to_string(), base_modulus)),
+                    )
+                    .chain([("SCALAR_MODULUS".to_string(), scalar_modulus)])
+                    .collect(),
+            ),
+            named_protocols: RefCell::new(Vec::new()),
+            gas_profiling: RefCell::new(false),
+            gas_metering_paths: RefCell::new(Vec::new()),
+            gas_metering_stack: RefCell::new(Vec::new()),
+            gas_metering_ends: RefCell::new(Vec::new()),
         })
     }
 
+    /// Defer inversions requested afterwards, which are collected and
+    /// resolved in batches by [`Program::resolve_inversions`], and fail
+    /// verification when any inverted value is zero.
+    pub fn enable_deferred_inversion(&self) {
+        *self.deferred_inversion.borrow_mut() = true;
+    }
+
+    /// Meter gas of sections started afterwards by
+    /// [`Loader::start_cost_metering`], which are nested when started before
+    /// the enclosing one ends, to be reported by [`EvmLoader::gas_profile`].
+    /// Each section costs extra gas for metering, so it's disabled by default.
+    pub fn enable_gas_profiling(&self) {
+        *self.gas_profiling.borrow_mut() = true;
+    }
+
+    /// Returns the program recorded so far, which is lowered to bytecode by
+    /// [`EvmLoader::code`] after resolving deferred inversions and reusing
+    /// memory.
+    pub fn program(&self) -> Program {
+        self.program.borrow().clone()
+    }
+
+    fn program_with_inversions_resolved(&self) -> Program {
+        self.program
+            .borrow()
+            .resolve_inversions(self.scalar_modulus)
+    }
+
+    /// Returns peak memory of the program before and after reusing memory.
+    pub fn memory_report(&self) -> MemoryReport {
+        self.program_with_inversions_resolved().reuse_memory().1
+    }
+
     pub fn code(self: &Rc<Self>) -> Vec<u8> {
-        let mut code = self.code.borrow().clone();
-        let dst = code.len() + 9;
-        code.push(dst)
-            .jumpi()
-            .push(0)
-            .push(0)
-            .revert()
-            .jumpdest()
-            .stop()
-            .to_owned()
-            .into()
+        self.code_from_program(self.program_with_inversions_resolved().reuse_memory().0)
+    }
+
+    /// Returns size of [`EvmLoader::code`], which can't be deployed when it
+    /// exceeds [`CODE_SIZE_LIMIT`](super::CODE_SIZE_LIMIT), in which case
+    /// [`EvmLoader::code_with_data_contract`] can be deployed instead.
+    pub fn code_size(self: &Rc<Self>) -> usize {
+        self.code().len()
+    }
+
+    /// Returns runtime code of the data contract holding large constants of
+    /// the program, to be deployed before the code returned by
+    /// [`EvmLoader::code_with_data_contract`], which fails if it exceeds
+    /// [`CODE_SIZE_LIMIT`](super::CODE_SIZE_LIMIT).
+    pub fn data_contract_code(self: &Rc<Self>) -> Result<Vec<u8>, Error> {
+        Ok(self
+            .program_with_inversions_resolved()
+            .reuse_memory()
+            .0
+            .split_constants(Address::zero())?
+            .1)
+    }
+
+    /// Returns code reading large constants from the data contract deployed
+    /// at `address` with [`EvmLoader::data_contract_code`], as split by
+    /// [`Program::split_constants`], which is smaller than
+    /// [`EvmLoader::code`] at the cost of an `extcodecopy`, which fails like
+    /// [`EvmLoader::data_contract_code`].
+    pub fn code_with_data_contract(self: &Rc<Self>, address: Address) -> Result<Vec<u8>, Error> {
+        Ok(self.code_from_program(
+            self.program_with_inversions_resolved()
+                .reuse_memory()
+                .0
+                .split_constants(address)?
+                .0,
+        ))
+    }
+
+    /// Returns code without reusing memory, to measure the gas saved by it.
+    #[cfg(test)]
+    pub fn code_without_memory_reuse(self: &Rc<Self>) -> Vec<u8> {
+        self.code_from_program(self.program_with_inversions_resolved())
+    }
+
+    /// Returns code of `program`, e.g. an altered [`EvmLoader::program`],
+    /// lowered the same way as [`EvmLoader::code`].
+    #[cfg(test)]
+    pub fn code_with_program(self: &Rc<Self>, program: Program) -> Vec<u8> {
+        self.code_from_program(
+            program
+                .resolve_inversions(self.scalar_modulus)
+                .reuse_memory()
+                .0,
+        )
+    }
+
+    fn code_from_program(&self, program: Program) -> Vec<u8> {
+        let mut code = self.with_calldata_checks(program).lower();
+        match self.calldata_layout {
+            CalldataLayout::Raw => {
+                let dst = code.len() + 9;
+                code.push(dst)
+                    .jumpi()
+                    .push(0)
+                    .push(0)
+                    .revert()
+                    .jumpdest()
+                    .stop()
+            }
+            // [success]
+            CalldataLayout::Abi { .. } => code.push(0).mstore().push(0x20).push(0).r#return(),
+        }
+        .to_owned()
+        .into()
+    }
+
+    /// Returns `program` followed by [`EvmLoader::abi_header_checks`] when
+    /// reading calldata in [`CalldataLayout::Abi`].
+    fn with_calldata_checks(&self, mut program: Program) -> Program {
+        if let CalldataLayout::Abi {
+            selector,
+            num_instances,
+        } = self.calldata_layout
+        {
+            program.ops_mut().extend(Self::abi_header_checks(
+                selector,
+                num_instances,
+                *self.calldata_len.borrow(),
+            ));
+        }
+        program
+    }
+
+    /// Returns checks that `selector` matches, and the head of
+    /// `(uint256[] instances, bytes proof)` is in canonical form with
+    /// `num_instances` instances and proof of the length read, and calldata is
+    /// long enough to contain it.
+    fn abi_header_checks(
+        selector: Option<[u8; 4]>,
+        num_instances: usize,
+        calldata_len: usize,
+    ) -> Vec<Op> {
+        let base = if selector.is_some() { 4 } else { 0 };
+        let proof_len = calldata_len.saturating_sub(num_instances * 0x20);
+        selector
+            .map(|selector| {
+                Expr::eq(
+                    Expr::Constant(U256::from_big_endian(&selector)),
+                    Expr::shr(Expr::constant(224), Expr::CalldataLoad(0)),
+                )
+            })
+            .into_iter()
+            .chain(
+                [
+                    (0, 0x40),
+                    (0x20, 0x60 + num_instances * 0x20),
+                    (0x40, num_instances),
+                    (0x60 + num_instances * 0x20, proof_len),
+                ]
+                .into_iter()
+                .map(|(offset, value)| {
+                    Expr::eq(Expr::constant(value), Expr::CalldataLoad(base + offset))
+                }),
+            )
+            .chain(iter::once(Expr::iszero(Expr::lt(
+                Expr::CalldataSize,
+                Expr::constant(base + 0x80 + num_instances * 0x20 + proof_len),
+            ))))
+            .map(Op::Assert)
+            .collect()
+    }
+
+    /// Returns offset in calldata of the word at `offset` in
+    /// [`CalldataLayout::Raw`], and records the length of calldata read.
+    fn calldata_offset(&self, offset: usize) -> usize {
+        let mut calldata_len = self.calldata_len.borrow_mut();
+        *calldata_len = (*calldata_len).max(offset + 0x20);
+        match self.calldata_layout {
+            CalldataLayout::Raw => offset,
+            CalldataLayout::Abi {
+                selector,
+                num_instances,
+            } => {
+                let base = if selector.is_some() { 4 } else { 0 };
+                let instances_len = num_instances * 0x20;
+                if offset < instances_len {
+                    assert!(offset + 0x20 <= instances_len);
+                    base + 0x60 + offset
+                } else {
+                    base + 0x80 + offset
+                }
+            }
+        }
+    }
+
+    /// Returns Solidity contract having the same semantics as
+    /// [`EvmLoader::code`].
+    ///
+    /// With [`CalldataLayout::Raw`], it has `verify(bytes calldata proof)`
+    /// taking calldata of the code as `proof`, which returns `true` or reverts.
+    /// With [`CalldataLayout::Abi`], which requires the selector to be
+    /// [`verify_selector`](super::verify_selector), it has
+    /// [`VERIFY_SIGNATURE`](super::VERIFY_SIGNATURE) returning `true` or
+    /// `false`, except calldata rejected by the ABI decoder of Solidity which
+    /// reverts.
+    pub fn solidity(self: &Rc<Self>) -> String {
+        if let CalldataLayout::Abi { selector, .. } = self.calldata_layout {
+            assert_eq!(selector, Some(verify_selector()));
+        }
+        solidity(
+            &self.with_calldata_checks(self.program_with_inversions_resolved().reuse_memory().0),
+            &self.constant_names.borrow(),
+            self.calldata_layout,
+        )
+    }
+
+    /// Name constant `value` as `name` in generated Solidity, unless `value`
+    /// is already named.
+    pub fn name_constant(&self, name: impl Into<String>, value: U256) {
+        let mut constant_names = self.constant_names.borrow_mut();
+        if !constant_names.iter().any(|(_, named)| *named == value) {
+            constant_names.push((name.into(), value));
+        }
+    }
+
+    /// Name constants of `protocol` in generated Solidity, where constants of
+    /// protocols other than the first one are prefixed by `PROTOCOL_{idx}_`.
+    pub fn name_protocol_constants<C>(&self, protocol: &Protocol<C>)
+    where
+        C: Curve + GroupEncoding + UncompressedEncoding,
+        C::Scalar: PrimeField<Repr = [u8; 32]>,
+    {
+        let digest = field_to_u256(&protocol.digest());
+        let idx = {
+            let mut named_protocols = self.named_protocols.borrow_mut();
+            if named_protocols.contains(&digest) {
+                return;
+            }
+            named_protocols.push(digest);
+            named_protocols.len() - 1
+        };
+        let prefix = if idx == 0 {
+            String::new()
+        } else {
+            format!("PROTOCOL_{}_", idx)
+        };
+
+        for (name, value) in [
+            (
+                "TRANSCRIPT_INITIAL_STATE",
+                &protocol.transcript_initial_state,
+            ),
+            ("OMEGA", &protocol.domain.gen),
+            ("OMEGA_INV", &protocol.domain.gen_inv),
+            ("N_INV", &protocol.domain.n_inv),
+        ] {
+            self.name_constant(format!("{}{}", prefix, name), field_to_u256(value));
+        }
+        for (idx, ec_point) in protocol.preprocessed.iter().enumerate() {
+            let (x, y) = self.coordinates(ec_point);
+            for (axis, coordinate) in [("X", x), ("Y", y)] {
+                let words = self.coordinate_words(coordinate);
+                for (word_idx, word) in words.iter().enumerate() {
+                    let name = if words.len() == 1 {
+                        format!("{}PREPROCESSED_{}_{}", prefix, idx, axis)
+                    } else {
+                        format!("{}PREPROCESSED_{}_{}_{}", prefix, idx, axis, word_idx)
+                    };
+                    self.name_constant(name, *word);
+                }
+            }
+        }
+    }
+
+    pub fn curve(&self) -> EvmCurve {
+        self.curve
+    }
+
+    /// Returns `ecMul` and `ecAdd` precompile calls saved by folding constant
+    /// terms of msm at code generation time.
+    pub fn msm_folding(&self) -> MsmFolding {
+        *self.msm_folding.borrow()
     }
 
     pub fn allocate(self: &Rc<Self>, size: usize) -> usize {
@@ -72,6 +422,22 @@ impl EvmLoader {
         ptr
     }
 
+    /// Returns pointer of memory to be allocated next.
+    pub fn ptr(&self) -> usize {
+        *self.ptr.borrow()
+    }
+
+    /// Copy `len` bytes at `ptr` word by word to newly allocated memory, and
+    /// returns pointer of the copy.
+    pub fn copy_memory(self: &Rc<Self>, ptr: usize, len: usize) -> usize {
+        assert_eq!(len % 0x20, 0);
+        let dst = self.allocate(len);
+        for offset in (0..len).step_by(0x20) {
+            self.mstore(dst + offset, Expr::MLoad(ptr + offset));
+        }
+        dst
+    }
+
     fn scalar(self: &Rc<Self>, value: Value<U256>) -> Scalar {
         Scalar {
             loader: self.clone(),
@@ -79,183 +445,275 @@ impl EvmLoader {
         }
     }
 
-    fn ec_point(self: &Rc<Self>, value: Value<(U256, U256)>) -> EcPoint {
+    fn ec_point(self: &Rc<Self>, value: Value<(U512, U512)>) -> EcPoint {
         EcPoint {
             loader: self.clone(),
             value,
         }
     }
 
-    fn push(self: &Rc<Self>, scalar: &Scalar) {
+    fn op(&self, op: Op) {
+        self.program.borrow_mut().push(op);
+    }
+
+    fn mstore(&self, ptr: usize, expr: Expr) {
+        self.op(Op::MStore(ptr, expr));
+    }
+
+    fn assert(&self, expr: Expr) {
+        self.op(Op::Assert(expr));
+    }
+
+    fn expr(&self, scalar: &Scalar) -> Expr {
         match scalar.value {
-            Value::Constant(constant) => {
-                self.code.borrow_mut().push(constant);
-            }
-            Value::Memory(ptr) => {
-                self.code.borrow_mut().push(ptr).mload();
-            }
+            Value::Constant(constant) => Expr::Constant(constant),
+            Value::Memory(ptr) => Expr::MLoad(ptr),
         }
     }
 
+    /// Returns modulus of base field, which panics when it doesn't fit in a
+    /// word.
+    fn base_modulus_value(&self) -> U256 {
+        self.curve
+            .base_modulus
+            .expect("Base field arithmetic requires coordinates fitting in a word")
+    }
+
+    fn base_modulus(&self) -> Expr {
+        Expr::Constant(self.base_modulus_value())
+    }
+
+    fn scalar_modulus(&self) -> Expr {
+        Expr::Constant(self.scalar_modulus)
+    }
+
     pub fn calldataload_scalar(self: &Rc<Self>, offset: usize) -> Scalar {
         let ptr = self.allocate(0x20);
-        self.code
-            .borrow_mut()
-            .push(self.scalar_modulus)
-            .push(offset)
-            .calldataload()
-            .r#mod()
-            .push(ptr)
-            .mstore();
+        let offset = self.calldata_offset(offset);
+        self.mstore(
+            ptr,
+            Expr::r#mod(Expr::CalldataLoad(offset), self.scalar_modulus()),
+        );
         self.scalar(Value::Memory(ptr))
     }
 
+    /// Load point encoded as input of precompiles of the curve from calldata
+    /// at `offset`, which takes [`EvmCurve::ec_point_size`] bytes.
     pub fn calldataload_ec_point(self: &Rc<Self>, offset: usize) -> EcPoint {
+        let ptr = self.allocate(self.curve.ec_point_size());
+        for word in (0..self.curve.ec_point_size()).step_by(0x20) {
+            let calldata_offset = self.calldata_offset(offset + word);
+            self.mstore(ptr + word, Expr::CalldataLoad(calldata_offset));
+        }
+        self.validate_ec_point(ptr);
+        self.ec_point(Value::Memory(ptr))
+    }
+
+    /// Load constant point of coordinates `(x, y)`, which is how constants of a
+    /// curve without a type implementing [`UncompressedEncoding`] are loaded,
+    /// e.g. ones of [`EvmCurve::BLS12_381`].
+    pub fn ec_point_load_coordinates(self: &Rc<Self>, x: U512, y: U512) -> EcPoint {
+        self.ec_point(Value::Constant((x, y)))
+    }
+
+    pub fn ec_point_from_limbs<const LIMBS: usize, const BITS: usize>(
+        self: &Rc<Self>,
+        x_limbs: &[Scalar],
+        y_limbs: &[Scalar],
+    ) -> EcPoint {
+        assert_eq!(x_limbs.len(), LIMBS);
+        assert_eq!(y_limbs.len(), LIMBS);
+        assert!(self.curve.is_word_sized());
+
         let ptr = self.allocate(0x40);
-        self.code
-            .borrow_mut()
-            // [..., success]
-            .push(offset)
-            // [..., success, x_cd_ptr]
-            .calldataload()
-            // [..., success, x]
-            .dup(0)
-            // [..., success, x, x]
-            .push(ptr)
-            // [..., success, x, x, x_ptr]
-            .mstore()
-            // [..., success, x]
-            .push(offset + 0x20)
-            // [..., success, x, y_cd_ptr]
-            .calldataload()
-            // [..., success, x, y]
-            .dup(0)
-            // [..., success, x, y, y]
-            .push(ptr + 0x20)
-            // [..., success, x, y, y, y_ptr]
-            .mstore();
-        // [..., success, x, y]
-        self.validate_ec_point();
+        for (ptr, limbs) in [(ptr, x_limbs), (ptr + 0x20, y_limbs)] {
+            self.range_check_limbs::<BITS>(limbs);
+            self.mstore(ptr, self.compose_limbs::<BITS>(limbs));
+        }
+        self.validate_ec_point(ptr);
         self.ec_point(Value::Memory(ptr))
     }
 
-    pub fn calldataload_ec_point_from_limbs<const LIMBS: usize, const BITS: usize>(
+    /// Decompress point from limbs of `x` and `sign`, the parity of `y`, by
+    /// computing `y` as `(x^3 + b)^((p + 1) / 4)` with `BigModExp` and
+    /// negating it when its parity doesn't match `sign`.
+    pub fn ec_point_from_compressed<const LIMBS: usize, const BITS: usize>(
         self: &Rc<Self>,
-        offset: usize,
+        x_limbs: &[Scalar],
+        sign: &Scalar,
     ) -> EcPoint {
+        assert_eq!(x_limbs.len(), LIMBS);
+        let base_modulus = self.base_modulus_value();
+        assert_eq!(base_modulus % U256::from(4), U256::from(3));
+
         let ptr = self.allocate(0x40);
-        for (ptr, offset) in [(ptr, offset), (ptr + 0x20, offset + LIMBS * 0x20)] {
-            for idx in 0..LIMBS {
-                if idx == 0 {
-                    self.code
-                        .borrow_mut()
-                        // [..., success]
-                        .push(offset)
-                        // [..., success, x_limb_0_ptr]
-                        .calldataload();
-                    // [..., success, x_limb_0]
-                } else {
-                    self.code
-                        .borrow_mut()
-                        // [..., success, x_acc]
-                        .push(offset + idx * 0x20)
-                        // [..., success, x_acc, x_limb_i_ptr]
-                        .calldataload()
-                        // [..., success, x_acc, x_limb_i]
-                        .push(idx * BITS)
-                        // [..., success, x_acc, x_limb_i, shift]
-                        .shl()
-                        // [..., success, x_acc, x_limb_i << shift]
-                        .add();
-                    // [..., success, x_acc]
-                }
-            }
-            self.code
-                .borrow_mut()
-                // [..., success, x]
-                .dup(0)
-                // [..., success, x, x]
-                .push(ptr)
-                // [..., success, x, x, x_ptr]
-                .mstore();
-            // [..., success, x]
-        }
-        // [..., success, x, y]
-        self.validate_ec_point();
+        let cd_ptr = self.allocate(0xc0);
+
+        self.assert(Expr::lt(self.expr(sign), Expr::constant(2)));
+        self.range_check_limbs::<BITS>(x_limbs);
+        self.mstore(ptr, self.compose_limbs::<BITS>(x_limbs));
+        self.mstore(cd_ptr + 0x60, self.x_cube_plus_b(Expr::MLoad(ptr)));
+        for (offset, value) in [
+            (0, U256::from(0x20)),
+            (0x20, U256::from(0x20)),
+            (0x40, U256::from(0x20)),
+            (0x80, (base_modulus + U256::one()) / U256::from(4)),
+            (0xa0, base_modulus),
+        ] {
+            self.mstore(cd_ptr + offset, Expr::Constant(value));
+        }
+        self.staticcall(Precompiled::BigModExp, cd_ptr, ptr + 0x20);
+
+        // Mask `y ^ (p - y)` by whether parity of `y` differs from `sign`,
+        // then xor it with `y` to select `y` or `p - y`.
+        let y = || Expr::MLoad(ptr + 0x20);
+        let mask = Expr::sub(
+            Expr::constant(0),
+            Expr::xor(Expr::and(y(), Expr::constant(1)), self.expr(sign)),
+        );
+        self.mstore(
+            ptr + 0x20,
+            Expr::xor(
+                y(),
+                Expr::and(Expr::xor(y(), Expr::sub(self.base_modulus(), y())), mask),
+            ),
+        );
+        self.validate_ec_point(ptr);
         self.ec_point(Value::Memory(ptr))
     }
 
-    fn validate_ec_point(self: &Rc<Self>) {
-        self.code
-            .borrow_mut()
-            // [..., success, x, y]
-            .push(self.base_modulus)
-            // [..., success, x, y, p]
-            .dup(2)
-            // [..., success, x, y, p, x]
-            .lt()
-            // [..., success, x, y, x_lt_p]
-            .push(self.base_modulus)
-            // [..., success, x, y, x_lt_p, p]
-            .dup(2)
-            // [..., success, x, y, x_lt_p, p, y]
-            .lt()
-            // [..., success, x, y, x_lt_p, y_lt_p]
-            .and()
-            // [..., success, x, y, valid]
-            .dup(2)
-            // [..., success, x, y, valid, x]
-            .iszero()
-            // [..., success, x, y, valid, x_is_zero]
-            .dup(2)
-            // [..., success, x, y, valid, x_is_zero, y]
-            .iszero()
-            // [..., success, x, y, valid, x_is_zero, y_is_zero]
-            .or()
-            // [..., success, x, y, valid, x_or_y_is_zero]
-            .not()
-            // [..., success, x, y, valid, x_and_y_is_not_zero]
-            .and()
-            // [..., success, x, y, valid]
-            .push(self.base_modulus)
-            // [..., success, x, y, valid, p]
-            .dup(2)
-            // [..., success, x, y, valid, p, y]
-            .dup(0)
-            // [..., success, x, y, valid, p, y, y]
-            .mulmod()
-            // [..., success, x, y, valid, y_square]
-            .push(self.base_modulus)
-            // [..., success, x, y, valid, y_square, p]
-            .push(3)
-            // [..., success, x, y, valid, y_square, p, 3]
-            .push(self.base_modulus)
-            // [..., success, x, y, valid, y_square, p, 3, p]
-            .dup(6)
-            // [..., success, x, y, valid, y_square, p, 3, p, x]
-            .push(self.base_modulus)
-            // [..., success, x, y, valid, y_square, p, 3, p, x, p]
-            .dup(1)
-            // [..., success, x, y, valid, y_square, p, 3, p, x, p, x]
-            .dup(0)
-            // [..., success, x, y, valid, y_square, p, 3, p, x, p, x, x]
-            .mulmod()
-            // [..., success, x, y, valid, y_square, p, 3, p, x, x_square]
-            .mulmod()
-            // [..., success, x, y, valid, y_square, p, 3, x_cube]
-            .addmod()
-            // [..., success, x, y, valid, y_square, x_cube_plus_3]
-            .eq()
-            // [..., success, x, y, valid, y_square_eq_x_cube_plus_3]
-            .and()
-            // [..., success, x, y, valid]
-            .swap(2)
-            // [..., success, valid, y, x]
-            .pop()
-            // [..., success, valid, y]
-            .pop()
-            // [..., success, valid]
-            .and();
+    /// Fold equality of `lhs` and `rhs` into `success`.
+    pub fn assert_scalar_eq(self: &Rc<Self>, lhs: &Scalar, rhs: &Scalar) {
+        self.assert(Expr::eq(self.expr(lhs), self.expr(rhs)));
+    }
+
+    /// Fold equality of `lhs` and `rhs` into `success`.
+    pub fn assert_ec_point_eq(self: &Rc<Self>, lhs: &EcPoint, rhs: &EcPoint) {
+        for (lhs, rhs) in self
+            .ec_point_words(lhs)
+            .into_iter()
+            .zip(self.ec_point_words(rhs))
+        {
+            self.assert(Expr::eq(lhs, rhs));
+        }
+    }
+
+    /// Coordinates of `ec_point` from its uncompressed encoding, whose halves
+    /// are `x` and `y` in little-endian and fit in
+    /// [`EvmCurve::coordinate_size`] bytes.
+    fn coordinates<C: UncompressedEncoding>(&self, ec_point: &C) -> (U512, U512) {
+        let uncompressed = ec_point.to_uncompressed();
+        let bytes = uncompressed.as_ref();
+        let size = bytes.len() / 2;
+        assert!(size <= self.curve.coordinate_size);
+        (
+            U512::from_little_endian(&bytes[..size]),
+            U512::from_little_endian(&bytes[size..]),
+        )
+    }
+
+    /// Words of coordinate `value` padded to [`EvmCurve::coordinate_size`]
+    /// bytes in big-endian.
+    fn coordinate_words(&self, value: U512) -> Vec<U256> {
+        (0..self.curve.coordinate_size / 0x20)
+            .rev()
+            .map(|idx| {
+                ((value >> (idx * 256)) & U512::from(U256::MAX))
+                    .try_into()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    /// Words of `ec_point` encoded as input of precompiles of the curve.
+    fn ec_point_words(&self, ec_point: &EcPoint) -> Vec<Expr> {
+        match ec_point.value {
+            Value::Constant((x, y)) => [x, y]
+                .into_iter()
+                .flat_map(|coordinate| self.coordinate_words(coordinate))
+                .map(Expr::Constant)
+                .collect(),
+            Value::Memory(ptr) => (ptr..ptr + self.curve.ec_point_size())
+                .step_by(0x20)
+                .map(Expr::MLoad)
+                .collect(),
+        }
+    }
+
+    /// Fold into `success` that each limb has at most `BITS` bits, and the
+    /// last limbs fit in the remaining bits of a word, so composing them can't
+    /// overflow.
+    fn range_check_limbs<const BITS: usize>(self: &Rc<Self>, limbs: &[Scalar]) {
+        for (idx, limb) in limbs.iter().enumerate() {
+            let bits = BITS.min(256usize.saturating_sub(idx * BITS));
+            if bits == 256 {
+                continue;
+            }
+            self.assert(Expr::lt(
+                self.expr(limb),
+                Expr::Constant(U256::one() << bits),
+            ));
+        }
+    }
+
+    fn compose_limbs<const BITS: usize>(self: &Rc<Self>, limbs: &[Scalar]) -> Expr {
+        limbs
+            .iter()
+            .enumerate()
+            .map(|(idx, limb)| match idx {
+                0 => self.expr(limb),
+                _ => Expr::shl(Expr::constant(idx * BITS), self.expr(limb)),
+            })
+            .reduce(|acc, limb| Expr::add(acc, limb))
+            .unwrap()
+    }
+
+    fn x_cube_plus_b(&self, x: Expr) -> Expr {
+        Expr::addmod(
+            Expr::mulmod(
+                x.clone(),
+                Expr::mulmod(x.clone(), x, self.base_modulus()),
+                self.base_modulus(),
+            ),
+            Expr::constant(self.curve.b),
+            self.base_modulus(),
+        )
+    }
+
+    /// Fold into `success` that point at `ptr` has coordinates in range, is
+    /// not identity and is on curve. When coordinates don't fit in a word,
+    /// range and curve equation are checked by adding identity to it with
+    /// precompile instead.
+    fn validate_ec_point(self: &Rc<Self>, ptr: usize) {
+        if !self.curve.is_word_sized() {
+            let size = self.curve.ec_point_size();
+            let is_not_identity = (ptr..ptr + size)
+                .step_by(0x20)
+                .map(Expr::MLoad)
+                .reduce(Expr::or)
+                .unwrap();
+            self.assert(is_not_identity);
+
+            let cd_ptr = self.dup_ec_point(&self.ec_point(Value::Memory(ptr))).ptr();
+            let identity_ptr = self.allocate(size);
+            for word in (0..size).step_by(0x20) {
+                self.mstore(identity_ptr + word, Expr::constant(0));
+            }
+            self.staticcall(self.curve.ec_add, cd_ptr, cd_ptr);
+            return;
+        }
+
+        let (x, y) = (|| Expr::MLoad(ptr), || Expr::MLoad(ptr + 0x20));
+        let in_range = Expr::and(
+            Expr::lt(x(), self.base_modulus()),
+            Expr::lt(y(), self.base_modulus()),
+        );
+        let is_not_identity = Expr::iszero(Expr::or(Expr::iszero(x()), Expr::iszero(y())));
+        let on_curve = Expr::eq(
+            Expr::mulmod(y(), y(), self.base_modulus()),
+            self.x_cube_plus_b(x()),
+        );
+        self.assert(Expr::and(Expr::and(in_range, is_not_identity), on_curve));
     }
 
     pub fn squeeze_challenge(self: &Rc<Self>, ptr: usize, len: usize) -> (usize, Scalar) {
@@ -273,7 +731,7 @@ impl EvmLoader {
             } else {
                 ptr
             };
-            self.code.borrow_mut().push(1).push(ptr + 0x20).mstore8();
+            self.op(Op::MStore8(ptr + 0x20, Expr::constant(1)));
             (ptr, len + 1)
         } else {
             (ptr, len)
@@ -282,36 +740,17 @@ impl EvmLoader {
         let challenge_ptr = self.allocate(0x20);
         let hash_ptr = self.allocate(0x20);
 
-        self.code
-            .borrow_mut()
-            .push(self.scalar_modulus)
-            .push(len)
-            .push(ptr)
-            .keccak256()
-            .dup(0)
-            .push(hash_ptr)
-            .mstore()
-            .r#mod()
-            .push(challenge_ptr)
-            .mstore();
+        self.mstore(hash_ptr, Expr::Keccak { ptr, len });
+        self.mstore(
+            challenge_ptr,
+            Expr::r#mod(Expr::MLoad(hash_ptr), self.scalar_modulus()),
+        );
 
         (hash_ptr, self.scalar(Value::Memory(challenge_ptr)))
     }
 
     pub fn copy_scalar(self: &Rc<Self>, scalar: &Scalar, ptr: usize) {
-        match scalar.value {
-            Value::Constant(constant) => {
-                self.code.borrow_mut().push(constant).push(ptr).mstore();
-            }
-            Value::Memory(src_ptr) => {
-                self.code
-                    .borrow_mut()
-                    .push(src_ptr)
-                    .mload()
-                    .push(ptr)
-                    .mstore();
-            }
-        }
+        self.mstore(ptr, self.expr(scalar));
     }
 
     pub fn dup_scalar(self: &Rc<Self>, scalar: &Scalar) -> Scalar {
@@ -321,30 +760,9 @@ impl EvmLoader {
     }
 
     fn dup_ec_point(self: &Rc<Self>, value: &EcPoint) -> EcPoint {
-        let ptr = self.allocate(0x40);
-        match value.value {
-            Value::Constant((x, y)) => {
-                self.code
-                    .borrow_mut()
-                    .push(x)
-                    .push(ptr)
-                    .mstore()
-                    .push(y)
-                    .push(ptr + 0x20)
-                    .mstore();
-            }
-            Value::Memory(src_ptr) => {
-                self.code
-                    .borrow_mut()
-                    .push(src_ptr)
-                    .mload()
-                    .push(ptr)
-                    .mstore()
-                    .push(src_ptr + 0x20)
-                    .mload()
-                    .push(ptr + 0x20)
-                    .mstore();
-            }
+        let ptr = self.allocate(self.curve.ec_point_size());
+        for (idx, word) in self.ec_point_words(value).into_iter().enumerate() {
+            self.mstore(ptr + idx * 0x20, word);
         }
         self.ec_point(Value::Memory(ptr))
     }
@@ -352,23 +770,38 @@ impl EvmLoader {
     fn staticcall(self: &Rc<Self>, precompile: Precompiled, cd_ptr: usize, rd_ptr: usize) {
         let (cd_len, rd_len) = match precompile {
             Precompiled::BigModExp => (0xc0, 0x20),
-            Precompiled::Bn254Add => (0x80, 0x40),
-            Precompiled::Bn254ScalarMul => (0x60, 0x40),
-            Precompiled::Bn254Pairing => (0x180, 0x20),
+            _ => self.curve.precompile_len(precompile),
         };
-        self.code
-            .borrow_mut()
-            .push(rd_len)
-            .push(rd_ptr)
-            .push(cd_len)
-            .push(cd_ptr)
-            .push(precompile as usize)
-            .gas()
-            .staticcall()
-            .and();
+        self.staticcall_with_len(precompile, cd_ptr, cd_len, rd_ptr, rd_len);
+    }
+
+    fn staticcall_with_len(
+        self: &Rc<Self>,
+        precompile: Precompiled,
+        cd_ptr: usize,
+        cd_len: usize,
+        rd_ptr: usize,
+        rd_len: usize,
+    ) {
+        self.op(Op::Precompile {
+            precompile,
+            cd_ptr,
+            cd_len,
+            rd_ptr,
+            rd_len,
+        });
     }
 
     fn invert(self: &Rc<Self>, scalar: &Scalar) -> Scalar {
+        if *self.deferred_inversion.borrow() {
+            let dst = self.allocate(0x20);
+            self.op(Op::Invert {
+                src: self.expr(scalar),
+                dst,
+            });
+            return self.scalar(Value::Memory(dst));
+        }
+
         let rd_ptr = self.allocate(0x20);
         let [cd_ptr, ..] = [
             &self.scalar(Value::Constant(0x20.into())),
@@ -386,22 +819,49 @@ impl EvmLoader {
     fn ec_point_add(self: &Rc<Self>, lhs: &EcPoint, rhs: &EcPoint) -> EcPoint {
         let rd_ptr = self.dup_ec_point(lhs).ptr();
         self.dup_ec_point(rhs);
-        self.staticcall(Precompiled::Bn254Add, rd_ptr, rd_ptr);
+        self.staticcall(self.curve.ec_add, rd_ptr, rd_ptr);
         self.ec_point(Value::Memory(rd_ptr))
     }
 
-    fn ec_point_sub(self: &Rc<Self>, _: &EcPoint, _: &EcPoint) -> EcPoint {
-        unreachable!()
+    fn ec_point_sub(self: &Rc<Self>, lhs: &EcPoint, rhs: &EcPoint) -> EcPoint {
+        self.ec_point_add(lhs, &self.ec_point_neg(rhs))
     }
 
-    fn ec_point_neg(self: &Rc<Self>, _: &EcPoint) -> EcPoint {
-        unreachable!()
+    /// Negate point by negating `y` modulo base field, which keeps identity
+    /// as zeros. When coordinates don't fit in a word, it's multiplied by `-1`
+    /// with precompile instead.
+    fn ec_point_neg(self: &Rc<Self>, ec_point: &EcPoint) -> EcPoint {
+        if !self.curve.is_word_sized() {
+            let minus_one = self.scalar(Value::Constant(self.scalar_modulus - 1));
+            return self.ec_point_scalar_mul(ec_point, &minus_one);
+        }
+
+        if let Value::Constant((x, y)) = ec_point.value {
+            let y = if y.is_zero() {
+                y
+            } else {
+                U512::from(self.base_modulus_value()) - y
+            };
+            return self.ec_point(Value::Constant((x, y)));
+        }
+
+        let src_ptr = ec_point.ptr();
+        let ptr = self.allocate(0x40);
+        self.mstore(ptr, Expr::MLoad(src_ptr));
+        self.mstore(
+            ptr + 0x20,
+            Expr::r#mod(
+                Expr::sub(self.base_modulus(), Expr::MLoad(src_ptr + 0x20)),
+                self.base_modulus(),
+            ),
+        );
+        self.ec_point(Value::Memory(ptr))
     }
 
     fn ec_point_scalar_mul(self: &Rc<Self>, ec_point: &EcPoint, scalar: &Scalar) -> EcPoint {
         let rd_ptr = self.dup_ec_point(ec_point).ptr();
         self.dup_scalar(scalar);
-        self.staticcall(Precompiled::Bn254ScalarMul, rd_ptr, rd_ptr);
+        self.staticcall(self.curve.ec_mul, rd_ptr, rd_ptr);
         self.ec_point(Value::Memory(rd_ptr))
     }
 
@@ -412,40 +872,39 @@ impl EvmLoader {
         rhs: &EcPoint,
         minus_s_g2: (U256, U256, U256, U256),
     ) {
-        let rd_ptr = self.dup_ec_point(lhs).ptr();
-        self.allocate(0x80);
-        self.code
-            .borrow_mut()
-            .push(g2.0)
-            .push(rd_ptr + 0x40)
-            .mstore()
-            .push(g2.1)
-            .push(rd_ptr + 0x60)
-            .mstore()
-            .push(g2.2)
-            .push(rd_ptr + 0x80)
-            .mstore()
-            .push(g2.3)
-            .push(rd_ptr + 0xa0)
-            .mstore();
-        self.dup_ec_point(rhs);
-        self.allocate(0x80);
-        self.code
-            .borrow_mut()
-            .push(minus_s_g2.0)
-            .push(rd_ptr + 0x100)
-            .mstore()
-            .push(minus_s_g2.1)
-            .push(rd_ptr + 0x120)
-            .mstore()
-            .push(minus_s_g2.2)
-            .push(rd_ptr + 0x140)
-            .mstore()
-            .push(minus_s_g2.3)
-            .push(rd_ptr + 0x160)
-            .mstore();
-        self.staticcall(Precompiled::Bn254Pairing, rd_ptr, rd_ptr);
-        self.code.borrow_mut().push(rd_ptr).mload().and();
+        let encode = |(x_1, x_0, y_1, y_0): (U256, U256, U256, U256)| [x_1, x_0, y_1, y_0];
+        self.pairing_n(&[(lhs, &encode(g2)), (rhs, &encode(minus_s_g2))]);
+    }
+
+    /// Check product of pairings of each G1 point with its G2 point is one,
+    /// where G2 points are encoded in words by [`EvmCurve::encode_g2`].
+    pub fn pairing_n(self: &Rc<Self>, pairs: &[(&EcPoint, &[U256])]) {
+        assert!(!pairs.is_empty());
+
+        let ec_point_size = self.curve.ec_point_size();
+        let ptrs = pairs
+            .iter()
+            .map(|(ec_point, g2)| {
+                assert_eq!(g2.len() * 0x20, self.curve.g2_size());
+                let ptr = self.dup_ec_point(ec_point).ptr();
+                self.allocate(self.curve.g2_size());
+                for (idx, value) in g2.iter().enumerate() {
+                    self.mstore(ptr + ec_point_size + idx * 0x20, Expr::Constant(*value));
+                }
+                ptr
+            })
+            .collect::<Vec<_>>();
+        let rd_ptr = ptrs[0];
+
+        let (cd_len, rd_len) = self.curve.precompile_len(self.curve.pairing);
+        self.staticcall_with_len(
+            self.curve.pairing,
+            rd_ptr,
+            pairs.len() * cd_len,
+            rd_ptr,
+            rd_len,
+        );
+        self.assert(Expr::MLoad(rd_ptr));
     }
 
     fn add(self: &Rc<Self>, lhs: &Scalar, rhs: &Scalar) -> Scalar {
@@ -455,12 +914,10 @@ impl EvmLoader {
         }
 
         let ptr = self.allocate(0x20);
-
-        self.code.borrow_mut().push(self.scalar_modulus);
-        self.push(rhs);
-        self.push(lhs);
-        self.code.borrow_mut().addmod().push(ptr).mstore();
-
+        self.mstore(
+            ptr,
+            Expr::addmod(self.expr(lhs), self.expr(rhs), self.scalar_modulus()),
+        );
         self.scalar(Value::Memory(ptr))
     }
 
@@ -470,13 +927,14 @@ impl EvmLoader {
         }
 
         let ptr = self.allocate(0x20);
-
-        self.code.borrow_mut().push(self.scalar_modulus);
-        self.push(rhs);
-        self.code.borrow_mut().push(self.scalar_modulus).sub();
-        self.push(lhs);
-        self.code.borrow_mut().addmod().push(ptr).mstore();
-
+        self.mstore(
+            ptr,
+            Expr::addmod(
+                self.expr(lhs),
+                Expr::sub(self.scalar_modulus(), self.expr(rhs)),
+                self.scalar_modulus(),
+            ),
+        );
         self.scalar(Value::Memory(ptr))
     }
 
@@ -487,12 +945,10 @@ impl EvmLoader {
         }
 
         let ptr = self.allocate(0x20);
-
-        self.code.borrow_mut().push(self.scalar_modulus);
-        self.push(rhs);
-        self.push(lhs);
-        self.code.borrow_mut().mulmod().push(ptr).mstore();
-
+        self.mstore(
+            ptr,
+            Expr::mulmod(self.expr(lhs), self.expr(rhs), self.scalar_modulus()),
+        );
         self.scalar(Value::Memory(ptr))
     }
 
@@ -502,53 +958,67 @@ impl EvmLoader {
         }
 
         let ptr = self.allocate(0x20);
-
-        self.push(scalar);
-        self.code
-            .borrow_mut()
-            .push(self.scalar_modulus)
-            .sub()
-            .push(ptr)
-            .mstore();
-
+        self.mstore(ptr, Expr::sub(self.scalar_modulus(), self.expr(scalar)));
         self.scalar(Value::Memory(ptr))
     }
 }
 
-#[cfg(test)]
 impl EvmLoader {
-    fn start_gas_metering(self: &Rc<Self>, identifier: &str) {
-        self.gas_metering_ids
-            .borrow_mut()
-            .push(identifier.to_string());
-        self.code.borrow_mut().gas().swap(1);
-    }
+    pub(super) fn start_gas_metering(self: &Rc<Self>, identifier: &str) {
+        if !*self.gas_profiling.borrow() {
+            return;
+        }
 
-    fn end_gas_metering(self: &Rc<Self>) {
-        self.code
-            .borrow_mut()
-            .swap(1)
-            .push(9)
-            .gas()
-            .swap(2)
-            .sub()
-            .sub()
-            .push(0)
-            .push(0)
-            .log1();
+        let mut paths = self.gas_metering_paths.borrow_mut();
+        let mut stack = self.gas_metering_stack.borrow_mut();
+        let path = stack
+            .last()
+            .map(|idx| paths[*idx].clone())
+            .unwrap_or_default()
+            .into_iter()
+            .chain(iter::once(identifier.to_string()))
+            .collect();
+        stack.push(paths.len());
+        paths.push(path);
+        self.op(Op::StartGasMetering);
     }
 
-    pub fn print_gas_metering(self: &Rc<Self>, costs: Vec<u64>) {
-        for (identifier, cost) in self.gas_metering_ids.borrow().iter().zip(costs) {
-            println!("{}: {}", identifier, cost);
+    pub(super) fn end_gas_metering(self: &Rc<Self>) {
+        if !*self.gas_profiling.borrow() {
+            return;
         }
+
+        let idx = self
+            .gas_metering_stack
+            .borrow_mut()
+            .pop()
+            .expect("Gas metering should be started before ended");
+        self.gas_metering_ends.borrow_mut().push(idx);
+        self.op(Op::EndGasMetering);
+    }
+
+    /// Run [`EvmLoader::code`] with `calldata` by [`simulate`], and return gas
+    /// used by each section instrumented since
+    /// [`EvmLoader::enable_gas_profiling`].
+    #[cfg(feature = "simulate")]
+    pub fn gas_profile(self: &Rc<Self>, calldata: Vec<u8>) -> GasProfile {
+        assert!(self.gas_metering_stack.borrow().is_empty());
+
+        let report = simulate(self.code(), calldata);
+        GasProfile::new(
+            report.success,
+            report.gas_used,
+            &self.gas_metering_paths.borrow(),
+            &self.gas_metering_ends.borrow(),
+            &report.section_costs,
+        )
     }
 }
 
 #[derive(Clone)]
 pub struct EcPoint {
     loader: Rc<EvmLoader>,
-    value: Value<(U256, U256)>,
+    value: Value<(U512, U512)>,
 }
 
 impl EcPoint {
@@ -556,7 +1026,7 @@ impl EcPoint {
         &self.loader
     }
 
-    pub fn value(&self) -> Value<(U256, U256)> {
+    pub fn value(&self) -> Value<(U512, U512)> {
         self.value
     }
 
@@ -652,15 +1122,42 @@ impl PartialEq for EcPoint {
 
 impl<C> LoadedEcPoint<C> for EcPoint
 where
-    C: Curve + UncompressedEncoding<Uncompressed = [u8; 0x40]>,
+    C: Curve + UncompressedEncoding,
     C::Scalar: PrimeField<Repr = [u8; 0x20]>,
 {
     type Loader = Rc<EvmLoader>;
+    type Key = Value<(U512, U512)>;
 
     fn loader(&self) -> &Rc<EvmLoader> {
         &self.loader
     }
 
+    fn key(&self) -> Option<Value<(U512, U512)>> {
+        Some(self.value)
+    }
+
+    fn as_const(&self) -> Option<C> {
+        match self.value {
+            Value::Constant((x, y)) => {
+                // Start from any uncompressed encoding to get one of the size
+                // of `C`, whose halves are then overwritten by `x` and `y`.
+                let mut uncompressed = C::generator().to_uncompressed();
+                let bytes = uncompressed.as_mut();
+                let size = bytes.len() / 2;
+                for (bytes, coordinate) in bytes.chunks_mut(size).zip([x, y]) {
+                    let mut repr = [0; 0x40];
+                    coordinate.to_little_endian(&mut repr);
+                    if repr[size..].iter().any(|byte| *byte != 0) {
+                        return None;
+                    }
+                    bytes.copy_from_slice(&repr[..size]);
+                }
+                C::from_uncompressed(uncompressed)
+            }
+            Value::Memory(_) => None,
+        }
+    }
+
     fn multi_scalar_multiplication(pairs: impl IntoIterator<Item = (Scalar, EcPoint)>) -> Self {
         pairs
             .into_iter()
@@ -815,9 +1312,26 @@ impl<F: PrimeField<Repr = [u8; 0x20]>> LoadedScalar<F> for Scalar {
         &self.loader
     }
 
+    fn as_const(&self) -> Option<F> {
+        match self.value {
+            Value::Constant(constant) => Some(u256_to_field(constant)),
+            Value::Memory(_) => None,
+        }
+    }
+
     fn batch_invert<'a>(values: impl IntoIterator<Item = &'a mut Self>) {
         let values = values.into_iter().collect::<Vec<_>>();
-        let loader = &values.first().unwrap().loader;
+        let loader = values.first().unwrap().loader.clone();
+        if *loader.deferred_inversion.borrow() {
+            for value in values {
+                loader.op(Op::Invert {
+                    src: loader.expr(value),
+                    dst: value.ptr(),
+                });
+            }
+            return;
+        }
+
         let products = iter::once(values[0].clone())
             .chain(
                 iter::repeat_with(|| loader.allocate(0x20))
@@ -826,71 +1340,53 @@ impl<F: PrimeField<Repr = [u8; 0x20]>> LoadedScalar<F> for Scalar {
             )
             .collect::<Vec<_>>();
 
-        loader.code.borrow_mut().push(loader.scalar_modulus);
-        for _ in 2..values.len() {
-            loader.code.borrow_mut().dup(0);
+        for idx in 1..values.len() {
+            loader.mstore(
+                products[idx].ptr(),
+                Expr::mulmod(
+                    loader.expr(&products[idx - 1]),
+                    loader.expr(&*values[idx]),
+                    loader.scalar_modulus(),
+                ),
+            );
         }
 
-        loader.push(products.first().unwrap());
-        for (idx, (value, product)) in values.iter().zip(products.iter()).skip(1).enumerate() {
-            loader.push(value);
-            loader.code.borrow_mut().mulmod();
-            if idx < values.len() - 2 {
-                loader.code.borrow_mut().dup(0);
-            }
-            loader.code.borrow_mut().push(product.ptr()).mstore();
-        }
-
-        let inv = loader.invert(products.last().unwrap());
-
-        loader.code.borrow_mut().push(loader.scalar_modulus);
-        for _ in 2..values.len() {
-            loader.code.borrow_mut().dup(0);
-        }
-
-        loader.push(&inv);
-        for (value, product) in values.iter().rev().zip(
-            products
-                .iter()
-                .rev()
-                .skip(1)
-                .map(Some)
-                .chain(iter::once(None)),
-        ) {
-            if let Some(product) = product {
-                loader.push(value);
-                loader
-                    .code
-                    .borrow_mut()
-                    .dup(2)
-                    .dup(2)
-                    .push(product.ptr())
-                    .mload()
-                    .mulmod()
-                    .push(value.ptr())
-                    .mstore()
-                    .mulmod();
-            } else {
-                loader.code.borrow_mut().push(value.ptr()).mstore();
-            }
+        let mut inv = loader.invert(products.last().unwrap());
+        for idx in (1..values.len()).rev() {
+            // Inverse of the product of the first `idx` values is kept in the
+            // slot of `products[idx]`, which is no longer needed.
+            let next_inv_ptr = products[idx].ptr();
+            loader.mstore(
+                next_inv_ptr,
+                Expr::mulmod(
+                    loader.expr(&inv),
+                    loader.expr(&*values[idx]),
+                    loader.scalar_modulus(),
+                ),
+            );
+            loader.mstore(
+                values[idx].ptr(),
+                Expr::mulmod(
+                    loader.expr(&inv),
+                    loader.expr(&products[idx - 1]),
+                    loader.scalar_modulus(),
+                ),
+            );
+            inv = loader.scalar(Value::Memory(next_inv_ptr));
         }
+        loader.copy_scalar(&inv, values[0].ptr());
     }
 }
 
 impl<C> EcPointLoader<C> for Rc<EvmLoader>
 where
-    C: Curve + UncompressedEncoding<Uncompressed = [u8; 0x40]>,
+    C: Curve + UncompressedEncoding,
     C::Scalar: PrimeField<Repr = [u8; 0x20]>,
 {
     type LoadedEcPoint = EcPoint;
 
     fn ec_point_load_const(&self, value: &C) -> EcPoint {
-        let bytes = value.to_uncompressed();
-        let (x, y) = (
-            U256::from_little_endian(&bytes[..32]),
-            U256::from_little_endian(&bytes[32..]),
-        );
-        self.ec_point(Value::Constant((x, y)))
+        self.ec_point(Value::Constant(self.coordinates(value)))
     }
 }
 
@@ -906,16 +1402,18 @@ impl<F: PrimeField<Repr = [u8; 0x20]>> ScalarLoader<F> for Rc<EvmLoader> {
 
 impl<C> Loader<C> for Rc<EvmLoader>
 where
-    C: Curve + UncompressedEncoding<Uncompressed = [u8; 0x40]>,
+    C: Curve + UncompressedEncoding,
     C::Scalar: PrimeField<Repr = [u8; 0x20]>,
 {
-    #[cfg(test)]
     fn start_cost_metering(&self, identifier: &str) {
         self.start_gas_metering(identifier)
     }
 
-    #[cfg(test)]
     fn end_cost_metering(&self) {
         self.end_gas_metering()
     }
+
+    fn record_msm_folding(&self, folding: MsmFolding) {
+        self.msm_folding.borrow_mut().merge(folding)
+    }
 }
@@ -0,0 +1,462 @@
+use crate::loader::evm::ir::Program;
+use std::{mem, ops::Range};
+
+/// Peak memory in bytes touched by a program, before and after reusing memory
+/// of values no longer live.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub before: usize,
+    pub after: usize,
+}
+
+/// Union of overlapping ranges accessed by ops, which has to stay contiguous
+/// when moved.
+#[derive(Debug)]
+struct Region {
+    range: Range<usize>,
+    /// Index of the first op accessing it.
+    first: usize,
+    /// Index of the last op accessing it.
+    last: usize,
+    /// Ranges written so far, sorted and merged.
+    written: Vec<Range<usize>>,
+    /// Whether any byte is read before written, which relies on untouched
+    /// memory being zero.
+    reads_untouched: bool,
+}
+
+impl Region {
+    fn new(range: Range<usize>) -> Self {
+        Self {
+            range,
+            first: usize::MAX,
+            last: 0,
+            written: Vec::new(),
+            reads_untouched: false,
+        }
+    }
+
+    fn access(&mut self, idx: usize) {
+        self.first = self.first.min(idx);
+        self.last = self.last.max(idx);
+    }
+
+    fn read(&mut self, range: Range<usize>) {
+        if !self
+            .written
+            .iter()
+            .any(|written| written.start <= range.start && range.end <= written.end)
+        {
+            self.reads_untouched = true;
+        }
+    }
+
+    fn write(&mut self, range: Range<usize>) {
+        self.written.push(range);
+        self.written.sort_by_key(|range| range.start);
+        self.written = merge(mem::take(&mut self.written), |lhs, rhs| {
+            lhs.end >= rhs.start
+        });
+    }
+}
+
+/// First-fit allocator over memory, where memory above `top` is untouched.
+#[derive(Debug, Default)]
+struct Allocator {
+    free: Vec<Range<usize>>,
+    top: usize,
+}
+
+impl Allocator {
+    fn allocate(&mut self, size: usize, untouched: bool) -> usize {
+        if !untouched {
+            if let Some(idx) = self.free.iter().position(|free| free.len() >= size) {
+                let ptr = self.free[idx].start;
+                self.free[idx].start += size;
+                if self.free[idx].is_empty() {
+                    self.free.remove(idx);
+                }
+                return ptr;
+            }
+            if let Some(last) = self.free.last().filter(|last| last.end == self.top) {
+                let ptr = last.start;
+                self.free.pop();
+                self.top = ptr + size;
+                return ptr;
+            }
+        }
+        let ptr = self.top;
+        self.top += size;
+        ptr
+    }
+
+    fn free(&mut self, range: Range<usize>) {
+        let idx = self.free.partition_point(|free| free.start < range.start);
+        self.free.insert(idx, range);
+        self.free = merge(mem::take(&mut self.free), |lhs, rhs| lhs.end == rhs.start);
+    }
+}
+
+/// Merge consecutive sorted ranges when `mergeable`.
+fn merge(
+    ranges: impl IntoIterator<Item = Range<usize>>,
+    mergeable: impl Fn(&Range<usize>, &Range<usize>) -> bool,
+) -> Vec<Range<usize>> {
+    let mut merged = Vec::<Range<usize>>::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if mergeable(last, &range) => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn round_up(value: usize) -> usize {
+    (value + 0x1f) & !0x1f
+}
+
+impl Program {
+    /// Returns program with memory reused by values whose live ranges don't
+    /// overlap, and the report of peak memory.
+    ///
+    /// Ranges accessed by ops are merged into regions when overlapping, so
+    /// values accessed together (e.g. inputs of a precompile, or words hashed
+    /// by transcript) stay contiguous. Each region is live from the first op
+    /// accessing it to the last one, and is moved to the first free memory
+    /// large enough at its first op, which is freed after its last op. Regions
+    /// reading bytes before writing them are moved to untouched memory to keep
+    /// reading zeros.
+    pub fn reuse_memory(&self) -> (Program, MemoryReport) {
+        let accesses = self
+            .ops()
+            .iter()
+            .map(|op| {
+                let mut reads = Vec::new();
+                op.visit_memory_reads(&mut |ptr, len| reads.push(ptr..ptr + len));
+                let write = op.memory_write().map(|(ptr, len)| ptr..ptr + len);
+                (reads, write)
+            })
+            .collect::<Vec<_>>();
+
+        let mut ranges = accesses
+            .iter()
+            .flat_map(|(reads, write)| reads.iter().cloned().chain(write.clone()))
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|range| range.start);
+        let mut regions = merge(ranges, |lhs, rhs| rhs.start < lhs.end)
+            .into_iter()
+            .map(Region::new)
+            .collect::<Vec<_>>();
+        let region_of = |ptr: usize, regions: &[Region]| {
+            regions.partition_point(|region| region.range.end <= ptr)
+        };
+
+        for (idx, (reads, write)) in accesses.into_iter().enumerate() {
+            for read in reads {
+                let region_idx = region_of(read.start, &regions);
+                regions[region_idx].access(idx);
+                regions[region_idx].read(read);
+            }
+            if let Some(write) = write {
+                let region_idx = region_of(write.start, &regions);
+                regions[region_idx].access(idx);
+                regions[region_idx].write(write);
+            }
+        }
+
+        let mut allocator = Allocator::default();
+        let mut bases = vec![0; regions.len()];
+        let mut births = regions
+            .iter()
+            .enumerate()
+            .map(|(idx, region)| (region.first, idx))
+            .collect::<Vec<_>>();
+        births.sort_unstable();
+        let mut deaths = regions
+            .iter()
+            .enumerate()
+            .map(|(idx, region)| (region.last, idx))
+            .collect::<Vec<_>>();
+        deaths.sort_unstable();
+        let mut births = births.into_iter().peekable();
+        for (op_idx, death) in deaths {
+            while let Some((_, idx)) = births.next_if(|(first, _)| *first <= op_idx) {
+                let region = &regions[idx];
+                bases[idx] =
+                    allocator.allocate(round_up(region.range.len()), region.reads_untouched);
+            }
+            allocator.free(bases[death]..bases[death] + round_up(regions[death].range.len()));
+        }
+
+        let map = |ptr: usize| {
+            let idx = region_of(ptr, &regions);
+            bases[idx] + ptr - regions[idx].range.start
+        };
+        let mut program = self.clone();
+        for op in program.ops_mut() {
+            op.map_ptrs(&map);
+        }
+
+        let report = MemoryReport {
+            before: round_up(regions.last().map_or(0, |region| region.range.end)),
+            after: allocator.top,
+        };
+        (program, report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::loader::evm::{
+        deploy_and_call, deployment_code,
+        ir::{Expr, Op, Program},
+        MemoryReport, Precompiled,
+    };
+    use ethereum_types::U256;
+    use sha3::{Digest, Keccak256};
+
+    fn program(ops: impl IntoIterator<Item = Op>) -> Program {
+        let mut program = Program::new([]);
+        program.ops_mut().extend(ops);
+        program
+    }
+
+    fn mstore(ptr: usize, value: u64) -> Op {
+        Op::MStore(ptr, Expr::constant(value))
+    }
+
+    fn assert_mload_eq(ptr: usize, value: impl Into<U256>) -> Op {
+        Op::Assert(Expr::eq(Expr::MLoad(ptr), Expr::Constant(value.into())))
+    }
+
+    fn keccak256(bytes: &[u8]) -> U256 {
+        U256::from_big_endian(&Keccak256::digest(bytes))
+    }
+
+    fn word(value: u64) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        U256::from(value).to_big_endian(&mut bytes);
+        bytes
+    }
+
+    /// Returns whether `program` executes with all assertions satisfied.
+    fn is_satisfied(program: &Program) -> bool {
+        let mut code = program.lower();
+        code.push(0).mstore().push(0x20).push(0).r#return();
+        let (success, output) = deploy_and_call(deployment_code(code.into()), Vec::new());
+        success && U256::from_big_endian(&output) == U256::one()
+    }
+
+    /// Reuse memory of `program`, and check the result has `expected` ops and
+    /// `report`, and is satisfied like `program`.
+    fn assert_reused(program: Program, expected: Vec<Op>, report: MemoryReport) {
+        let (reused, reused_report) = program.reuse_memory();
+        assert_eq!(reused.ops(), expected.as_slice());
+        assert_eq!(reused_report, report);
+        assert!(is_satisfied(&program));
+        assert!(is_satisfied(&reused));
+    }
+
+    #[test]
+    fn test_relocate_regions() {
+        // Regions with disjoint live ranges are moved to the same memory
+        assert_reused(
+            program([
+                mstore(0x100, 1),
+                assert_mload_eq(0x100, 1),
+                mstore(0x200, 2),
+                assert_mload_eq(0x200, 2),
+            ]),
+            vec![
+                mstore(0, 1),
+                assert_mload_eq(0, 1),
+                mstore(0, 2),
+                assert_mload_eq(0, 2),
+            ],
+            MemoryReport {
+                before: 0x220,
+                after: 0x20,
+            },
+        );
+    }
+
+    #[test]
+    fn test_first_fit() {
+        // Words at 0x000 and 0x200 are freed while the ones at 0x100 and 0x300
+        // are still live, and the word at 0x500 takes the first hole, while
+        // the word at 0x400 born when the holes are freed isn't moved into them
+        let sum = |lhs: usize, rhs: usize| Expr::add(Expr::MLoad(lhs), Expr::MLoad(rhs));
+        assert_reused(
+            program([
+                mstore(0x000, 1),
+                mstore(0x100, 2),
+                mstore(0x200, 3),
+                mstore(0x300, 2),
+                Op::MStore(0x400, sum(0x000, 0x200)),
+                Op::MStore(0x500, sum(0x100, 0x300)),
+                Op::Assert(Expr::eq(Expr::MLoad(0x400), Expr::MLoad(0x500))),
+            ]),
+            vec![
+                mstore(0x00, 1),
+                mstore(0x20, 2),
+                mstore(0x40, 3),
+                mstore(0x60, 2),
+                Op::MStore(0x80, sum(0x00, 0x40)),
+                Op::MStore(0x00, sum(0x20, 0x60)),
+                Op::Assert(Expr::eq(Expr::MLoad(0x80), Expr::MLoad(0x00))),
+            ],
+            MemoryReport {
+                before: 0x520,
+                after: 0xa0,
+            },
+        );
+    }
+
+    #[test]
+    fn test_reads_untouched() {
+        // Word at 0x300 is read before written, so it's moved to untouched
+        // memory instead of the hole of the word at 0x100 holding 1
+        assert_reused(
+            program([
+                mstore(0x100, 1),
+                Op::MStore(0x200, Expr::MLoad(0x100)),
+                Op::Assert(Expr::iszero(Expr::MLoad(0x300))),
+                assert_mload_eq(0x200, 1),
+            ]),
+            vec![
+                mstore(0x00, 1),
+                Op::MStore(0x20, Expr::MLoad(0x00)),
+                Op::Assert(Expr::iszero(Expr::MLoad(0x40))),
+                assert_mload_eq(0x20, 1),
+            ],
+            MemoryReport {
+                before: 0x320,
+                after: 0x60,
+            },
+        );
+    }
+
+    #[test]
+    fn test_overlapping_precompile_buffers() {
+        // Input of `ecAdd` as generator and identity, with output written over
+        // the identity, stays contiguous as one region, which is moved to the
+        // hole at top extended to fit it
+        let precompile = |cd_ptr: usize, rd_ptr: usize| Op::Precompile {
+            precompile: Precompiled::Bn254Add,
+            cd_ptr,
+            cd_len: 0x80,
+            rd_ptr,
+            rd_len: 0x40,
+        };
+        assert_reused(
+            program([
+                mstore(0x300, 5),
+                assert_mload_eq(0x300, 5),
+                mstore(0x100, 1),
+                mstore(0x120, 2),
+                mstore(0x140, 0),
+                mstore(0x160, 0),
+                precompile(0x100, 0x140),
+                assert_mload_eq(0x140, 1),
+                assert_mload_eq(0x160, 2),
+            ]),
+            vec![
+                mstore(0x00, 5),
+                assert_mload_eq(0x00, 5),
+                mstore(0x00, 1),
+                mstore(0x20, 2),
+                mstore(0x40, 0),
+                mstore(0x60, 0),
+                precompile(0x00, 0x40),
+                assert_mload_eq(0x40, 1),
+                assert_mload_eq(0x60, 2),
+            ],
+            MemoryReport {
+                before: 0x320,
+                after: 0x80,
+            },
+        );
+    }
+
+    #[test]
+    fn test_transcript_buffer() {
+        // Words written separately but hashed together stay contiguous
+        let hash = keccak256(&[word(1), word(2)].concat());
+        assert_reused(
+            program([
+                mstore(0x300, 3),
+                assert_mload_eq(0x300, 3),
+                mstore(0x100, 1),
+                mstore(0x120, 2),
+                Op::MStore(
+                    0x200,
+                    Expr::Keccak {
+                        ptr: 0x100,
+                        len: 0x40,
+                    },
+                ),
+                assert_mload_eq(0x200, hash),
+            ]),
+            vec![
+                mstore(0x00, 3),
+                assert_mload_eq(0x00, 3),
+                mstore(0x00, 1),
+                mstore(0x20, 2),
+                Op::MStore(
+                    0x40,
+                    Expr::Keccak {
+                        ptr: 0x00,
+                        len: 0x40,
+                    },
+                ),
+                assert_mload_eq(0x40, hash),
+            ],
+            MemoryReport {
+                before: 0x320,
+                after: 0x60,
+            },
+        );
+    }
+
+    #[test]
+    fn test_mstore8_and_keccak() {
+        // Byte appended by `MStore8` when squeezing a single word is written
+        // before hashed, so the region can be moved into touched memory
+        let hash = keccak256(&[&word(1)[..], &[1u8][..]].concat());
+        assert_reused(
+            program([
+                mstore(0x300, 3),
+                assert_mload_eq(0x300, 3),
+                mstore(0x100, 1),
+                Op::MStore8(0x120, Expr::constant(1)),
+                Op::MStore(
+                    0x200,
+                    Expr::Keccak {
+                        ptr: 0x100,
+                        len: 0x21,
+                    },
+                ),
+                assert_mload_eq(0x200, hash),
+            ]),
+            vec![
+                mstore(0x00, 3),
+                assert_mload_eq(0x00, 3),
+                mstore(0x00, 1),
+                Op::MStore8(0x20, Expr::constant(1)),
+                Op::MStore(
+                    0x40,
+                    Expr::Keccak {
+                        ptr: 0x00,
+                        len: 0x21,
+                    },
+                ),
+                assert_mload_eq(0x40, hash),
+            ],
+            MemoryReport {
+                before: 0x320,
+                after: 0x60,
+            },
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Write};
+
+/// Gas used by a section of code named by the path of sections enclosing it,
+/// summed over each time it's entered.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct GasSection {
+    pub path: Vec<String>,
+    /// Number of times the section is entered.
+    pub count: usize,
+    /// Gas used including nested sections.
+    pub gas: u64,
+    /// Gas used excluding nested sections.
+    pub self_gas: u64,
+}
+
+/// Gas profile of code generated by [`EvmLoader`](super::EvmLoader) with gas
+/// profiling enabled, which is obtained by
+/// [`EvmLoader::gas_profile`](super::EvmLoader::gas_profile).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GasProfile {
+    pub accepted: bool,
+    /// Gas used by the call, including the intrinsic gas of transaction.
+    pub total: u64,
+    /// Sections in the order they are first entered.
+    pub sections: Vec<GasSection>,
+}
+
+impl GasProfile {
+    /// Build profile from `costs` emitted by sections when they end, where
+    /// the `idx`-th cost belongs to section `paths[ends[idx]]`.
+    #[cfg(feature = "simulate")]
+    pub(super) fn new(
+        accepted: bool,
+        total: u64,
+        paths: &[Vec<String>],
+        ends: &[usize],
+        costs: &[u64],
+    ) -> Self {
+        let mut sections = Vec::<GasSection>::new();
+        let mut indices = HashMap::<&[String], usize>::new();
+        let mut section_indices = vec![0; paths.len()];
+        for (path, section_idx) in paths.iter().zip(section_indices.iter_mut()) {
+            *section_idx = *indices.entry(path.as_slice()).or_insert_with(|| {
+                sections.push(GasSection {
+                    path: path.clone(),
+                    count: 0,
+                    gas: 0,
+                    self_gas: 0,
+                });
+                sections.len() - 1
+            });
+        }
+        for (end, cost) in ends.iter().zip(costs) {
+            let section = &mut sections[section_indices[*end]];
+            section.count += 1;
+            section.gas += cost;
+        }
+
+        let nested_gas = sections
+            .iter()
+            .map(|section| {
+                sections
+                    .iter()
+                    .filter(|nested| {
+                        nested.path.len() == section.path.len() + 1
+                            && nested.path.starts_with(&section.path)
+                    })
+                    .map(|nested| nested.gas)
+                    .sum::<u64>()
+            })
+            .collect::<Vec<_>>();
+        for (section, nested_gas) in sections.iter_mut().zip(nested_gas) {
+            section.self_gas = section.gas.saturating_sub(nested_gas);
+        }
+
+        Self {
+            accepted,
+            total,
+            sections,
+        }
+    }
+
+    /// Returns gas used outside any section.
+    pub fn unprofiled_gas(&self) -> u64 {
+        self.total.saturating_sub(
+            self.sections
+                .iter()
+                .filter(|section| section.path.len() == 1)
+                .map(|section| section.gas)
+                .sum(),
+        )
+    }
+
+    /// Returns the profile as JSON, with [`GasProfile::unprofiled_gas`] as
+    /// `unprofiled_gas`.
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            accepted: bool,
+            total: u64,
+            unprofiled_gas: u64,
+            sections: &'a [GasSection],
+        }
+
+        serde_json::to_string(&Json {
+            accepted: self.accepted,
+            total: self.total,
+            unprofiled_gas: self.unprofiled_gas(),
+            sections: &self.sections,
+        })
+        .unwrap()
+    }
+
+    /// Returns the profile in folded stacks format, one `path self_gas` per
+    /// line with path under `verify` separated by `;`, which is accepted by
+    /// flame graph tools like `inferno-flamegraph`.
+    pub fn to_folded(&self) -> String {
+        let mut output = String::new();
+        writeln!(output, "verify {}", self.unprofiled_gas()).unwrap();
+        for section in self.sections.iter() {
+            writeln!(
+                output,
+                "verify;{} {}",
+                section
+                    .path
+                    .iter()
+                    .map(|name| name.replace(|char| char == ';' || char == ' ', "_"))
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                section.self_gas
+            )
+            .unwrap();
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GasProfile, GasSection};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_to_json() {
+        let profile = GasProfile {
+            accepted: true,
+            total: 100,
+            sections: vec![
+                GasSection {
+                    path: vec!["msm".to_string()],
+                    count: 1,
+                    gas: 60,
+                    self_gas: 50,
+                },
+                GasSection {
+                    path: vec!["msm".to_string(), "\"ec\\add\"\n".to_string()],
+                    count: 2,
+                    gas: 10,
+                    self_gas: 10,
+                },
+            ],
+        };
+        assert_eq!(
+            serde_json::from_str::<Value>(&profile.to_json()).unwrap(),
+            json!({
+                "accepted": true,
+                "total": 100,
+                "unprofiled_gas": 40,
+                "sections": [
+                    { "path": ["msm"], "count": 1, "gas": 60, "self_gas": 50 },
+                    { "path": ["msm", "\"ec\\add\"\n"], "count": 2, "gas": 10, "self_gas": 10 },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let profile = GasProfile {
+            accepted: true,
+            total: 100,
+            sections: vec![
+                GasSection {
+                    path: vec!["msm".to_string()],
+                    count: 1,
+                    gas: 60,
+                    self_gas: 50,
+                },
+                GasSection {
+                    path: vec!["msm".to_string(), "ec add;double".to_string()],
+                    count: 2,
+                    gas: 10,
+                    self_gas: 10,
+                },
+                GasSection {
+                    path: vec!["pairing".to_string()],
+                    count: 1,
+                    gas: 30,
+                    self_gas: 30,
+                },
+            ],
+        };
+        assert_eq!(
+            profile.to_folded(),
+            [
+                "verify 10",
+                "verify;msm 50",
+                "verify;msm;ec_add_double 10",
+                "verify;pairing 30",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
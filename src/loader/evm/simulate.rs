@@ -0,0 +1,111 @@
+use ethereum_types::U256;
+use revm::{
+    db::InMemoryDB,
+    primitives::{AccountInfo, Address, Bytecode, ExecutionResult, Output, SpecId, TxKind},
+    Evm,
+};
+
+/// Selector of `Error(string)`, which prefixes revert data of `require` and
+/// `revert` with reason in Solidity.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Result of calling code by [`simulate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecutionReport {
+    pub success: bool,
+    /// Gas used by the call, including the intrinsic gas of transaction.
+    pub gas_used: u64,
+    pub return_data: Vec<u8>,
+    /// Reason of failure, which is the message of `Error(string)` in return
+    /// data when present, otherwise the exit reason of EVM.
+    pub revert_reason: Option<String>,
+    /// Cost of each section metered by code generated with gas profiling
+    /// enabled, in the order sections end.
+    pub section_costs: Vec<u64>,
+}
+
+fn address(lsb: u8) -> Address {
+    Address::with_last_byte(lsb)
+}
+
+/// Decode message of `Error(string)` from `data`.
+fn decode_error_message(data: &[u8]) -> Option<String> {
+    let data = data.strip_prefix(&ERROR_SELECTOR)?;
+    let word = |offset: usize| {
+        data.get(offset..offset + 0x20)
+            .map(U256::from_big_endian)
+            .filter(|value| value.bits() <= 32)
+            .map(|value| value.as_usize())
+    };
+    let offset = word(0)?;
+    let len = word(offset)?;
+    let message = data.get(offset + 0x20..offset + 0x20 + len)?;
+    String::from_utf8(message.to_vec()).ok()
+}
+
+/// Call `code` with `calldata` in a local EVM backed by an in-memory database,
+/// without forking any network, and report the execution. The EVM runs with
+/// Prague rules, so EIP-2537 precompiles of BLS12-381 are available.
+pub fn simulate(code: Vec<u8>, calldata: Vec<u8>) -> ExecutionReport {
+    let caller = address(0xfe);
+    let callee = address(0xff);
+
+    let mut db = InMemoryDB::default();
+    db.insert_account_info(
+        callee,
+        AccountInfo::from_bytecode(Bytecode::new_raw(code.into())),
+    );
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .with_spec_id(SpecId::PRAGUE)
+        .modify_tx_env(|tx| {
+            tx.caller = caller;
+            tx.transact_to = TxKind::Call(callee);
+            tx.data = calldata.into();
+            tx.gas_limit = u64::MAX;
+        })
+        .build();
+    let result = evm.transact().unwrap().result;
+
+    let (gas_used, return_data, exit_reason, logs) = match result {
+        ExecutionResult::Success {
+            gas_used,
+            output,
+            logs,
+            ..
+        } => {
+            let return_data = match output {
+                Output::Call(data) => data.to_vec(),
+                Output::Create(data, _) => data.to_vec(),
+            };
+            (gas_used, return_data, None, logs)
+        }
+        ExecutionResult::Revert { gas_used, output } => (
+            gas_used,
+            output.to_vec(),
+            Some("Revert".to_string()),
+            Vec::new(),
+        ),
+        ExecutionResult::Halt { reason, gas_used } => (
+            gas_used,
+            Vec::new(),
+            Some(format!("{:?}", reason)),
+            Vec::new(),
+        ),
+    };
+    let success = exit_reason.is_none();
+    let revert_reason =
+        exit_reason.map(|exit_reason| decode_error_message(&return_data).unwrap_or(exit_reason));
+    let section_costs = logs
+        .iter()
+        .map(|log| U256::from_big_endian(log.data.topics()[0].as_slice()).as_u64())
+        .collect();
+
+    ExecutionReport {
+        success,
+        gas_used,
+        return_data,
+        revert_reason,
+        section_costs,
+    }
+}
@@ -0,0 +1,115 @@
+use crate::loader::evm::{
+    ir::{Expr, Op, Program},
+    loader::CalldataLayout,
+};
+use ethereum_types::U256;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Write},
+};
+
+/// Op formatted as Yul, where constants are referred by their names, and
+/// calldata is read from `proof` when it's in [`CalldataLayout::Raw`].
+struct Yul<'a> {
+    op: &'a Op,
+    names: &'a HashMap<U256, &'a str>,
+    calldata_layout: CalldataLayout,
+}
+
+impl<'a> Display for Yul<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = self.calldata_layout == CalldataLayout::Raw;
+        self.op.fmt_with(f, &|expr| match expr {
+            Expr::Constant(value) => self.names.get(value).map(|name| name.to_string()),
+            Expr::CalldataLoad(offset) if raw => {
+                Some(format!("calldataload(add(proof.offset, {:#x}))", offset))
+            }
+            Expr::CalldataSize if raw => Some("proof.length".to_string()),
+            _ => None,
+        })
+    }
+}
+
+/// Generate Solidity contract from `program` generated by `EvmLoader`, where
+/// `constants` are declared by their names.
+///
+/// With [`CalldataLayout::Raw`], the contract has `verify(bytes calldata
+/// proof)` with `proof` as the calldata of the code, which reverts on failure.
+/// With [`CalldataLayout::Abi`], the contract has `verify(uint256[] calldata
+/// instances, bytes calldata proof)` reading the calldata as is, which returns
+/// `false` on failure, and a `fallback` returning `false` on other selectors
+/// like the code does.
+pub(super) fn solidity(
+    program: &Program,
+    constants: &[(String, U256)],
+    calldata_layout: CalldataLayout,
+) -> String {
+    let names = constants
+        .iter()
+        .map(|(name, value)| (*value, name.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    let mut output = String::new();
+    writeln!(output, "// SPDX-License-Identifier: MIT").unwrap();
+    writeln!(output, "pragma solidity ^0.8.0;").unwrap();
+    writeln!(output).unwrap();
+    writeln!(output, "contract PlonkVerifier {{").unwrap();
+    for (name, value) in constants {
+        writeln!(
+            output,
+            "    uint256 internal constant {} = {:#x};",
+            name, value
+        )
+        .unwrap();
+    }
+    writeln!(output).unwrap();
+    let params = match calldata_layout {
+        CalldataLayout::Raw => "bytes calldata proof",
+        CalldataLayout::Abi { .. } => "uint256[] calldata instances, bytes calldata proof",
+    };
+    writeln!(
+        output,
+        "    function verify({}) external view returns (bool) {{",
+        params
+    )
+    .unwrap();
+    writeln!(output, "        assembly {{").unwrap();
+    writeln!(output, "            let success := 1").unwrap();
+    for op in program.ops() {
+        let line = Yul {
+            op,
+            names: &names,
+            calldata_layout,
+        }
+        .to_string();
+        if !line.is_empty() {
+            writeln!(output, "            {}", line).unwrap();
+        }
+    }
+    match calldata_layout {
+        CalldataLayout::Raw => {
+            writeln!(output, "            if iszero(success) {{").unwrap();
+            writeln!(output, "                revert(0, 0)").unwrap();
+            writeln!(output, "            }}").unwrap();
+            writeln!(output, "            mstore(0, 1)").unwrap();
+        }
+        CalldataLayout::Abi { .. } => {
+            writeln!(output, "            mstore(0, success)").unwrap();
+        }
+    }
+    writeln!(output, "            return(0, 0x20)").unwrap();
+    writeln!(output, "        }}").unwrap();
+    writeln!(output, "    }}").unwrap();
+    if let CalldataLayout::Abi { .. } = calldata_layout {
+        writeln!(output).unwrap();
+        writeln!(
+            output,
+            "    fallback(bytes calldata) external returns (bytes memory) {{"
+        )
+        .unwrap();
+        writeln!(output, "        return abi.encode(false);").unwrap();
+        writeln!(output, "    }}").unwrap();
+    }
+    writeln!(output, "}}").unwrap();
+    output
+}
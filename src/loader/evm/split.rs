@@ -0,0 +1,105 @@
+use crate::{
+    loader::evm::{
+        ir::{Expr, Op, Program},
+        CODE_SIZE_LIMIT,
+    },
+    Error,
+};
+use ethereum_types::{Address, U256};
+use std::collections::HashMap;
+
+/// Constants whose push takes more code than `mload` of a word, which is
+/// a push of at most 3 bytes and the opcode itself.
+fn is_large(value: &U256) -> bool {
+    value.bits() > 32
+}
+
+impl Program {
+    /// Returns program reading large constants from code of data contract at
+    /// `address` instead of pushing them, and runtime code of the data
+    /// contract.
+    ///
+    /// Each distinct constant wider than 4 bytes, except the ones kept on
+    /// stack, is stored as a word in the data contract, which is copied by
+    /// `extcodecopy` before any other op to memory above memory touched by
+    /// the program, and each use is replaced by `mload` of its word. The data
+    /// contract starts with `STOP` so it halts immediately when called.
+    ///
+    /// Fails with [`Error::DataContractTooLarge`] if runtime code of the data
+    /// contract exceeds [`CODE_SIZE_LIMIT`], so it can't be deployed.
+    pub fn split_constants(&self, address: Address) -> Result<(Program, Vec<u8>), Error> {
+        let ptr = self.memory_size();
+        let mut program = self.clone();
+        let mut words = Vec::<U256>::new();
+        let mut indices = HashMap::<U256, usize>::new();
+        for op in program.ops_mut() {
+            op.map_constants(&mut |value| {
+                if !is_large(&value) || self.constants().contains(&value) {
+                    return None;
+                }
+                let idx = *indices.entry(value).or_insert_with(|| {
+                    words.push(value);
+                    words.len() - 1
+                });
+                Some(Expr::MLoad(ptr + idx * 0x20))
+            });
+        }
+        let data_size = 1 + words.len() * 0x20;
+        if data_size > CODE_SIZE_LIMIT {
+            return Err(Error::DataContractTooLarge(data_size));
+        }
+        if !words.is_empty() {
+            program.ops_mut().insert(
+                0,
+                Op::ExtCodeCopy {
+                    address,
+                    ptr,
+                    offset: 1,
+                    len: words.len() * 0x20,
+                },
+            );
+        }
+
+        let mut data = vec![0; data_size];
+        for (word, chunk) in words.iter().zip(data[1..].chunks_mut(0x20)) {
+            word.to_big_endian(chunk);
+        }
+        Ok((program, data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        loader::evm::{
+            ir::{Expr, Op, Program},
+            CODE_SIZE_LIMIT,
+        },
+        Error,
+    };
+    use ethereum_types::{Address, U256};
+
+    /// Program storing `n` distinct large constants, which are all moved to
+    /// the data contract.
+    fn program(n: usize) -> Program {
+        let mut program = Program::new([]);
+        program.ops_mut().extend(
+            (0..n).map(|idx| Op::MStore(0, Expr::Constant(U256::from(u64::MAX) + U256::from(idx)))),
+        );
+        program
+    }
+
+    #[test]
+    fn test_data_contract_size_limit() {
+        // Data contract is a `STOP` followed by a word per constant
+        let max = (CODE_SIZE_LIMIT - 1) / 0x20;
+        let (_, data) = program(max).split_constants(Address::zero()).unwrap();
+        assert_eq!(data.len(), 1 + max * 0x20);
+        assert!(data.len() <= CODE_SIZE_LIMIT);
+
+        assert!(matches!(
+            program(max + 1).split_constants(Address::zero()),
+            Err(Error::DataContractTooLarge(size)) if size == 1 + (max + 1) * 0x20
+        ));
+    }
+}
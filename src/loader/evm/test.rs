@@ -1,11 +1,17 @@
-use crate::loader::evm::test::tui::Tui;
+use crate::loader::evm::{deployment_code, test::tui::Tui};
+use ethereum_types::U256;
 use foundry_evm::{
-    executor::{backend::Backend, fork::MultiFork, ExecutorBuilder},
+    executor::{backend::Backend, fork::MultiFork, Executor, ExecutorBuilder},
     revm::AccountInfo,
     utils::h256_to_u256_be,
     Address,
 };
-use std::env::var_os;
+use sha3::{Digest, Keccak256};
+use std::{
+    env::var_os,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
 
 mod tui;
 
@@ -22,16 +28,21 @@ fn debug() -> bool {
     )
 }
 
+fn executor() -> Executor {
+    let debug = debug();
+    ExecutorBuilder::default()
+        .with_gas_limit(u64::MAX.into())
+        .set_tracing(debug)
+        .set_debugger(debug)
+        .build(Backend::new(MultiFork::new().0, None))
+}
+
 pub fn execute(code: Vec<u8>, calldata: Vec<u8>) -> (bool, u64, Vec<u64>) {
     let debug = debug();
     let caller = small_address(0xfe);
     let callee = small_address(0xff);
 
-    let mut evm = ExecutorBuilder::default()
-        .with_gas_limit(u64::MAX.into())
-        .set_tracing(debug)
-        .set_debugger(debug)
-        .build(Backend::new(MultiFork::new().0, None));
+    let mut evm = executor();
 
     evm.backend_mut()
         .insert_account_info(callee, AccountInfo::new(0.into(), 1, code.into()));
@@ -52,3 +63,107 @@ pub fn execute(code: Vec<u8>, calldata: Vec<u8>) -> (bool, u64, Vec<u64>) {
 
     (!result.reverted, result.gas, costs)
 }
+
+/// Deploy `deployment_code` then call it with `calldata`, and return whether
+/// it succeeds and its return data.
+pub fn deploy_and_call(deployment_code: Vec<u8>, calldata: Vec<u8>) -> (bool, Vec<u8>) {
+    let caller = small_address(0xfe);
+
+    let mut evm = executor();
+    let callee = evm
+        .deploy(caller, deployment_code.into(), 0.into(), None)
+        .unwrap()
+        .address;
+    let result = evm
+        .call_raw(caller, callee, calldata.into(), 0.into())
+        .unwrap();
+
+    (!result.reverted, result.result.to_vec())
+}
+
+/// Deploy `data_contract_code` as runtime code, then code returned by `code`
+/// with address of the data contract, and call the latter with `calldata`, and
+/// return whether it succeeds and gas used.
+pub fn deploy_with_data_contract_and_call(
+    data_contract_code: Vec<u8>,
+    code: impl FnOnce(Address) -> Vec<u8>,
+    calldata: Vec<u8>,
+) -> (bool, u64) {
+    let caller = small_address(0xfe);
+
+    let mut evm = executor();
+    let data_contract = evm
+        .deploy(
+            caller,
+            deployment_code(data_contract_code).into(),
+            0.into(),
+            None,
+        )
+        .unwrap()
+        .address;
+    let callee = evm
+        .deploy(
+            caller,
+            deployment_code(code(data_contract)).into(),
+            0.into(),
+            None,
+        )
+        .unwrap()
+        .address;
+    let result = evm
+        .call_raw(caller, callee, calldata.into(), 0.into())
+        .unwrap();
+
+    (!result.reverted, result.gas)
+}
+
+/// Compile Solidity `source` with `solc` in `PATH`, and return the runtime
+/// bytecode.
+pub fn compile_solidity(source: &str) -> Vec<u8> {
+    let mut process = match Command::new("solc")
+        .args(["--bin-runtime", "--optimize", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(process) => process,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            panic!("Command solc not found, which is required to test generated Solidity")
+        }
+        Err(err) => panic!("Failed to run solc: {}", err),
+    };
+    process
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = process.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let binary = stdout
+        .split("Binary of the runtime part:")
+        .last()
+        .unwrap()
+        .trim();
+    (0..binary.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&binary[idx..idx + 2], 16).unwrap())
+        .collect()
+}
+
+/// Encode `calldata` of code generated by `EvmLoader` as calldata of
+/// `verify(bytes calldata proof)` of the generated Solidity.
+pub fn encode_verify_calldata(calldata: &[u8]) -> Vec<u8> {
+    let selector = Keccak256::digest(b"verify(bytes)");
+    let mut encoded = selector[..4].to_vec();
+    for value in [U256::from(0x20), U256::from(calldata.len())] {
+        let mut bytes = [0; 32];
+        value.to_big_endian(&mut bytes);
+        encoded.extend(bytes);
+    }
+    encoded.extend(calldata);
+    encoded.resize(4 + 64 + (calldata.len() + 31) / 32 * 32, 0);
+    encoded
+}
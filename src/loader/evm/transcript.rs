@@ -7,7 +7,9 @@ use crate::{
         native::NativeLoader,
         Loader,
     },
-    util::{Curve, Group, PrimeField, Transcript, TranscriptRead, UncompressedEncoding},
+    util::{
+        Curve, Group, GroupEncoding, PrimeField, Transcript, TranscriptRead, UncompressedEncoding,
+    },
     Error,
 };
 use ethereum_types::U256;
@@ -28,19 +30,23 @@ impl MemoryChunk {
         Self { ptr, len: 0x20 }
     }
 
+    fn end(&self) -> usize {
+        self.ptr + self.len
+    }
+
     fn reset(&mut self, ptr: usize) {
         self.ptr = ptr;
         self.len = 0x20;
     }
 
     fn include(&self, ptr: usize, size: usize) -> bool {
-        let range = self.ptr..=self.ptr + self.len;
+        let range = self.ptr..=self.end();
         range.contains(&ptr) && range.contains(&(ptr + size))
     }
 
     fn extend(&mut self, ptr: usize, size: usize) {
         if !self.include(ptr, size) {
-            assert_eq!(self.ptr + self.len, ptr);
+            assert_eq!(self.end(), ptr);
             self.len += size;
         }
     }
@@ -55,7 +61,7 @@ pub struct EvmTranscript<C: Curve, L: Loader<C>, S, B> {
 
 impl<C> EvmTranscript<C, Rc<EvmLoader>, usize, MemoryChunk>
 where
-    C: Curve + UncompressedEncoding<Uncompressed = [u8; 64]>,
+    C: Curve + UncompressedEncoding,
     C::Scalar: PrimeField<Repr = [u8; 32]>,
 {
     pub fn new(loader: Rc<EvmLoader>) -> Self {
@@ -68,37 +74,68 @@ where
             _marker: PhantomData,
         }
     }
+
+    /// Copy buffer to the end of memory unless it's already there, so data
+    /// allocated next is contiguous to it.
+    fn move_buf_to_end(&mut self) {
+        if self.buf.end() != self.loader.ptr() {
+            let ptr = self.loader.copy_memory(self.buf.ptr, self.buf.len);
+            self.buf.ptr = ptr;
+        }
+    }
+
+    /// Absorb `size` bytes at `ptr`, which are copied after the buffer moved
+    /// to the end of memory when they are neither in nor right after it.
+    fn absorb(&mut self, ptr: usize, size: usize) {
+        if self.buf.include(ptr, size) || self.buf.end() == ptr {
+            self.buf.extend(ptr, size);
+        } else {
+            self.move_buf_to_end();
+            let ptr = self.loader.copy_memory(ptr, size);
+            self.buf.extend(ptr, size);
+        }
+    }
 }
 
 impl<C> Transcript<C, Rc<EvmLoader>> for EvmTranscript<C, Rc<EvmLoader>, usize, MemoryChunk>
 where
-    C: Curve + UncompressedEncoding<Uncompressed = [u8; 64]>,
+    C: Curve + UncompressedEncoding,
     C::Scalar: PrimeField<Repr = [u8; 32]>,
 {
     fn squeeze_challenge(&mut self) -> Scalar {
+        self.loader.start_gas_metering("squeeze_challenge");
         let (ptr, scalar) = self.loader.squeeze_challenge(self.buf.ptr, self.buf.len);
         self.buf.reset(ptr);
+        self.loader.end_gas_metering();
         scalar
     }
 
     fn common_ec_point(&mut self, ec_point: &EcPoint) -> Result<(), Error> {
         if let Value::Memory(ptr) = ec_point.value() {
-            self.buf.extend(ptr, 0x40);
+            self.absorb(ptr, self.loader.curve().ec_point_size());
         } else {
             unreachable!()
         }
         Ok(())
     }
 
+    /// Absorb `scalar`, where a constant absorbed before any squeeze is the
+    /// transcript initial state written to the memory reserved for it, and
+    /// other constants are copied to memory right after the buffer.
     fn common_scalar(&mut self, scalar: &Scalar) -> Result<(), Error> {
         match scalar.value() {
             Value::Constant(_) if self.buf.ptr == 0 => {
                 self.loader.copy_scalar(scalar, self.buf.ptr);
+                self.buf.extend(self.buf.ptr, 0x20);
+            }
+            Value::Constant(_) => {
+                self.move_buf_to_end();
+                let scalar = self.loader.dup_scalar(scalar);
+                self.buf.extend(scalar.ptr(), 0x20);
             }
             Value::Memory(ptr) => {
-                self.buf.extend(ptr, 0x20);
+                self.absorb(ptr, 0x20);
             }
-            _ => unreachable!(),
         }
         Ok(())
     }
@@ -106,7 +143,7 @@ where
 
 impl<C> TranscriptRead<C, Rc<EvmLoader>> for EvmTranscript<C, Rc<EvmLoader>, usize, MemoryChunk>
 where
-    C: Curve + UncompressedEncoding<Uncompressed = [u8; 64]>,
+    C: Curve + UncompressedEncoding,
     C::Scalar: PrimeField<Repr = [u8; 32]>,
 {
     fn read_scalar(&mut self) -> Result<Scalar, Error> {
@@ -118,7 +155,7 @@ where
 
     fn read_ec_point(&mut self) -> Result<EcPoint, Error> {
         let ec_point = self.loader.calldataload_ec_point(self.stream);
-        self.stream += 0x40;
+        self.stream += self.loader.curve().ec_point_size();
         self.common_ec_point(&ec_point)?;
         Ok(ec_point)
     }
@@ -126,7 +163,7 @@ where
 
 impl<C, S> EvmTranscript<C, NativeLoader, S, Vec<u8>>
 where
-    C: Curve,
+    C: Curve + GroupEncoding,
 {
     pub fn new(stream: S) -> Self {
         Self {
@@ -140,7 +177,7 @@ where
 
 impl<C, S> Transcript<C, NativeLoader> for EvmTranscript<C, NativeLoader, S, Vec<u8>>
 where
-    C: Curve + UncompressedEncoding<Uncompressed = [u8; 64]>,
+    C: Curve + GroupEncoding + UncompressedEncoding<Uncompressed = [u8; 64]>,
     C::Scalar: PrimeField<Repr = [u8; 32]>,
 {
     fn squeeze_challenge(&mut self) -> C::Scalar {
@@ -176,7 +213,7 @@ where
 
 impl<C, S> TranscriptRead<C, NativeLoader> for EvmTranscript<C, NativeLoader, S, Vec<u8>>
 where
-    C: Curve + UncompressedEncoding<Uncompressed = [u8; 64]>,
+    C: Curve + GroupEncoding + UncompressedEncoding<Uncompressed = [u8; 64]>,
     C::Scalar: PrimeField<Repr = [u8; 32]>,
     S: Read,
 {
@@ -216,7 +253,7 @@ where
 
 impl<C, S> EvmTranscript<C, NativeLoader, S, Vec<u8>>
 where
-    C: Curve,
+    C: Curve + GroupEncoding,
     S: Write,
 {
     pub fn stream_mut(&mut self) -> &mut S {
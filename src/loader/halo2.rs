@@ -1,6 +1,13 @@
 mod accumulation;
+mod aggregation;
+mod aggregation_tree;
 mod loader;
 mod transcript;
 
-pub use loader::Halo2Loader;
+pub use aggregation::{
+    AggregationCircuit, AggregationConfig, AggregationTranscript, NativeAggregationTranscript,
+    SnarkWitness,
+};
+pub use aggregation_tree::AggregationTree;
+pub use loader::{hash_to_aux_generator, EcPoint, Halo2Loader, Scalar, Value};
 pub use transcript::PoseidonTranscript;
@@ -1,14 +1,18 @@
 use crate::{
     loader::{
-        halo2::loader::{Halo2Loader, Scalar},
+        halo2::loader::{EcPoint, Halo2Loader, Scalar},
         LoadedEcPoint,
     },
     protocol::Protocol,
-    scheme::kzg::{AccumulationStrategy, Accumulator, SameCurveAccumulation, MSM},
+    scheme::kzg::{
+        AccumulationStrategy, Accumulator, AccumulatorEncoding, CompressedEncoding,
+        HashCommittedEncoding, LimbsEncoding, SameCurveAccumulation,
+    },
     util::Transcript,
     Error,
 };
 use halo2_curves::CurveAffine;
+use halo2_proofs::circuit;
 use halo2_wrong_ecc::AssignedPoint;
 use std::rc::Rc;
 
@@ -18,16 +22,42 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
     pub fn finalize(
         self,
         g1: C,
-    ) -> (
-        AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>,
-        AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>,
-    ) {
-        let (lhs, rhs) = self.accumulator.unwrap().evaluate(g1.to_curve());
-        let loader = lhs.loader();
+    ) -> Result<
         (
+            AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>,
+            AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>,
+        ),
+        Error,
+    > {
+        let (lhs, rhs) = self
+            .accumulator
+            .ok_or(Error::MissingAccumulator)?
+            .evaluate(g1.to_curve())?;
+        let loader = lhs.loader();
+        Ok((
             loader.ec_point_nomalize(&lhs.assigned()),
             loader.ec_point_nomalize(&rhs.assigned()),
-        )
+        ))
+    }
+
+    /// Finalize each term of accumulator with the index of G2 point it should
+    /// be paired with.
+    pub fn finalize_terms(
+        self,
+        g1: C,
+    ) -> Result<Vec<(AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>, usize)>, Error> {
+        Ok(self
+            .accumulator
+            .ok_or(Error::MissingAccumulator)?
+            .evaluate_terms(g1.to_curve())
+            .into_iter()
+            .map(|(ec_point, g2_index)| {
+                (
+                    ec_point.loader().ec_point_nomalize(&ec_point.assigned()),
+                    g2_index,
+                )
+            })
+            .collect())
     }
 }
 
@@ -41,53 +71,129 @@ where
     type Output = ();
 
     fn extract_accumulator(
-        &self,
+        &mut self,
         protocol: &Protocol<C::CurveExt>,
         loader: &Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
         transcript: &mut T,
         statements: &[Vec<Scalar<'a, 'b, C, LIMBS, BITS>>],
-    ) -> Option<Accumulator<C::CurveExt, Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>>> {
-        let accumulator_indices = protocol.accumulator_indices.as_ref()?;
-
-        let challenges = transcript.squeeze_n_challenges(accumulator_indices.len());
-        let accumulators = accumulator_indices
-            .iter()
-            .map(|indices| {
-                assert_eq!(indices.len(), 4 * LIMBS);
-                let assinged = indices
-                    .iter()
-                    .map(|index| statements[index.0][index.1].assigned())
-                    .collect::<Vec<_>>();
-                let lhs = loader.assign_ec_point_from_limbs(
-                    assinged[..LIMBS].to_vec().try_into().unwrap(),
-                    assinged[LIMBS..2 * LIMBS].to_vec().try_into().unwrap(),
-                );
-                let rhs = loader.assign_ec_point_from_limbs(
-                    assinged[2 * LIMBS..3 * LIMBS].to_vec().try_into().unwrap(),
-                    assinged[3 * LIMBS..].to_vec().try_into().unwrap(),
-                );
-                Accumulator::new(MSM::base(lhs), MSM::base(rhs))
-            })
-            .collect::<Vec<_>>();
-
-        Some(Accumulator::random_linear_combine(
-            challenges.into_iter().zip(accumulators),
-        ))
+    ) -> Result<Option<Accumulator<C::CurveExt, Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>>>, Error>
+    {
+        self.decode_accumulators(protocol, loader, transcript, statements)
     }
 
     fn process(
         &mut self,
-        _: &Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
+        protocol: &Protocol<C::CurveExt>,
+        loader: &Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
         transcript: &mut T,
         _: P,
         accumulator: Accumulator<C::CurveExt, Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>>,
     ) -> Result<Self::Output, Error> {
-        self.accumulator = Some(match self.accumulator.take() {
-            Some(curr_accumulator) => {
-                accumulator + curr_accumulator * &transcript.squeeze_challenge()
-            }
-            None => accumulator,
+        self.combine(protocol, loader, transcript, accumulator)
+    }
+}
+
+impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
+    AccumulatorEncoding<C::CurveExt, Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>>
+    for LimbsEncoding<LIMBS, BITS>
+{
+    fn decode(
+        &self,
+        loader: &Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
+        _: usize,
+        statements: &[Scalar<'a, 'b, C, LIMBS, BITS>],
+    ) -> Result<
+        (
+            EcPoint<'a, 'b, C, LIMBS, BITS>,
+            EcPoint<'a, 'b, C, LIMBS, BITS>,
+        ),
+        Error,
+    > {
+        if statements.len() != 4 * LIMBS {
+            return Err(Error::InvalidInstances);
+        }
+
+        let assigned = statements
+            .iter()
+            .map(|statement| statement.assigned())
+            .collect::<Vec<_>>();
+        let [lhs_x, lhs_y, rhs_x, rhs_y]: [[_; LIMBS]; 4] = assigned
+            .chunks(LIMBS)
+            .map(|limbs| limbs.to_vec().try_into().unwrap())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        Ok((
+            loader.assign_ec_point_from_limbs(lhs_x, lhs_y),
+            loader.assign_ec_point_from_limbs(rhs_x, rhs_y),
+        ))
+    }
+}
+
+impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
+    AccumulatorEncoding<C::CurveExt, Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>>
+    for CompressedEncoding<LIMBS, BITS>
+{
+    fn decode(
+        &self,
+        loader: &Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
+        _: usize,
+        statements: &[Scalar<'a, 'b, C, LIMBS, BITS>],
+    ) -> Result<
+        (
+            EcPoint<'a, 'b, C, LIMBS, BITS>,
+            EcPoint<'a, 'b, C, LIMBS, BITS>,
+        ),
+        Error,
+    > {
+        if statements.len() != 2 * (LIMBS + 1) {
+            return Err(Error::InvalidInstances);
+        }
+
+        let assigned = statements
+            .iter()
+            .map(|statement| statement.assigned())
+            .collect::<Vec<_>>();
+        let [lhs, rhs] = [&assigned[..LIMBS + 1], &assigned[LIMBS + 1..]].map(|assigned| {
+            loader.assign_ec_point_from_compressed(
+                assigned[..LIMBS].to_vec().try_into().unwrap(),
+                assigned[LIMBS].clone(),
+            )
         });
-        Ok(())
+        Ok((lhs, rhs))
+    }
+}
+
+/// Witness of [`HashCommittedEncoding`] for `Rc<Halo2Loader>` is the `Value`
+/// of accumulator `(lhs, rhs)`.
+impl<'a, 'b, C, H, const LIMBS: usize, const BITS: usize>
+    AccumulatorEncoding<C::CurveExt, Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>>
+    for HashCommittedEncoding<circuit::Value<(C, C)>, H>
+where
+    C: CurveAffine,
+    H: Fn(
+        &EcPoint<'a, 'b, C, LIMBS, BITS>,
+        &EcPoint<'a, 'b, C, LIMBS, BITS>,
+    ) -> Result<Scalar<'a, 'b, C, LIMBS, BITS>, Error>,
+{
+    fn decode(
+        &self,
+        loader: &Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
+        idx: usize,
+        statements: &[Scalar<'a, 'b, C, LIMBS, BITS>],
+    ) -> Result<
+        (
+            EcPoint<'a, 'b, C, LIMBS, BITS>,
+            EcPoint<'a, 'b, C, LIMBS, BITS>,
+        ),
+        Error,
+    > {
+        let [statement]: &[_; 1] = statements.try_into().map_err(|_| Error::InvalidInstances)?;
+        let witness = *self.witness(idx)?;
+        let lhs = loader.assign_ec_point(witness.map(|(lhs, _)| lhs));
+        let rhs = loader.assign_ec_point(witness.map(|(_, rhs)| rhs));
+        let hash = (self.hash())(&lhs, &rhs)?;
+        loader.assert_scalar_eq(&hash, statement);
+        Ok((lhs, rhs))
     }
 }
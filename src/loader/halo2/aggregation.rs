@@ -0,0 +1,368 @@
+use crate::{
+    loader::{
+        halo2::{loader::Halo2Loader, transcript::PoseidonTranscript},
+        native::NativeLoader,
+    },
+    protocol::{halo2::Config, Protocol, Snark},
+    scheme::kzg::{AccumulationScheme, Accumulator, SameCurveAccumulation, MSM},
+    util::{fe_to_limbs, Curve, Group, PrimeCurveAffine, Transcript},
+    Error,
+};
+use halo2_curves::CurveAffine;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{floor_planner::V1, Layouter, Value},
+    plonk::{self, Circuit, ConstraintSystem},
+};
+use halo2_wrong_ecc::{BaseFieldEccChip, EccConfig};
+use halo2_wrong_maingate::{
+    MainGate, MainGateConfig, RangeChip, RangeConfig, RangeInstructions, RegionCtx,
+};
+use halo2_wrong_transcript::{NativeRepresentation, TranscriptChip};
+use poseidon::Poseidon;
+use std::{iter, marker::PhantomData, rc::Rc};
+
+pub const T: usize = 5;
+pub const RATE: usize = 4;
+pub const R_F: usize = 8;
+pub const R_P: usize = 57;
+
+/// Transcript used to accumulate snarks in [`AggregationCircuit`], which the
+/// snarks should be proved with.
+pub type AggregationTranscript<'a, 'b, 'c, C, const LIMBS: usize, const BITS: usize> =
+    PoseidonTranscript<
+        C,
+        Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
+        Value<&'c [u8]>,
+        TranscriptChip<NativeRepresentation, C, LIMBS, BITS, T, RATE>,
+        NativeRepresentation,
+        LIMBS,
+        BITS,
+        T,
+        RATE,
+        R_F,
+        R_P,
+    >;
+
+/// Native counterpart of [`AggregationTranscript`].
+pub type NativeAggregationTranscript<C, S, const LIMBS: usize, const BITS: usize> =
+    PoseidonTranscript<
+        C,
+        NativeLoader,
+        S,
+        Poseidon<<C as CurveAffine>::ScalarExt, T, RATE>,
+        NativeRepresentation,
+        LIMBS,
+        BITS,
+        T,
+        RATE,
+        R_F,
+        R_P,
+    >;
+
+pub struct SnarkWitness<C: Curve> {
+    pub protocol: Protocol<C>,
+    pub statements: Vec<Vec<Value<<C as Group>::Scalar>>>,
+    pub proof: Value<Vec<u8>>,
+}
+
+impl<C: Curve> From<Snark<C>> for SnarkWitness<C> {
+    fn from(snark: Snark<C>) -> Self {
+        Self {
+            protocol: snark.protocol,
+            statements: snark
+                .statements
+                .into_iter()
+                .map(|statements| statements.into_iter().map(Value::known).collect::<Vec<_>>())
+                .collect(),
+            proof: Value::known(snark.proof),
+        }
+    }
+}
+
+impl<C: Curve> SnarkWitness<C> {
+    pub fn without_witnesses(&self) -> Self {
+        SnarkWitness {
+            protocol: self.protocol.clone(),
+            statements: self
+                .statements
+                .iter()
+                .map(|statements| vec![Value::unknown(); statements.len()])
+                .collect(),
+            proof: Value::unknown(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AggregationConfig {
+    main_gate_config: MainGateConfig,
+    range_config: RangeConfig,
+}
+
+impl AggregationConfig {
+    pub fn configure<C: CurveAffine, const LIMBS: usize, const BITS: usize>(
+        meta: &mut ConstraintSystem<C::Scalar>,
+    ) -> Self {
+        let main_gate_config = MainGate::<C::Scalar>::configure(meta);
+        let range_config = RangeChip::<C::Scalar>::configure(
+            meta,
+            &main_gate_config,
+            vec![BITS / LIMBS],
+            BaseFieldEccChip::<C, LIMBS, BITS>::rns().overflow_lengths(),
+        );
+        AggregationConfig {
+            main_gate_config,
+            range_config,
+        }
+    }
+
+    pub fn ecc_config(&self) -> EccConfig {
+        EccConfig::new(self.range_config.clone(), self.main_gate_config.clone())
+    }
+
+    pub fn load_table<F: FieldExt>(
+        &self,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), plonk::Error> {
+        RangeChip::<F>::new(self.range_config.clone()).load_table(layouter)
+    }
+}
+
+/// Circuit that verifies snarks with accumulation scheme `AS`, optionally
+/// combines a previous accumulator, and exposes the resulting accumulator
+/// `(lhs, rhs)` as `4 * LIMBS` limbs in the first instance column, in order
+/// `lhs.x, lhs.y, rhs.x, rhs.y`.
+///
+/// The previous accumulator, if any, is exposed as another `4 * LIMBS` limbs
+/// in the same order right after, so the verifier can check it's the one it
+/// expects (e.g. output of the last aggregation), otherwise it'd be an
+/// unconstrained witness.
+pub struct AggregationCircuit<C: CurveAffine, AS, const LIMBS: usize, const BITS: usize> {
+    g1: C,
+    snarks: Vec<SnarkWitness<C::CurveExt>>,
+    previous: Option<Value<(C, C)>>,
+    instances: Vec<C::Scalar>,
+    _marker: PhantomData<AS>,
+}
+
+impl<C, AS, const LIMBS: usize, const BITS: usize> AggregationCircuit<C, AS, LIMBS, BITS>
+where
+    C: CurveAffine,
+    for<'c> AS: AccumulationScheme<
+        C::CurveExt,
+        NativeLoader,
+        NativeAggregationTranscript<C, &'c [u8], LIMBS, BITS>,
+        SameCurveAccumulation<C::CurveExt, NativeLoader, LIMBS, BITS>,
+    >,
+{
+    /// Accumulate `snarks` natively to compute instances. The `previous`
+    /// accumulator, if any, is combined with a challenge squeezed after
+    /// absorbing both accumulators, and it fails with
+    /// [`Error::AccumulatorIsIdentity`] if either of its points is identity
+    /// since it has no coordinates to expose.
+    pub fn new(
+        g1: C,
+        snarks: impl IntoIterator<Item = Snark<C::CurveExt>>,
+        previous: Option<(C, C)>,
+    ) -> Result<Self, Error> {
+        let snarks = snarks.into_iter().collect::<Vec<_>>();
+        if snarks.is_empty() {
+            return Err(Error::MissingSnark);
+        }
+        if let Some((prev_lhs, prev_rhs)) = previous {
+            if bool::from(prev_lhs.is_identity() | prev_rhs.is_identity()) {
+                return Err(Error::AccumulatorIsIdentity);
+            }
+        }
+
+        let mut strategy = SameCurveAccumulation::<_, NativeLoader, LIMBS, BITS>::default();
+        for snark in snarks.iter() {
+            AS::accumulate(
+                &snark.protocol,
+                &NativeLoader,
+                snark.statements.clone(),
+                &mut NativeAggregationTranscript::<C, _, LIMBS, BITS>::new(snark.proof.as_slice()),
+                &mut strategy,
+            )?;
+        }
+        let (mut lhs, mut rhs) = strategy.finalize(g1.to_curve())?;
+
+        if let Some((prev_lhs, prev_rhs)) = previous {
+            let (prev_lhs, prev_rhs) = (prev_lhs.to_curve(), prev_rhs.to_curve());
+            let mut transcript =
+                NativeAggregationTranscript::<C, _, LIMBS, BITS>::new(&[] as &[u8]);
+            for ec_point in [&lhs, &rhs, &prev_lhs, &prev_rhs] {
+                transcript.common_ec_point(ec_point)?;
+            }
+            let challenge = transcript.squeeze_challenge();
+            lhs += prev_lhs * challenge;
+            rhs += prev_rhs * challenge;
+        }
+
+        let instances = [lhs.to_affine(), rhs.to_affine()]
+            .into_iter()
+            .chain(previous.into_iter().flat_map(|(lhs, rhs)| [lhs, rhs]))
+            .flat_map(|ec_point| {
+                let coordinates = ec_point.coordinates().unwrap();
+                [*coordinates.x(), *coordinates.y()]
+            })
+            .flat_map(fe_to_limbs::<_, _, LIMBS, BITS>)
+            .collect();
+
+        Ok(Self {
+            g1,
+            snarks: snarks.into_iter().map(Into::into).collect(),
+            previous: previous.map(Value::known),
+            instances,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<C: CurveAffine, AS, const LIMBS: usize, const BITS: usize>
+    AggregationCircuit<C, AS, LIMBS, BITS>
+{
+    pub fn accumulator_indices() -> Vec<(usize, usize)> {
+        (0..4 * LIMBS).map(|idx| (0, idx)).collect()
+    }
+
+    /// Returns the config to compile `Protocol` of this circuit with, so it
+    /// can be verified again with its accumulator extracted from instances.
+    pub fn config(zk: bool) -> Config {
+        Config::new(zk, false, 1).with_accumulator_indices(Self::accumulator_indices())
+    }
+
+    pub fn instances(&self) -> Vec<Vec<C::Scalar>> {
+        vec![self.instances.clone()]
+    }
+}
+
+impl<C, AS, const LIMBS: usize, const BITS: usize> Circuit<C::Scalar>
+    for AggregationCircuit<C, AS, LIMBS, BITS>
+where
+    C: CurveAffine,
+    for<'a, 'b, 'c> AS: AccumulationScheme<
+        C::CurveExt,
+        Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
+        AggregationTranscript<'a, 'b, 'c, C, LIMBS, BITS>,
+        SameCurveAccumulation<C::CurveExt, Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>, LIMBS, BITS>,
+    >,
+{
+    type Config = AggregationConfig;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            g1: self.g1,
+            snarks: self
+                .snarks
+                .iter()
+                .map(SnarkWitness::without_witnesses)
+                .collect(),
+            previous: self.previous.map(|_| Value::unknown()),
+            instances: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<C::Scalar>) -> Self::Config {
+        AggregationConfig::configure::<C, LIMBS, BITS>(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<C::Scalar>,
+    ) -> Result<(), plonk::Error> {
+        config.load_table(&mut layouter)?;
+
+        let ((lhs, rhs), previous) = layouter.assign_region(
+            || "",
+            |mut region| {
+                let mut offset = 0;
+                let ctx = RegionCtx::new(&mut region, &mut offset);
+
+                let loader = Halo2Loader::<C, LIMBS, BITS>::new(config.ecc_config(), ctx);
+                // Generator and preprocessed commitments are known ahead, so
+                // they're multiplied with window tables in fixed columns
+                loader.enable_fixed_base_tables(iter::once(self.g1).chain(
+                    self.snarks.iter().flat_map(|snark| {
+                        snark
+                            .protocol
+                            .preprocessed
+                            .iter()
+                            .map(|ec_point| ec_point.to_affine())
+                    }),
+                ));
+                let mut strategy = SameCurveAccumulation::default();
+                for snark in self.snarks.iter() {
+                    let statements = snark
+                        .statements
+                        .iter()
+                        .map(|statements| {
+                            statements
+                                .iter()
+                                .map(|statement| loader.assign_scalar(*statement))
+                                .collect::<Vec<_>>()
+                        })
+                        .collect::<Vec<_>>();
+                    AS::accumulate(
+                        &snark.protocol,
+                        &loader,
+                        statements,
+                        &mut AggregationTranscript::new(
+                            &loader,
+                            snark.proof.as_ref().map(|proof| proof.as_slice()),
+                        ),
+                        &mut strategy,
+                    )
+                    .map_err(|_| plonk::Error::Synthesis)?;
+                }
+
+                let previous = if let Some(previous) = self.previous {
+                    let (lhs, rhs) = strategy
+                        .accumulator
+                        .take()
+                        .ok_or(Error::MissingAccumulator)
+                        .and_then(|accumulator| accumulator.evaluate(self.g1.to_curve()))
+                        .map_err(|_| plonk::Error::Synthesis)?;
+                    let prev_lhs = loader.assign_ec_point(previous.map(|(lhs, _)| lhs));
+                    let prev_rhs = loader.assign_ec_point(previous.map(|(_, rhs)| rhs));
+                    let mut transcript =
+                        AggregationTranscript::new(&loader, Value::known(&[] as &[u8]));
+                    for ec_point in [&lhs, &rhs, &prev_lhs, &prev_rhs] {
+                        transcript
+                            .common_ec_point(ec_point)
+                            .map_err(|_| plonk::Error::Synthesis)?;
+                    }
+                    let challenge = transcript.squeeze_challenge();
+                    let previous = (prev_lhs.assigned(), prev_rhs.assigned());
+                    strategy.accumulator = Some(
+                        Accumulator::new(MSM::base(lhs), MSM::base(rhs))
+                            + Accumulator::new(MSM::base(prev_lhs), MSM::base(prev_rhs))
+                                * &challenge,
+                    );
+                    Some(previous)
+                } else {
+                    None
+                };
+
+                let accumulator = strategy
+                    .finalize(self.g1)
+                    .map_err(|_| plonk::Error::Synthesis)?;
+                Ok((accumulator, previous))
+            },
+        )?;
+
+        let ecc_chip = BaseFieldEccChip::<C, LIMBS, BITS>::new(config.ecc_config());
+        ecc_chip.expose_public(layouter.namespace(|| ""), lhs, 0)?;
+        ecc_chip.expose_public(layouter.namespace(|| ""), rhs, 2 * LIMBS)?;
+        if let Some((prev_lhs, prev_rhs)) = previous {
+            ecc_chip.expose_public(layouter.namespace(|| ""), prev_lhs, 4 * LIMBS)?;
+            ecc_chip.expose_public(layouter.namespace(|| ""), prev_rhs, 6 * LIMBS)?;
+        }
+
+        Ok(())
+    }
+}
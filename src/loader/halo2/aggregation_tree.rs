@@ -0,0 +1,218 @@
+use crate::{
+    loader::{
+        halo2::{
+            aggregation::{AggregationCircuit, AggregationTranscript, NativeAggregationTranscript},
+            loader::Halo2Loader,
+        },
+        native::NativeLoader,
+    },
+    protocol::{
+        halo2::{compile, util::halo2::ChallengeScalar},
+        Protocol, Snark,
+    },
+    scheme::kzg::{AccumulationScheme, SameCurveAccumulation},
+    util::PrimeField,
+    Error,
+};
+use halo2_curves::{pairing::MultiMillerLoop, CurveAffine};
+use halo2_proofs::{
+    plonk::{create_proof, keygen_pk, keygen_vk, ProvingKey},
+    poly::{
+        commitment::{ParamsProver, Prover},
+        kzg::commitment::{KZGCommitmentScheme, ParamsKZG},
+    },
+    transcript::{EncodedChallenge, TranscriptWriterBuffer},
+};
+use rand::RngCore;
+use std::{collections::HashMap, fmt::Debug, marker::PhantomData, rc::Rc};
+
+type IntermediateTranscript<C, const LIMBS: usize, const BITS: usize> =
+    NativeAggregationTranscript<C, Vec<u8>, LIMBS, BITS>;
+
+/// Builder of a tree of [`AggregationCircuit`]s, where each layer aggregates
+/// chunks of `fan_in` snarks of the layer below, and passes its accumulator
+/// to the next layer via instances at
+/// [`AggregationCircuit::accumulator_indices`].
+///
+/// Proving keys are cached by digests of protocols of the aggregated chunk in
+/// order, so chunks of different protocols, even in the same layer, get their
+/// own proving keys. The aggregation circuits are proved without
+/// zero-knowledge.
+pub struct AggregationTree<'params, E, AS, P, const LIMBS: usize, const BITS: usize>
+where
+    E: MultiMillerLoop + Debug,
+{
+    params: &'params ParamsKZG<E>,
+    fan_in: usize,
+    keys: HashMap<Vec<u8>, (ProvingKey<E::G1Affine>, Protocol<E::G1>)>,
+    _marker: PhantomData<(AS, P)>,
+}
+
+impl<'params, E, AS, P, const LIMBS: usize, const BITS: usize>
+    AggregationTree<'params, E, AS, P, LIMBS, BITS>
+where
+    E: MultiMillerLoop + Debug,
+    E::G1Affine: CurveAffine<ScalarExt = E::Scalar, CurveExt = E::G1>,
+    P: Prover<'params, KZGCommitmentScheme<E>>,
+    for<'c> AS: AccumulationScheme<
+        E::G1,
+        NativeLoader,
+        NativeAggregationTranscript<E::G1Affine, &'c [u8], LIMBS, BITS>,
+        SameCurveAccumulation<E::G1, NativeLoader, LIMBS, BITS>,
+    >,
+    for<'a, 'b, 'c> AS: AccumulationScheme<
+        E::G1,
+        Rc<Halo2Loader<'a, 'b, E::G1Affine, LIMBS, BITS>>,
+        AggregationTranscript<'a, 'b, 'c, E::G1Affine, LIMBS, BITS>,
+        SameCurveAccumulation<
+            E::G1,
+            Rc<Halo2Loader<'a, 'b, E::G1Affine, LIMBS, BITS>>,
+            LIMBS,
+            BITS,
+        >,
+    >,
+{
+    /// Create a builder with `params` large enough for aggregating `fan_in`
+    /// snarks in a single circuit.
+    pub fn new(params: &'params ParamsKZG<E>, fan_in: usize) -> Self {
+        assert!(fan_in > 1);
+        Self {
+            params,
+            fan_in,
+            keys: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns number of layers needed to aggregate `num_snarks` snarks.
+    pub fn num_layers(&self, num_snarks: usize) -> usize {
+        let mut num_layers = 1;
+        let mut num_snarks = num_snarks;
+        while num_snarks > self.fan_in {
+            num_snarks = num_snarks.div_ceil(self.fan_in);
+            num_layers += 1;
+        }
+        num_layers
+    }
+
+    /// Returns cached proving key of aggregation circuit for snarks of
+    /// `protocols` in order, if any.
+    pub fn proving_key(&self, protocols: &[Protocol<E::G1>]) -> Option<&ProvingKey<E::G1Affine>> {
+        self.keys.get(&Self::key(protocols)).map(|(pk, _)| pk)
+    }
+
+    /// Insert a previously generated proving key of aggregation circuit for
+    /// snarks of `protocols` in order into cache.
+    pub fn insert_proving_key(
+        &mut self,
+        protocols: &[Protocol<E::G1>],
+        pk: ProvingKey<E::G1Affine>,
+    ) {
+        self.insert_proving_key_by_key(Self::key(protocols), pk);
+    }
+
+    fn insert_proving_key_by_key(&mut self, key: Vec<u8>, pk: ProvingKey<E::G1Affine>) {
+        let protocol = compile(
+            pk.get_vk(),
+            AggregationCircuit::<E::G1Affine, AS, LIMBS, BITS>::config(false),
+        );
+        self.keys.insert(key, (pk, protocol));
+    }
+
+    fn key<'a>(protocols: impl IntoIterator<Item = &'a Protocol<E::G1>>) -> Vec<u8> {
+        protocols
+            .into_iter()
+            .flat_map(|protocol| protocol.digest().to_repr().as_ref().to_vec())
+            .collect()
+    }
+
+    /// Aggregate `snarks` layer by layer until only one is left. Layers below
+    /// the root are proved with [`NativeAggregationTranscript`], and the root
+    /// is proved with `TW`, e.g. `EvmTranscript` for a snark verifiable on
+    /// EVM.
+    ///
+    /// Returns [`Error::Halo2`] if keygen or proving fails, e.g. `params` is
+    /// too small for any layer.
+    pub fn aggregate<TW, EC, R>(
+        &mut self,
+        snarks: impl IntoIterator<Item = Snark<E::G1>>,
+        mut rng: R,
+    ) -> Result<Snark<E::G1>, Error>
+    where
+        TW: TranscriptWriterBuffer<Vec<u8>, E::G1Affine, EC>,
+        EC: EncodedChallenge<E::G1Affine>,
+        R: RngCore,
+    {
+        let g1 = self.params.get_g()[0];
+        let mut snarks = snarks.into_iter().collect::<Vec<_>>();
+        if snarks.is_empty() {
+            return Err(Error::MissingSnark);
+        }
+
+        loop {
+            let is_root = snarks.len() <= self.fan_in;
+            snarks = snarks
+                .chunks(self.fan_in)
+                .map(|snarks| {
+                    let key = Self::key(snarks.iter().map(|snark| &snark.protocol));
+                    let circuit = AggregationCircuit::<E::G1Affine, AS, LIMBS, BITS>::new(
+                        g1,
+                        snarks.to_vec(),
+                        None,
+                    )?;
+                    if is_root {
+                        self.prove::<TW, EC, _>(key, circuit, &mut rng)
+                    } else {
+                        self.prove::<IntermediateTranscript<_, LIMBS, BITS>, ChallengeScalar<_>, _>(
+                            key, circuit, &mut rng,
+                        )
+                    }
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            if is_root {
+                return Ok(snarks.pop().unwrap());
+            }
+        }
+    }
+
+    fn prove<TW, EC, R>(
+        &mut self,
+        key: Vec<u8>,
+        circuit: AggregationCircuit<E::G1Affine, AS, LIMBS, BITS>,
+        rng: R,
+    ) -> Result<Snark<E::G1>, Error>
+    where
+        TW: TranscriptWriterBuffer<Vec<u8>, E::G1Affine, EC>,
+        EC: EncodedChallenge<E::G1Affine>,
+        R: RngCore,
+    {
+        let params = self.params;
+        if !self.keys.contains_key(&key) {
+            let vk = keygen_vk::<KZGCommitmentScheme<E>, _, false>(params, &circuit)
+                .map_err(|err| Error::Halo2(format!("{:?}", err)))?;
+            let pk = keygen_pk::<KZGCommitmentScheme<E>, _, false>(params, vk, &circuit)
+                .map_err(|err| Error::Halo2(format!("{:?}", err)))?;
+            self.insert_proving_key_by_key(key.clone(), pk);
+        }
+        let (pk, protocol) = &self.keys[&key];
+
+        let instances = circuit.instances();
+        let proof = {
+            let instances = instances.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let mut transcript = TW::init(Vec::new());
+            create_proof::<KZGCommitmentScheme<E>, P, _, _, _, _, false>(
+                params,
+                pk,
+                &[circuit],
+                &[instances.as_slice()],
+                rng,
+                &mut transcript,
+            )
+            .map_err(|err| Error::Halo2(format!("{:?}", err)))?;
+            transcript.finalize()
+        };
+
+        Ok(Snark::new(protocol.clone(), instances, proof))
+    }
+}
@@ -1,22 +1,23 @@
 use crate::{
-    loader::{EcPointLoader, LoadedEcPoint, LoadedScalar, Loader, ScalarLoader},
-    util::{Curve, Field, FieldOps, Group},
+    loader::{EcPointLoader, LoadedEcPoint, LoadedScalar, Loader, MsmFolding, ScalarLoader},
+    util::{fe_from_limbs, fe_to_limbs, Curve, Field, FieldOps, Group, GroupEncoding, PrimeField},
 };
 use halo2_curves::CurveAffine;
 use halo2_proofs::circuit;
 use halo2_wrong_ecc::{
     integer::{
         rns::{Integer, Rns},
-        IntegerInstructions, Range,
+        AssignedInteger, IntegerInstructions, Range,
     },
     AssignedPoint, BaseFieldEccChip, EccConfig,
 };
 use halo2_wrong_maingate::{
-    AssignedValue, CombinationOptionCommon, MainGate, MainGateInstructions, RegionCtx, Term,
+    AssignedCondition, AssignedValue, CombinationOptionCommon, MainGate, MainGateInstructions,
+    RegionCtx, Term,
 };
-use rand::rngs::OsRng;
 use std::{
     cell::RefCell,
+    collections::{HashMap, HashSet},
     fmt::{self, Debug},
     iter,
     ops::{Add, AddAssign, Deref, DerefMut, Mul, MulAssign, Neg, Sub, SubAssign},
@@ -25,18 +26,88 @@ use std::{
 
 const WINDOW_SIZE: usize = 3;
 
-#[derive(Clone, Debug)]
+const AUX_GENERATOR_DOMAIN: &[u8] = b"plonk-verifier/halo2-loader/aux-generator";
+
+const FIXED_BASE_WINDOW_SIZE: usize = 4;
+
+const FIXED_BASE_AUX_DOMAIN: &[u8] = b"plonk-verifier/halo2-loader/fixed-base-aux";
+
+/// Window tables of a fixed base `P`, where the `j`-th point of the `i`-th
+/// window is `j * 2^(w * i) * P + 2^i * aux`. The offset `aux` is derived from
+/// `P` with unknown discrete log, so selected points and partial sums never
+/// collide in incomplete addition, even when several windows (or tables of
+/// different bases) select the zero digit. The offsets are cancelled by adding
+/// `correction` once per scalar multiplication.
+///
+/// Tables are never assigned. Each limb of a selected point is constrained as
+/// the multilinear polynomial in bits of the window that interpolates the limbs
+/// of the window's points, whose coefficients are constants of main gate and so
+/// live in fixed columns. A multiplication costs the monomials of bits and a
+/// combination per limb for each window, and an addition between windows,
+/// which is cheaper than a pair of variable-base msm, so constant bases known
+/// ahead such as preprocessed commitments of a protocol can be registered by
+/// [`Halo2Loader::enable_fixed_base_tables`].
+struct FixedBaseTable<C: CurveAffine> {
+    windows: Vec<Vec<C>>,
+    correction: C::CurveExt,
+}
+
+/// Counts and rows spent on fixed-base scalar multiplications (excluding table
+/// assignment) and additions of msm so far, used to estimate rows saved by
+/// folding constant terms.
+#[derive(Clone, Copy, Debug, Default)]
+struct MsmRows {
+    fixed_base_muls: (usize, usize),
+    additions: (usize, usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Value<T, L> {
     Constant(T),
     Assigned(L),
 }
 
+/// Derive a point with unknown discrete log from `domain` by hashing it with
+/// an incrementing counter to `x` until `x^3 + a * x + b` is a square.
+pub fn hash_to_aux_generator<C: CurveAffine>(domain: &[u8]) -> C {
+    (0u64..)
+        .find_map(|counter| {
+            let hash = blake2b_simd::Params::new()
+                .hash_length(64)
+                .to_state()
+                .update(domain)
+                .update(&counter.to_le_bytes())
+                .finalize();
+            let mut repr = <C::Base as PrimeField>::Repr::default();
+            let len = repr.as_ref().len();
+            repr.as_mut().copy_from_slice(&hash.as_bytes()[..len]);
+
+            let x = Option::<C::Base>::from(C::Base::from_repr(repr))?;
+            let y = Option::<C::Base>::from((x.square() * x + C::a() * x + C::b()).sqrt())?;
+            Option::<C>::from(C::from_xy(x, y))
+        })
+        .unwrap()
+}
+
 pub struct Halo2Loader<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> {
     rns: Rc<Rns<C::Base, C::Scalar, LIMBS, BITS>>,
     ecc_chip: RefCell<BaseFieldEccChip<C, LIMBS, BITS>>,
     main_gate: MainGate<C::Scalar>,
     ctx: RefCell<RegionCtx<'a, 'b, C::Scalar>>,
     num_ec_point: RefCell<usize>,
+    msm_folding: RefCell<MsmFolding>,
+    msm_rows: RefCell<MsmRows>,
+    /// Encodings of constant bases multiplied with window tables, other
+    /// constant bases are assigned and take the variable-base path.
+    fixed_bases: RefCell<HashSet<Vec<u8>>>,
+    fixed_base_tables: RefCell<HashMap<Vec<u8>, Rc<FixedBaseTable<C>>>>,
+    /// Constant points assigned so far keyed by their encoding, so each
+    /// constant is assigned only once.
+    assigned_constants: RefCell<HashMap<Vec<u8>, AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>>>,
+    aux_generator: C,
+    /// Numbers of pairs the aux has been assigned for, which is `None` until
+    /// the aux generator itself is assigned.
+    assigned_aux: RefCell<Option<HashSet<usize>>>,
     #[cfg(test)]
     row_meterings: RefCell<Vec<(String, usize)>>,
 }
@@ -44,7 +115,19 @@ pub struct Halo2Loader<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: u
 impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
     Halo2Loader<'a, 'b, C, LIMBS, BITS>
 {
+    /// Create a loader with aux generator derived from a fixed domain
+    /// separator, so witness generation is reproducible across runs.
     pub fn new(ecc_config: EccConfig, ctx: RegionCtx<'a, 'b, C::Scalar>) -> Rc<Self> {
+        Self::with_aux_generator(ecc_config, ctx, hash_to_aux_generator(AUX_GENERATOR_DOMAIN))
+    }
+
+    /// Create a loader with caller-supplied aux generator, which should have
+    /// unknown discrete log (e.g. from [`hash_to_aux_generator`]).
+    pub fn with_aux_generator(
+        ecc_config: EccConfig,
+        ctx: RegionCtx<'a, 'b, C::Scalar>,
+        aux_generator: C,
+    ) -> Rc<Self> {
         let ecc_chip = BaseFieldEccChip::new(ecc_config);
         let main_gate = ecc_chip.main_gate();
         Rc::new(Self {
@@ -53,6 +136,13 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
             main_gate,
             ctx: RefCell::new(ctx),
             num_ec_point: RefCell::new(0),
+            msm_folding: RefCell::new(MsmFolding::default()),
+            msm_rows: RefCell::new(MsmRows::default()),
+            fixed_bases: RefCell::new(HashSet::new()),
+            fixed_base_tables: RefCell::new(HashMap::new()),
+            assigned_constants: RefCell::new(HashMap::new()),
+            aux_generator,
+            assigned_aux: RefCell::new(None),
             #[cfg(test)]
             row_meterings: RefCell::new(Vec::new()),
         })
@@ -102,13 +192,33 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
         }
     }
 
+    pub fn assert_scalar_eq(
+        self: &Rc<Self>,
+        lhs: &Scalar<'a, 'b, C, LIMBS, BITS>,
+        rhs: &Scalar<'a, 'b, C, LIMBS, BITS>,
+    ) {
+        let (lhs, rhs) = (lhs.assigned(), rhs.assigned());
+        self.main_gate
+            .assert_equal(&mut self.ctx_mut(), &lhs, &rhs)
+            .unwrap();
+    }
+
+    /// Assign constant point, which is cached so assigning the same constant
+    /// again reuses the cells.
     pub fn assign_const_ec_point(self: &Rc<Self>, ec_point: C) -> EcPoint<'a, 'b, C, LIMBS, BITS> {
+        let key = ec_point.to_bytes().as_ref().to_vec();
         let assigned = self
-            .ecc_chip
-            .borrow()
-            .assign_constant(&mut self.ctx_mut(), ec_point)
-            .unwrap();
-        self.ec_point(assigned)
+            .assigned_constants
+            .borrow_mut()
+            .entry(key)
+            .or_insert_with(|| {
+                self.ecc_chip
+                    .borrow()
+                    .assign_constant(&mut self.ctx_mut(), ec_point)
+                    .unwrap()
+            })
+            .clone();
+        self.ec_point(Value::Assigned(assigned))
     }
 
     pub fn assign_ec_point(
@@ -120,74 +230,418 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
             .borrow()
             .assign_point(&mut self.ctx_mut(), ec_point)
             .unwrap();
-        self.ec_point(assigned)
+        self.ec_point(Value::Assigned(assigned))
     }
 
+    /// Assign point from limbs of `x` and `y`, which are range checked and
+    /// constrained to be canonical and on curve, where identity can't pass as
+    /// `(0, 0)` is not on curve.
     pub fn assign_ec_point_from_limbs(
         self: &Rc<Self>,
         x_limbs: [AssignedValue<C::Scalar>; LIMBS],
         y_limbs: [AssignedValue<C::Scalar>; LIMBS],
     ) -> EcPoint<'a, 'b, C, LIMBS, BITS> {
         let [x, y] = [&x_limbs, &y_limbs]
-            .map(|limbs| {
-                limbs.iter().enumerate().fold(
-                    circuit::Value::known([C::Scalar::zero(); LIMBS]),
-                    |acc, (idx, limb)| {
-                        acc.zip(limb.value()).map(|(mut acc, limb)| {
-                            acc[idx] = *limb;
-                            acc
-                        })
-                    },
-                )
-            })
-            .map(|limbs| {
-                self.ecc_chip
-                    .borrow()
-                    .integer_chip()
-                    .assign_integer(
-                        &mut self.ctx_mut(),
-                        limbs
-                            .map(|limbs| Integer::from_limbs(&limbs, self.rns.clone()))
-                            .into(),
-                        Range::Remainder,
-                    )
-                    .unwrap()
-            });
+            .map(|limbs| self.assign_integer_from_limbs(Self::limbs_value(limbs)));
 
         let ec_point = AssignedPoint::new(x, y);
         self.ecc_chip()
             .assert_is_on_curve(&mut self.ctx_mut(), &ec_point)
             .unwrap();
 
-        for (src, dst) in x_limbs.iter().chain(y_limbs.iter()).zip(
-            ec_point
-                .get_x()
-                .limbs()
-                .iter()
-                .chain(ec_point.get_y().limbs().iter()),
-        ) {
+        self.constrain_limbs_equal(&x_limbs, ec_point.get_x());
+        self.constrain_limbs_equal(&y_limbs, ec_point.get_y());
+
+        self.ec_point(Value::Assigned(ec_point))
+    }
+
+    /// Assign point from limbs of `x` and `sign`, the parity of `y`, where `y`
+    /// is witnessed as the square root with the given parity and constrained
+    /// to be canonical and on curve. The `sign` is constrained to be a bit by
+    /// equality with the least significant bit of `y`.
+    pub fn assign_ec_point_from_compressed(
+        self: &Rc<Self>,
+        x_limbs: [AssignedValue<C::Scalar>; LIMBS],
+        sign: AssignedValue<C::Scalar>,
+    ) -> EcPoint<'a, 'b, C, LIMBS, BITS> {
+        let x_value = Self::limbs_value(&x_limbs);
+        let y_value = x_value.zip(sign.value()).map(|(x_limbs, sign)| {
+            let x = fe_from_limbs::<_, C::Base, LIMBS, BITS>(x_limbs);
+            let y = Option::<C::Base>::from((x.square() * x + C::a() * x + C::b()).sqrt())
+                .unwrap_or_else(C::Base::zero);
+            let is_odd = bool::from(y.is_odd());
+            let y = if is_odd == (*sign == C::Scalar::one()) {
+                y
+            } else {
+                -y
+            };
+            fe_to_limbs::<_, C::Scalar, LIMBS, BITS>(y)
+        });
+
+        let x = self.assign_integer_from_limbs(x_value);
+        let y = self.assign_integer_from_limbs(y_value);
+
+        let ec_point = AssignedPoint::new(x, y);
+        self.ecc_chip()
+            .assert_is_on_curve(&mut self.ctx_mut(), &ec_point)
+            .unwrap();
+
+        self.constrain_limbs_equal(&x_limbs, ec_point.get_x());
+
+        let y_limb_0 = ec_point.get_y().limbs()[0].as_ref().clone();
+        let bits = self
+            .main_gate
+            .to_bits(&mut self.ctx_mut(), &y_limb_0, BITS)
+            .unwrap();
+        self.main_gate
+            .assert_equal(&mut self.ctx_mut(), &bits[0], &sign)
+            .unwrap();
+
+        self.ec_point(Value::Assigned(ec_point))
+    }
+
+    fn limbs_value(
+        limbs: &[AssignedValue<C::Scalar>; LIMBS],
+    ) -> circuit::Value<[C::Scalar; LIMBS]> {
+        limbs.iter().enumerate().fold(
+            circuit::Value::known([C::Scalar::zero(); LIMBS]),
+            |acc, (idx, limb)| {
+                acc.zip(limb.value()).map(|(mut acc, limb)| {
+                    acc[idx] = *limb;
+                    acc
+                })
+            },
+        )
+    }
+
+    /// Assign integer with limbs range checked by `Range::Remainder` and
+    /// value constrained to be less than base field modulus.
+    fn assign_integer_from_limbs(
+        &self,
+        limbs: circuit::Value<[C::Scalar; LIMBS]>,
+    ) -> AssignedInteger<C::Base, C::Scalar, LIMBS, BITS> {
+        let integer = self.assign_integer(limbs);
+        self.ecc_chip()
+            .integer_chip()
+            .assert_in_field(&mut self.ctx_mut(), &integer)
+            .unwrap();
+        integer
+    }
+
+    /// Assign integer with limbs range checked by `Range::Remainder`.
+    fn assign_integer(
+        &self,
+        limbs: circuit::Value<[C::Scalar; LIMBS]>,
+    ) -> AssignedInteger<C::Base, C::Scalar, LIMBS, BITS> {
+        self.ecc_chip()
+            .integer_chip()
+            .assign_integer(
+                &mut self.ctx_mut(),
+                limbs
+                    .map(|limbs| Integer::from_limbs(&limbs, self.rns.clone()))
+                    .into(),
+                Range::Remainder,
+            )
+            .unwrap()
+    }
+
+    fn constrain_limbs_equal(
+        &self,
+        limbs: &[AssignedValue<C::Scalar>; LIMBS],
+        integer: &AssignedInteger<C::Base, C::Scalar, LIMBS, BITS>,
+    ) {
+        for (src, dst) in limbs.iter().zip(integer.limbs().iter()) {
             self.ctx
                 .borrow_mut()
                 .constrain_equal(src.cell(), dst.as_ref().cell())
                 .unwrap();
         }
-
-        self.ec_point(ec_point)
     }
 
     pub fn ec_point(
         self: &Rc<Self>,
-        assigned: AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>,
+        value: Value<C, AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>>,
     ) -> EcPoint<'a, 'b, C, LIMBS, BITS> {
         let index = *self.num_ec_point.borrow();
         *self.num_ec_point.borrow_mut() += 1;
         EcPoint {
             loader: self.clone(),
             index,
-            assigned,
+            value,
         }
     }
 
+    /// Returns scalar multiplications and additions saved by folding constant
+    /// terms of msm at synthesis time.
+    pub fn msm_folding(&self) -> MsmFolding {
+        *self.msm_folding.borrow()
+    }
+
+    /// Returns rows saved by folding constant terms of msm, estimated by rows
+    /// spent per fixed-base scalar multiplication and addition of msm so far,
+    /// since a constant term would otherwise take the fixed-base path.
+    ///
+    /// It's an upper bound, as the folded constant point itself is assigned,
+    /// and it's zero for operations that haven't been metered yet, so scalar
+    /// multiplications are counted only when fixed-base tables are enabled.
+    pub fn msm_folding_rows(&self) -> usize {
+        let folding = self.msm_folding();
+        let rows = self.msm_rows.borrow();
+        [
+            (folding.scalar_muls, rows.fixed_base_muls),
+            (folding.additions, rows.additions),
+        ]
+        .into_iter()
+        .map(|(saved, (count, rows))| if count == 0 { 0 } else { saved * rows / count })
+        .sum()
+    }
+
+    /// Multiply given constant bases with window tables in fixed columns in
+    /// msm instead of assigning them as variable bases, see
+    /// [`FixedBaseTable`].
+    pub fn enable_fixed_base_tables(&self, bases: impl IntoIterator<Item = C>) {
+        self.fixed_bases.borrow_mut().extend(
+            bases
+                .into_iter()
+                .map(|base| base.to_bytes().as_ref().to_vec()),
+        );
+    }
+
+    fn is_fixed_base(&self, base: &C) -> bool {
+        self.fixed_bases.borrow().contains(base.to_bytes().as_ref())
+    }
+
+    fn fixed_base_table(&self, base: C) -> Rc<FixedBaseTable<C>> {
+        let key = base.to_bytes().as_ref().to_vec();
+        if let Some(table) = self.fixed_base_tables.borrow().get(&key) {
+            return table.clone();
+        }
+
+        let aux = hash_to_aux_generator::<C>(&[FIXED_BASE_AUX_DOMAIN, key.as_slice()].concat())
+            .to_curve();
+        let num_bits = C::Scalar::NUM_BITS as usize;
+        let num_windows = num_bits.div_ceil(FIXED_BASE_WINDOW_SIZE);
+        let mut window_base = base.to_curve();
+        let mut window_aux = aux;
+        let windows = (0..num_windows)
+            .map(|idx| {
+                let window_size =
+                    FIXED_BASE_WINDOW_SIZE.min(num_bits - idx * FIXED_BASE_WINDOW_SIZE);
+                let points = iter::successors(Some(window_aux), |acc| Some(*acc + window_base))
+                    .take(1 << window_size)
+                    .map(|point| point.to_affine())
+                    .collect::<Vec<_>>();
+                for _ in 0..window_size {
+                    window_base = window_base.double();
+                }
+                window_aux = window_aux.double();
+                points
+            })
+            .collect();
+        let table = Rc::new(FixedBaseTable {
+            windows,
+            correction: aux - window_aux,
+        });
+
+        self.fixed_base_tables
+            .borrow_mut()
+            .insert(key, table.clone());
+        table
+    }
+
+    /// Multiply a fixed base by an assigned scalar with precomputed window
+    /// tables, and return the output along with the constant correction to be
+    /// added to the final sum.
+    fn fixed_base_mul(
+        self: &Rc<Self>,
+        scalar: &AssignedValue<C::Scalar>,
+        base: C,
+    ) -> (AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>, C::CurveExt) {
+        let table = self.fixed_base_table(base);
+        let bits = self
+            .main_gate
+            .to_bits(&mut self.ctx_mut(), scalar, C::Scalar::NUM_BITS as usize)
+            .unwrap();
+        let output = table
+            .windows
+            .iter()
+            .zip(bits.chunks(FIXED_BASE_WINDOW_SIZE))
+            .map(|(points, bits)| self.select_from_table(points, bits))
+            .reduce(|acc, point| {
+                self.ecc_chip()
+                    .add(&mut self.ctx_mut(), &acc, &point)
+                    .unwrap()
+            })
+            .unwrap();
+        (output, table.correction)
+    }
+
+    /// Select the point indexed by little-endian `bits` from `points`, where
+    /// each limb is constrained to the combination of monomials of `bits` with
+    /// coefficients interpolating the limbs of `points`.
+    fn select_from_table(
+        &self,
+        points: &[C],
+        bits: &[AssignedCondition<C::Scalar>],
+    ) -> AssignedPoint<C::Base, C::Scalar, LIMBS, BITS> {
+        // Monomial of the subset of bits given by each index as mask, where the
+        // empty one is the constant term
+        let mut monomials = vec![None];
+        for bit in bits {
+            let products = monomials
+                .iter()
+                .map(|monomial| {
+                    Some(match monomial {
+                        Some(monomial) => self
+                            .main_gate
+                            .mul(&mut self.ctx_mut(), monomial, bit)
+                            .unwrap(),
+                        None => bit.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            monomials.extend(products);
+        }
+
+        let index = bits
+            .iter()
+            .rev()
+            .fold(circuit::Value::known(0), |acc, bit| {
+                acc.zip(bit.value())
+                    .map(|(acc, bit)| 2 * acc + (*bit == C::Scalar::one()) as usize)
+            });
+        let [x, y] = [true, false].map(|is_x| {
+            let limbs = points
+                .iter()
+                .map(|point| {
+                    let coordinates = point.coordinates().unwrap();
+                    let coordinate = if is_x {
+                        coordinates.x()
+                    } else {
+                        coordinates.y()
+                    };
+                    fe_to_limbs::<_, C::Scalar, LIMBS, BITS>(*coordinate)
+                })
+                .collect::<Vec<_>>();
+            let combinations: [_; LIMBS] = (0..LIMBS)
+                .map(|idx| {
+                    // Mobius transform of limbs into coefficients of monomials
+                    let mut coeffs = limbs.iter().map(|limbs| limbs[idx]).collect::<Vec<_>>();
+                    for bit in 0..bits.len() {
+                        for mask in 0..coeffs.len() {
+                            if mask & (1 << bit) != 0 {
+                                let coeff = coeffs[mask ^ (1 << bit)];
+                                coeffs[mask] -= coeff;
+                            }
+                        }
+                    }
+                    self.fixed_combination(&monomials, &coeffs)
+                })
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap();
+
+            // Limbs are constrained to ones of a point in table, so they are
+            // not checked to be in field again
+            let integer = self.assign_integer(index.map(|index| limbs[index]));
+            self.constrain_limbs_equal(&combinations, &integer);
+            integer
+        });
+        AssignedPoint::new(x, y)
+    }
+
+    /// Returns `coeffs[0] + sum(coeffs[i] * monomials[i])` for `i > 0`, with
+    /// coefficients as constants of main gate, chained over rows with the
+    /// partial sum.
+    fn fixed_combination(
+        &self,
+        monomials: &[Option<AssignedValue<C::Scalar>>],
+        coeffs: &[C::Scalar],
+    ) -> AssignedValue<C::Scalar> {
+        let terms = monomials
+            .iter()
+            .zip(coeffs.iter())
+            .skip(1)
+            .map(|(monomial, coeff)| (monomial.as_ref().unwrap(), *coeff))
+            .collect::<Vec<_>>();
+        let mut constant = coeffs[0];
+        let mut acc = None;
+        for chunk in terms.chunks(3) {
+            let value =
+                iter::empty()
+                    .chain(
+                        acc.as_ref()
+                            .map(|acc: &AssignedValue<C::Scalar>| acc.value().map(|acc| *acc)),
+                    )
+                    .chain(chunk.iter().map(|(monomial, coeff)| {
+                        monomial.value().map(|monomial| *monomial * coeff)
+                    }))
+                    .fold(circuit::Value::known(constant), |acc, value| {
+                        acc.zip(value).map(|(acc, value)| acc + value)
+                    });
+            let row = iter::empty()
+                .chain(
+                    acc.as_ref()
+                        .map(|acc| Term::Assigned(acc, C::Scalar::one())),
+                )
+                .chain(
+                    chunk
+                        .iter()
+                        .map(|(monomial, coeff)| Term::Assigned(*monomial, *coeff)),
+                )
+                .chain(iter::once(Term::unassigned_to_sub(value)))
+                .collect::<Vec<_>>();
+            acc = MainGateInstructions::apply(
+                &self.main_gate,
+                &mut self.ctx_mut(),
+                row,
+                constant,
+                CombinationOptionCommon::OneLinerAdd.into(),
+            )
+            .unwrap()
+            .pop();
+            constant = C::Scalar::zero();
+        }
+        acc.unwrap()
+    }
+
+    /// Assign aux generator once per loader, and aux for `num_pairs` once per
+    /// distinct `num_pairs`, to be reused by later msm.
+    fn assign_aux(&self, num_pairs: usize) {
+        let mut assigned_aux = self.assigned_aux.borrow_mut();
+        let assigned_aux = assigned_aux.get_or_insert_with(|| {
+            self.ecc_chip
+                .borrow_mut()
+                .assign_aux_generator(
+                    &mut self.ctx_mut(),
+                    circuit::Value::known(self.aux_generator),
+                )
+                .unwrap();
+            HashSet::new()
+        });
+        if assigned_aux.insert(num_pairs) {
+            self.ecc_chip
+                .borrow_mut()
+                .assign_aux(&mut self.ctx_mut(), WINDOW_SIZE, num_pairs)
+                .unwrap();
+        }
+    }
+
+    fn meter_msm_rows<T>(&self, is_fixed_base_mul: bool, op: impl FnOnce() -> T) -> T {
+        let offset = *self.ctx.borrow().offset;
+        let output = op();
+        let rows = *self.ctx.borrow().offset - offset;
+        let mut msm_rows = self.msm_rows.borrow_mut();
+        let (total_count, total_rows) = if is_fixed_base_mul {
+            &mut msm_rows.fixed_base_muls
+        } else {
+            &mut msm_rows.additions
+        };
+        *total_count += 1;
+        *total_rows += rows;
+        output
+    }
+
     pub fn ec_point_nomalize(
         self: &Rc<Self>,
         assigned: &AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>,
@@ -352,6 +806,10 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
             println!("{}: {}", identifier, cost);
         }
     }
+
+    pub fn row_meterings(&self) -> Vec<(String, usize)> {
+        self.row_meterings.borrow().clone()
+    }
 }
 
 #[derive(Clone)]
@@ -377,6 +835,13 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> LoadedScalar
     fn loader(&self) -> &Self::Loader {
         &self.loader
     }
+
+    fn as_const(&self) -> Option<C::Scalar> {
+        match &self.value {
+            Value::Constant(constant) => Some(*constant),
+            Value::Assigned(_) => None,
+        }
+    }
 }
 
 impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> Debug
@@ -519,14 +984,17 @@ impl<'a, 'b, 'c, C: CurveAffine, const LIMBS: usize, const BITS: usize> MulAssig
 pub struct EcPoint<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> {
     loader: Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>,
     index: usize,
-    assigned: AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>,
+    value: Value<C, AssignedPoint<C::Base, C::Scalar, LIMBS, BITS>>,
 }
 
 impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize>
     EcPoint<'a, 'b, C, LIMBS, BITS>
 {
     pub fn assigned(&self) -> AssignedPoint<C::Base, C::Scalar, LIMBS, BITS> {
-        self.assigned.clone()
+        match &self.value {
+            Value::Constant(constant) => self.loader.assign_const_ec_point(*constant).assigned(),
+            Value::Assigned(assigned) => assigned.clone(),
+        }
     }
 }
 
@@ -542,48 +1010,66 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> LoadedEcPoin
     for EcPoint<'a, 'b, C, LIMBS, BITS>
 {
     type Loader = Rc<Halo2Loader<'a, 'b, C, LIMBS, BITS>>;
+    type Key = Value<Vec<u8>, usize>;
 
     fn loader(&self) -> &Self::Loader {
         &self.loader
     }
 
+    fn key(&self) -> Option<Value<Vec<u8>, usize>> {
+        Some(match &self.value {
+            Value::Constant(constant) => Value::Constant(constant.to_bytes().as_ref().to_vec()),
+            Value::Assigned(_) => Value::Assigned(self.index),
+        })
+    }
+
+    fn as_const(&self) -> Option<C::CurveExt> {
+        match &self.value {
+            Value::Constant(constant) => Some(constant.to_curve()),
+            Value::Assigned(_) => None,
+        }
+    }
+
     fn multi_scalar_multiplication(
         pairs: impl IntoIterator<Item = (Scalar<'a, 'b, C, LIMBS, BITS>, Self)>,
     ) -> Self {
         let pairs = pairs.into_iter().collect::<Vec<_>>();
         let loader = &pairs[0].0.loader;
 
-        let (non_scaled, scaled) = pairs.iter().fold(
-            (Vec::new(), Vec::new()),
-            |(mut non_scaled, mut scaled), (scalar, ec_point)| {
-                if matches!(scalar.value, Value::Constant(constant) if constant == C::Scalar::one())
-                {
-                    non_scaled.push(ec_point.assigned());
-                } else {
-                    scaled.push((ec_point.assigned(), scalar.assigned()))
+        let (non_scaled, fixed_based, scaled) = pairs.iter().fold(
+            (Vec::new(), Vec::new(), Vec::new()),
+            |(mut non_scaled, mut fixed_based, mut scaled), (scalar, ec_point)| {
+                match (&scalar.value, &ec_point.value) {
+                    (Value::Constant(constant), _) if *constant == C::Scalar::one() => {
+                        non_scaled.push(ec_point.assigned())
+                    }
+                    (_, Value::Constant(base)) if loader.is_fixed_base(base) => {
+                        fixed_based.push((scalar.assigned(), *base))
+                    }
+                    _ => scaled.push((ec_point.assigned(), scalar.assigned())),
                 }
-                (non_scaled, scaled)
+                (non_scaled, fixed_based, scaled)
             },
         );
 
+        let (fixed_based, corrections) = fixed_based
+            .iter()
+            .map(|(scalar, base)| {
+                loader.meter_msm_rows(true, || loader.fixed_base_mul(scalar, *base))
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        let correction = corrections
+            .into_iter()
+            .reduce(|acc, correction| acc + correction)
+            .map(|correction| correction.to_affine())
+            .filter(|correction| !bool::from(correction.is_identity()))
+            .map(|correction| loader.assign_const_ec_point(correction).assigned());
+
         let output = iter::empty()
             .chain(if scaled.is_empty() {
                 None
             } else {
-                let aux_generator = <C as CurveAffine>::CurveExt::random(OsRng).to_affine();
-                loader
-                    .ecc_chip
-                    .borrow_mut()
-                    .assign_aux_generator(
-                        &mut loader.ctx.borrow_mut(),
-                        circuit::Value::known(aux_generator),
-                    )
-                    .unwrap();
-                loader
-                    .ecc_chip
-                    .borrow_mut()
-                    .assign_aux(&mut loader.ctx.borrow_mut(), WINDOW_SIZE, scaled.len())
-                    .unwrap();
+                loader.assign_aux(scaled.len());
                 Some(
                     loader
                         .ecc_chip
@@ -592,14 +1078,18 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> LoadedEcPoin
                         .unwrap(),
                 )
             })
+            .chain(fixed_based)
             .chain(non_scaled)
+            .chain(correction)
             .reduce(|acc, ec_point| {
-                (loader.ecc_chip().deref())
-                    .add(&mut loader.ctx.borrow_mut(), &acc, &ec_point)
-                    .unwrap()
+                loader.meter_msm_rows(false, || {
+                    (loader.ecc_chip().deref())
+                        .add(&mut loader.ctx.borrow_mut(), &acc, &ec_point)
+                        .unwrap()
+                })
             })
             .unwrap();
-        loader.ec_point(output)
+        loader.ec_point(Value::Assigned(output))
     }
 }
 
@@ -609,7 +1099,7 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> Debug
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("EcPoint")
             .field("index", &self.index)
-            .field("assigned", &self.assigned)
+            .field("value", &self.value)
             .finish()
     }
 }
@@ -712,7 +1202,7 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> EcPointLoade
     type LoadedEcPoint = EcPoint<'a, 'b, C, LIMBS, BITS>;
 
     fn ec_point_load_const(&self, ec_point: &C::CurveExt) -> EcPoint<'a, 'b, C, LIMBS, BITS> {
-        self.assign_const_ec_point(ec_point.to_affine())
+        self.ec_point(Value::Constant(ec_point.to_affine()))
     }
 }
 
@@ -728,4 +1218,8 @@ impl<'a, 'b, C: CurveAffine, const LIMBS: usize, const BITS: usize> Loader<C::Cu
     fn end_cost_metering(&self) {
         self.end_row_metering()
     }
+
+    fn record_msm_folding(&self, folding: MsmFolding) {
+        self.msm_folding.borrow_mut().merge(folding)
+    }
 }
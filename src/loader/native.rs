@@ -1,4 +1,7 @@
 mod accumulation;
+mod batch;
 mod loader;
 
-pub use loader::NativeLoader;
+pub use accumulation::{AccumulatorCheckpoint, CycleAccumulator};
+pub use batch::{batch_verify, MaybeSync};
+pub use loader::{EcPointKey, NativeLoader};
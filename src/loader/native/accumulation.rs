@@ -1,24 +1,36 @@
 use crate::{
     loader::native::NativeLoader,
     protocol::Protocol,
-    scheme::kzg::{AccumulationStrategy, Accumulator, SameCurveAccumulation, MSM},
-    util::{fe_from_limbs, Curve, Group, PrimeCurveAffine, Transcript},
+    scheme::kzg::{
+        AccumulationStrategy, Accumulator, AccumulatorEncoding, CompressedEncoding,
+        CycleCurveAccumulation, HashCommittedEncoding, LimbsEncoding, SameCurveAccumulation, MSM,
+    },
+    util::{
+        fe_from_limbs_checked, fe_to_limbs, Curve, Field, Group, GroupEncoding, PrimeCurveAffine,
+        PrimeField, Transcript,
+    },
     Error,
 };
 use halo2_curves::{
     pairing::{MillerLoopResult, MultiMillerLoop},
     CurveAffine, CurveExt,
 };
+use std::{
+    io::{self, Read, Write},
+    iter,
+};
 
-impl<C: Curve, const LIMBS: usize, const BITS: usize>
+impl<C: Curve + GroupEncoding, const LIMBS: usize, const BITS: usize>
     SameCurveAccumulation<C, NativeLoader, LIMBS, BITS>
 {
-    pub fn finalize(self, g1: C) -> (C, C) {
-        self.accumulator.unwrap().evaluate(g1)
+    pub fn finalize(self, g1: C) -> Result<(C, C), Error> {
+        self.accumulator
+            .ok_or(Error::MissingAccumulator)?
+            .evaluate(g1)
     }
 }
 
-impl<C: Curve, const LIMBS: usize, const BITS: usize>
+impl<C: Curve + GroupEncoding, const LIMBS: usize, const BITS: usize>
     SameCurveAccumulation<C, NativeLoader, LIMBS, BITS>
 {
     pub fn decide<M: MultiMillerLoop<G1 = C>>(
@@ -26,17 +38,155 @@ impl<C: Curve, const LIMBS: usize, const BITS: usize>
         g1: M::G1Affine,
         g2: M::G2Affine,
         s_g2: M::G2Affine,
-    ) -> bool {
-        let (lhs, rhs) = self.finalize(g1.to_curve());
+    ) -> Result<bool, Error> {
+        self.decide_with_g2s::<M>(g1, &[g2, -s_g2])
+    }
+
+    /// Decide accumulator with each term paired with the G2 point in `g2s` by
+    /// its index, which fails with [`Error::MissingG2`] if the index is out of
+    /// range.
+    pub fn decide_with_g2s<M: MultiMillerLoop<G1 = C>>(
+        self,
+        g1: M::G1Affine,
+        g2s: &[M::G2Affine],
+    ) -> Result<bool, Error> {
+        let accumulator = self.accumulator.ok_or(Error::MissingAccumulator)?;
+        accumulator.check_g2_indices(g2s.len())?;
+
+        Ok(pairing_check::<M>(
+            accumulator
+                .evaluate_terms(g1.to_curve())
+                .into_iter()
+                .map(|(ec_point, g2_index)| (ec_point, g2s[g2_index]))
+                .collect(),
+        ))
+    }
+}
+
+/// Returns whether the product of pairings of `terms` is one.
+fn pairing_check<M: MultiMillerLoop>(terms: Vec<(M::G1, M::G2Affine)>) -> bool {
+    let terms = terms
+        .into_iter()
+        .map(|(ec_point, g2)| (M::G1Affine::from(ec_point), M::G2Prepared::from(g2)))
+        .collect::<Vec<_>>();
+
+    M::multi_miller_loop(&terms.iter().map(|(g1, g2)| (g1, g2)).collect::<Vec<_>>())
+        .final_exponentiation()
+        .is_identity()
+        .into()
+}
+
+/// Decide evaluated accumulator `(lhs, rhs)` paired with `g2` and `-s_g2`.
+fn decide_evaluated<M: MultiMillerLoop>(
+    lhs: M::G1,
+    rhs: M::G1,
+    g2: M::G2Affine,
+    s_g2: M::G2Affine,
+) -> bool {
+    pairing_check::<M>(vec![(lhs, g2), (rhs, -s_g2)])
+}
 
-        let g2 = M::G2Prepared::from(g2);
-        let minus_s_g2 = M::G2Prepared::from(-s_g2);
+impl<C: CurveExt, const LIMBS: usize, const BITS: usize>
+    SameCurveAccumulation<C, NativeLoader, LIMBS, BITS>
+{
+    /// Checkpoint the running accumulator without consuming it, where
+    /// `transcript` is the one of the last accumulated proof. The evaluated
+    /// accumulator is absorbed and a binding is squeezed from it, which seeds
+    /// [`SameCurveAccumulation::binding()`] after resuming, so the next
+    /// combining challenge depends on the checkpointed accumulator.
+    pub fn checkpoint<T: Transcript<C, NativeLoader>>(
+        &self,
+        g1: C,
+        transcript: &mut T,
+    ) -> Result<AccumulatorCheckpoint<C>, Error> {
+        let (lhs, rhs) = self
+            .accumulator
+            .clone()
+            .ok_or(Error::MissingAccumulator)?
+            .evaluate(g1)?;
+        transcript.common_ec_point(&lhs)?;
+        transcript.common_ec_point(&rhs)?;
+        let binding = transcript.squeeze_challenge();
 
-        let terms = [(&lhs.into(), &g2), (&rhs.into(), &minus_s_g2)];
-        M::multi_miller_loop(&terms)
-            .final_exponentiation()
-            .is_identity()
-            .into()
+        Ok(AccumulatorCheckpoint {
+            lhs,
+            rhs,
+            binding,
+            bind_protocols: self.bind_protocols(),
+        })
+    }
+
+    /// Resume accumulation from a checkpoint with its binding, and with
+    /// `bind_protocols` as it was when checkpointing.
+    pub fn resume(checkpoint: AccumulatorCheckpoint<C>) -> Self {
+        let strategy =
+            Self::with_accumulator(checkpoint.accumulator()).with_binding(checkpoint.binding);
+        if checkpoint.bind_protocols {
+            strategy.with_protocol_binding()
+        } else {
+            strategy
+        }
+    }
+}
+
+/// Evaluated `(lhs, rhs)` of a running accumulator with the transcript binding
+/// squeezed after it, which can be persisted and resumed later.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccumulatorCheckpoint<C: CurveExt> {
+    pub lhs: C,
+    pub rhs: C,
+    pub binding: C::ScalarExt,
+    /// Whether protocols were bound when checkpointing, see
+    /// [`SameCurveAccumulation::with_protocol_binding`].
+    pub bind_protocols: bool,
+}
+
+impl<C: CurveExt> AccumulatorCheckpoint<C> {
+    fn accumulator(&self) -> Accumulator<C, NativeLoader> {
+        Accumulator::new(MSM::base(self.lhs), MSM::base(self.rhs))
+    }
+
+    pub fn decide<M: MultiMillerLoop<G1 = C>>(&self, g2: M::G2Affine, s_g2: M::G2Affine) -> bool {
+        decide_evaluated::<M>(self.lhs, self.rhs, g2, s_g2)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.lhs.to_bytes().as_ref())?;
+        writer.write_all(self.rhs.to_bytes().as_ref())?;
+        writer.write_all(self.binding.to_repr().as_ref())?;
+        writer.write_all(&[self.bind_protocols as u8])
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut read_ec_point = || {
+            let mut repr = <C as GroupEncoding>::Repr::default();
+            reader.read_exact(repr.as_mut())?;
+            Option::from(C::from_bytes(&repr)).ok_or_else(|| {
+                io::Error::other("Invalid elliptic curve point encoding in checkpoint")
+            })
+        };
+        let lhs = read_ec_point()?;
+        let rhs = read_ec_point()?;
+
+        let mut repr = <C::ScalarExt as PrimeField>::Repr::default();
+        reader.read_exact(repr.as_mut())?;
+        let binding = Option::from(C::ScalarExt::from_repr(repr))
+            .ok_or_else(|| io::Error::other("Invalid scalar encoding in checkpoint"))?;
+
+        let mut bind_protocols = [0];
+        reader.read_exact(&mut bind_protocols)?;
+        let bind_protocols = match bind_protocols[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(io::Error::other("Invalid flag encoding in checkpoint")),
+        };
+
+        Ok(Self {
+            lhs,
+            rhs,
+            binding,
+            bind_protocols,
+        })
     }
 }
 
@@ -49,63 +199,241 @@ where
     type Output = P;
 
     fn extract_accumulator(
-        &self,
+        &mut self,
         protocol: &Protocol<C>,
-        _: &NativeLoader,
+        loader: &NativeLoader,
         transcript: &mut T,
         statements: &[Vec<C::ScalarExt>],
-    ) -> Option<Accumulator<C, NativeLoader>> {
-        let accumulator_indices = protocol.accumulator_indices.as_ref()?;
+    ) -> Result<Option<Accumulator<C, NativeLoader>>, Error> {
+        self.decode_accumulators(protocol, loader, transcript, statements)
+    }
 
-        let challenges = transcript.squeeze_n_challenges(accumulator_indices.len());
-        let accumulators = accumulator_indices
+    fn process(
+        &mut self,
+        protocol: &Protocol<C>,
+        loader: &NativeLoader,
+        transcript: &mut T,
+        proof: P,
+        accumulator: Accumulator<C, NativeLoader>,
+    ) -> Result<Self::Output, Error> {
+        self.combine(protocol, loader, transcript, accumulator)?;
+        Ok(proof)
+    }
+}
+
+impl<C: CurveExt> CycleCurveAccumulation<C, NativeLoader> {
+    /// Finalize into accumulator `(lhs, rhs)` with the deferred pairs of its
+    /// msm, see [`CycleCurveAccumulation::deferred_terms`].
+    pub fn finalize(self, g1: C) -> Result<CycleAccumulator<C>, Error> {
+        let deferred = self.deferred_terms(&NativeLoader, &g1)?;
+        let (lhs, rhs) = self
+            .accumulator
+            .ok_or(Error::MissingAccumulator)?
+            .evaluate(g1)?;
+        Ok(CycleAccumulator {
+            lhs,
+            rhs,
+            deferred,
+            carried: self.carried_accumulators,
+        })
+    }
+}
+
+// Limbs of a deferred scalar in `CycleAccumulator::base_instances`, which fit
+// in base field of any curve whose scalars are at most 256 bits.
+const SCALAR_LIMBS: usize = 2;
+const SCALAR_BITS: usize = 128;
+
+/// Accumulator `(lhs, rhs)` of [`CycleCurveAccumulation`] with the pairs of
+/// scalar and base of its msm deferred to circuits of both curves, and the
+/// accumulators of the other curve carried forward to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CycleAccumulator<C: CurveExt> {
+    pub lhs: C,
+    pub rhs: C,
+    /// Pairs of scalar and base of msm of `lhs` and `rhs` respectively.
+    pub deferred: Vec<Vec<(C::ScalarExt, C)>>,
+    pub carried: Vec<[C::ScalarExt; 4]>,
+}
+
+impl<C: CurveExt> CycleAccumulator<C> {
+    /// Public inputs of the circuit of the other curve, which is over
+    /// `C::Base`, as `lhs.x, lhs.y, rhs.x, rhs.y` with a single element each,
+    /// followed by each deferred pair as `base.x, base.y` and the scalar in 2
+    /// limbs of 128 bits, so the circuit can recompute `(lhs, rhs)` with native
+    /// points. Identity is `(0, 0)`.
+    pub fn base_instances(&self) -> Vec<C::Base> {
+        let coordinates = |ec_point: &C| {
+            if bool::from(ec_point.is_identity()) {
+                return [C::Base::zero(); 2];
+            }
+            let coordinates = ec_point.to_affine().coordinates().unwrap();
+            [*coordinates.x(), *coordinates.y()]
+        };
+        let limbs =
+            |scalar: &C::ScalarExt| fe_to_limbs::<_, C::Base, SCALAR_LIMBS, SCALAR_BITS>(*scalar);
+        iter::empty()
+            .chain([self.lhs, self.rhs].iter().flat_map(coordinates))
+            .chain(self.deferred.iter().flatten().flat_map(|(scalar, base)| {
+                iter::empty().chain(coordinates(base)).chain(limbs(scalar))
+            }))
+            .collect()
+    }
+
+    /// Public inputs of the circuit on `C`, which is over `C::ScalarExt`, as
+    /// the deferred scalars in the same order as in
+    /// [`CycleAccumulator::base_instances`], followed by coordinates of the
+    /// carried accumulators.
+    pub fn scalar_instances(&self) -> Vec<C::ScalarExt> {
+        self.deferred
             .iter()
-            .map(|indices| {
-                assert_eq!(indices.len(), 4 * LIMBS);
-                let [lhs_x, lhs_y, rhs_x, rhs_y]: [_; 4] = indices
-                    .chunks(4)
-                    .into_iter()
-                    .map(|indices| {
-                        fe_from_limbs::<_, _, LIMBS, BITS>(
-                            indices
-                                .iter()
-                                .map(|index| statements[index.0][index.1])
-                                .collect::<Vec<_>>()
-                                .try_into()
-                                .unwrap(),
-                        )
-                    })
-                    .collect::<Vec<_>>()
-                    .try_into()
-                    .unwrap();
-                let lhs = <C::AffineExt as CurveAffine>::from_xy(lhs_x, lhs_y)
-                    .unwrap()
-                    .to_curve();
-                let rhs = <C::AffineExt as CurveAffine>::from_xy(rhs_x, rhs_y)
-                    .unwrap()
-                    .to_curve();
-                Accumulator::new(MSM::base(lhs), MSM::base(rhs))
-            })
-            .collect::<Vec<_>>();
+            .flatten()
+            .map(|(scalar, _)| *scalar)
+            .chain(self.carried.iter().flatten().cloned())
+            .collect()
+    }
 
-        Some(Accumulator::random_linear_combine(
-            challenges.into_iter().zip(accumulators),
-        ))
+    pub fn decide<M: MultiMillerLoop<G1 = C>>(&self, g2: M::G2Affine, s_g2: M::G2Affine) -> bool {
+        decide_evaluated::<M>(self.lhs, self.rhs, g2, s_g2)
+    }
+}
+
+impl<C, T, P> AccumulationStrategy<C, NativeLoader, T, P>
+    for CycleCurveAccumulation<C, NativeLoader>
+where
+    C: CurveExt,
+    T: Transcript<C, NativeLoader>,
+{
+    type Output = P;
+
+    fn extract_accumulator(
+        &mut self,
+        protocol: &Protocol<C>,
+        _: &NativeLoader,
+        _: &mut T,
+        statements: &[Vec<C::ScalarExt>],
+    ) -> Result<Option<Accumulator<C, NativeLoader>>, Error> {
+        let carried = Self::cycle_accumulators(protocol, statements)?;
+        self.carried_accumulators.extend(carried);
+        Ok(None)
     }
 
     fn process(
         &mut self,
+        _: &Protocol<C>,
         _: &NativeLoader,
         transcript: &mut T,
         proof: P,
         accumulator: Accumulator<C, NativeLoader>,
     ) -> Result<Self::Output, Error> {
-        self.accumulator = Some(match self.accumulator.take() {
-            Some(curr_accumulator) => {
-                accumulator + curr_accumulator * &transcript.squeeze_challenge()
-            }
-            None => accumulator,
-        });
+        self.combine(transcript, accumulator);
         Ok(proof)
     }
 }
+
+impl<C, const LIMBS: usize, const BITS: usize> AccumulatorEncoding<C, NativeLoader>
+    for LimbsEncoding<LIMBS, BITS>
+where
+    C: CurveExt,
+{
+    fn decode(
+        &self,
+        _: &NativeLoader,
+        _: usize,
+        statements: &[C::ScalarExt],
+    ) -> Result<(C, C), Error> {
+        if statements.len() != 4 * LIMBS {
+            return Err(Error::InvalidInstances);
+        }
+
+        let [lhs_x, lhs_y, rhs_x, rhs_y]: [C::Base; 4] = statements
+            .chunks(LIMBS)
+            .map(fe_from_limbs_checked::<_, _, LIMBS, BITS>)
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+            .unwrap();
+        Ok((
+            ec_point_from_xy(lhs_x, lhs_y)?,
+            ec_point_from_xy(rhs_x, rhs_y)?,
+        ))
+    }
+}
+
+impl<C, const LIMBS: usize, const BITS: usize> AccumulatorEncoding<C, NativeLoader>
+    for CompressedEncoding<LIMBS, BITS>
+where
+    C: CurveExt,
+{
+    fn decode(
+        &self,
+        _: &NativeLoader,
+        _: usize,
+        statements: &[C::ScalarExt],
+    ) -> Result<(C, C), Error> {
+        if statements.len() != 2 * (LIMBS + 1) {
+            return Err(Error::InvalidInstances);
+        }
+
+        let [lhs, rhs]: [C; 2] = statements
+            .chunks(LIMBS + 1)
+            .map(|statements| {
+                let x = fe_from_limbs_checked::<_, C::Base, LIMBS, BITS>(&statements[..LIMBS])?;
+                let sign = statements[LIMBS];
+                if sign != C::ScalarExt::zero() && sign != C::ScalarExt::one() {
+                    return Err(Error::AccumulatorLimbOutOfRange);
+                }
+
+                let a = <C::AffineExt as CurveAffine>::a();
+                let b = <C::AffineExt as CurveAffine>::b();
+                let y = Option::<C::Base>::from((x.square() * x + a * x + b).sqrt())
+                    .ok_or(Error::AccumulatorNotOnCurve)?;
+                let y = if bool::from(y.is_odd()) == (sign == C::ScalarExt::one()) {
+                    y
+                } else {
+                    -y
+                };
+                ec_point_from_xy(x, y)
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+            .unwrap();
+        Ok((lhs, rhs))
+    }
+}
+
+/// Witness of [`HashCommittedEncoding`] for `NativeLoader` is the accumulator
+/// `(lhs, rhs)` itself.
+impl<C, H> AccumulatorEncoding<C, NativeLoader> for HashCommittedEncoding<(C, C), H>
+where
+    C: CurveExt,
+    H: Fn(&C, &C) -> Result<C::ScalarExt, Error>,
+{
+    fn decode(
+        &self,
+        _: &NativeLoader,
+        idx: usize,
+        statements: &[C::ScalarExt],
+    ) -> Result<(C, C), Error> {
+        let [statement]: [C::ScalarExt; 1] =
+            statements.try_into().map_err(|_| Error::InvalidInstances)?;
+        let (lhs, rhs) = *self.witness(idx)?;
+        if bool::from(lhs.is_identity() | rhs.is_identity()) {
+            return Err(Error::AccumulatorIsIdentity);
+        }
+        if (self.hash())(&lhs, &rhs)? != statement {
+            return Err(Error::InvalidInstances);
+        }
+        Ok((lhs, rhs))
+    }
+}
+
+/// Returns point `(x, y)` if it's on curve and not identity, where identity
+/// is rejected explicitly since `from_xy` accepts `(0, 0)` as identity.
+fn ec_point_from_xy<C: CurveExt>(x: C::Base, y: C::Base) -> Result<C, Error> {
+    if bool::from(x.is_zero() & y.is_zero()) {
+        return Err(Error::AccumulatorIsIdentity);
+    }
+    Option::<C::AffineExt>::from(<C::AffineExt as CurveAffine>::from_xy(x, y))
+        .map(|ec_point| ec_point.to_curve())
+        .ok_or(Error::AccumulatorNotOnCurve)
+}
@@ -0,0 +1,171 @@
+use crate::{
+    loader::native::NativeLoader,
+    protocol::Snark,
+    scheme::kzg::{AccumulationScheme, SameCurveAccumulation},
+    util::{Curve, Field, PrimeCurveAffine, TranscriptRead},
+    Error,
+};
+use halo2_curves::{
+    pairing::{MillerLoopResult, MultiMillerLoop},
+    CurveExt,
+};
+use rand::RngCore;
+use std::iter;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Bound of the transcript constructor taken by [`batch_verify`], which is
+/// `Sync` to be shared across threads with feature `parallel` enabled, and
+/// implemented by any type otherwise.
+#[cfg(feature = "parallel")]
+pub trait MaybeSync: Sync {}
+
+#[cfg(feature = "parallel")]
+impl<T: Sync> MaybeSync for T {}
+
+/// Implemented by any type without feature `parallel`.
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSync {}
+
+#[cfg(not(feature = "parallel"))]
+impl<T> MaybeSync for T {}
+
+/// Verify many independent snarks with a single pairing check.
+///
+/// Each snark is accumulated with its own transcript, then the resulting
+/// `(lhs, rhs)` pairs are random linear combined with factors sampled from
+/// `rng` and checked with one `multi_miller_loop`. When the combined check
+/// fails, the set is bisected to find out which snarks are invalid, and they
+/// are returned by index in [`Error::InvalidProofs`].
+///
+/// With feature `parallel` enabled, the snarks are accumulated in parallel.
+pub fn batch_verify<'a, M, C, AS, T, F, R, const LIMBS: usize, const BITS: usize>(
+    g1: M::G1Affine,
+    g2: M::G2Affine,
+    s_g2: M::G2Affine,
+    snarks: &'a [Snark<C>],
+    transcript: F,
+    mut rng: R,
+) -> Result<(), Error>
+where
+    M: MultiMillerLoop<G1 = C>,
+    C: CurveExt,
+    AS: AccumulationScheme<C, NativeLoader, T, SameCurveAccumulation<C, NativeLoader, LIMBS, BITS>>,
+    T: TranscriptRead<C, NativeLoader>,
+    F: Fn(&'a [u8]) -> T + MaybeSync,
+    R: RngCore,
+{
+    let g1 = g1.to_curve();
+    let accumulate = |snark: &'a Snark<C>| {
+        let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
+        AS::accumulate(
+            &snark.protocol,
+            &NativeLoader,
+            snark.statements.clone(),
+            &mut transcript(snark.proof.as_slice()),
+            &mut strategy,
+        )
+        .and_then(|_| strategy.finalize(g1))
+    };
+
+    #[cfg(feature = "parallel")]
+    let accumulators = snarks.par_iter().map(accumulate).collect::<Vec<_>>();
+    #[cfg(not(feature = "parallel"))]
+    let accumulators = snarks.iter().map(accumulate).collect::<Vec<_>>();
+
+    let (indices, accumulators, mut invalid) = accumulators.into_iter().enumerate().fold(
+        (Vec::new(), Vec::new(), Vec::new()),
+        |(mut indices, mut accumulators, mut invalid), (idx, accumulator)| {
+            match accumulator {
+                Ok(accumulator) => {
+                    indices.push(idx);
+                    accumulators.push(accumulator);
+                }
+                Err(_) => invalid.push(idx),
+            }
+            (indices, accumulators, invalid)
+        },
+    );
+
+    let g2 = M::G2Prepared::from(g2);
+    let minus_s_g2 = M::G2Prepared::from(-s_g2);
+    invalid.extend(bisect::<M, _, _>(
+        &g2,
+        &minus_s_g2,
+        &indices,
+        &accumulators,
+        &mut rng,
+    ));
+
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        invalid.sort_unstable();
+        Err(Error::InvalidProofs(invalid))
+    }
+}
+
+fn bisect<M, C, R>(
+    g2: &M::G2Prepared,
+    minus_s_g2: &M::G2Prepared,
+    indices: &[usize],
+    accumulators: &[(C, C)],
+    rng: &mut R,
+) -> Vec<usize>
+where
+    M: MultiMillerLoop<G1 = C>,
+    C: Curve,
+    R: RngCore,
+{
+    if accumulators.is_empty() || decide::<M, _, _>(g2, minus_s_g2, accumulators, rng) {
+        return Vec::new();
+    }
+    if accumulators.len() == 1 {
+        return indices.to_vec();
+    }
+
+    let mid = accumulators.len() / 2;
+    iter::empty()
+        .chain(bisect::<M, _, _>(
+            g2,
+            minus_s_g2,
+            &indices[..mid],
+            &accumulators[..mid],
+            rng,
+        ))
+        .chain(bisect::<M, _, _>(
+            g2,
+            minus_s_g2,
+            &indices[mid..],
+            &accumulators[mid..],
+            rng,
+        ))
+        .collect()
+}
+
+fn decide<M, C, R>(
+    g2: &M::G2Prepared,
+    minus_s_g2: &M::G2Prepared,
+    accumulators: &[(C, C)],
+    rng: &mut R,
+) -> bool
+where
+    M: MultiMillerLoop<G1 = C>,
+    C: Curve,
+    R: RngCore,
+{
+    let (lhs, rhs) = accumulators.iter().fold(
+        (C::identity(), C::identity()),
+        |(lhs_acc, rhs_acc), (lhs, rhs)| {
+            let factor = C::Scalar::random(&mut *rng);
+            (lhs_acc + *lhs * factor, rhs_acc + *rhs * factor)
+        },
+    );
+
+    let terms = [(&lhs.into(), g2), (&rhs.into(), minus_s_g2)];
+    M::multi_miller_loop(&terms)
+        .final_exponentiation()
+        .is_identity()
+        .into()
+}
@@ -1,27 +1,68 @@
 use crate::{
     loader::{EcPointLoader, LoadedEcPoint, LoadedScalar, Loader, ScalarLoader},
-    util::{Curve, FieldOps, PrimeField},
+    util::{multi_scalar_multiplication, Curve, FieldOps, GroupEncoding, PrimeField},
 };
 use lazy_static::lazy_static;
-use std::fmt::Debug;
+use std::{
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+};
 
 lazy_static! {
     static ref LOADER: NativeLoader = NativeLoader;
 }
 
-impl<C: Curve> LoadedEcPoint<C> for C {
+/// Encoding of a point used as its key, which is compared and hashed by its
+/// bytes without allocating.
+pub struct EcPointKey<C: GroupEncoding>(C::Repr);
+
+impl<C: GroupEncoding> Clone for EcPointKey<C> {
+    fn clone(&self) -> Self {
+        Self(self.0)
+    }
+}
+
+impl<C: GroupEncoding> Debug for EcPointKey<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EcPointKey").field(&self.0.as_ref()).finish()
+    }
+}
+
+impl<C: GroupEncoding> PartialEq for EcPointKey<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_ref() == other.0.as_ref()
+    }
+}
+
+impl<C: GroupEncoding> Eq for EcPointKey<C> {}
+
+impl<C: GroupEncoding> Hash for EcPointKey<C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ref().hash(state);
+    }
+}
+
+impl<C: Curve + GroupEncoding> LoadedEcPoint<C> for C {
     type Loader = NativeLoader;
+    type Key = EcPointKey<C>;
 
     fn loader(&self) -> &NativeLoader {
         &LOADER
     }
 
+    /// Returns the encoding of the point, which is canonical for its affine
+    /// coordinates regardless of its projective representation.
+    fn key(&self) -> Option<EcPointKey<C>> {
+        Some(EcPointKey(self.to_bytes()))
+    }
+
+    fn as_const(&self) -> Option<C> {
+        Some(*self)
+    }
+
     fn multi_scalar_multiplication(pairs: impl IntoIterator<Item = (C::Scalar, C)>) -> Self {
-        pairs
-            .into_iter()
-            .map(|(scalar, base)| base * scalar)
-            .reduce(|acc, value| acc + value)
-            .unwrap()
+        let (scalars, bases) = pairs.into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
+        multi_scalar_multiplication(&scalars, &bases)
     }
 }
 
@@ -37,12 +78,16 @@ impl<F: PrimeField> LoadedScalar<F> for F {
     fn loader(&self) -> &NativeLoader {
         &LOADER
     }
+
+    fn as_const(&self) -> Option<F> {
+        Some(*self)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct NativeLoader;
 
-impl<C: Curve> EcPointLoader<C> for NativeLoader {
+impl<C: Curve + GroupEncoding> EcPointLoader<C> for NativeLoader {
     type LoadedEcPoint = C;
 
     fn ec_point_load_const(&self, value: &C) -> Self::LoadedEcPoint {
@@ -58,4 +103,4 @@ impl<F: PrimeField> ScalarLoader<F> for NativeLoader {
     }
 }
 
-impl<C: Curve> Loader<C> for NativeLoader {}
+impl<C: Curve + GroupEncoding> Loader<C> for NativeLoader {}
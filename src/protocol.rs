@@ -1,4 +1,7 @@
-use crate::util::{Curve, Domain, Expression, Group, Query};
+use crate::util::{
+    CommonPolynomial, Curve, Domain, Expression, Field, Group, GroupEncoding, PrimeField, Query,
+};
+use blake2b_simd::State;
 
 #[cfg(feature = "halo2")]
 pub mod halo2;
@@ -24,6 +27,119 @@ impl<C: Curve> Protocol<C> {
     }
 }
 
+impl<C: Curve + GroupEncoding> Protocol<C> {
+    /// Digest identifying the protocol, which is Blake2b hash of all its
+    /// fields reduced into a scalar.
+    pub fn digest(&self) -> C::Scalar {
+        let mut state = blake2b_simd::Params::new()
+            .hash_length(64)
+            .personal(b"Protocol-Digest")
+            .to_state();
+
+        state.update(&[self.zk as u8]);
+        update_usize(&mut state, self.domain.k);
+        update_usize(&mut state, self.preprocessed.len());
+        for ec_point in self.preprocessed.iter() {
+            state.update(ec_point.to_bytes().as_ref());
+        }
+        update_usize(&mut state, self.num_statement);
+        for nums in [&self.num_auxiliary, &self.num_challenge] {
+            update_usize(&mut state, nums.len());
+            for num in nums.iter() {
+                update_usize(&mut state, *num);
+            }
+        }
+        for queries in [&self.evaluations, &self.queries] {
+            update_usize(&mut state, queries.len());
+            for query in queries.iter() {
+                update_query(&mut state, query);
+            }
+        }
+        update_usize(&mut state, self.relations.len());
+        for relation in self.relations.iter() {
+            update_expression(&mut state, relation);
+        }
+        state.update(self.transcript_initial_state.to_repr().as_ref());
+        match &self.accumulator_indices {
+            Some(accumulator_indices) => {
+                state.update(&[1]);
+                update_usize(&mut state, accumulator_indices.len());
+                for indices in accumulator_indices.iter() {
+                    update_usize(&mut state, indices.len());
+                    for (poly, row) in indices.iter() {
+                        update_usize(&mut state, *poly);
+                        update_usize(&mut state, *row);
+                    }
+                }
+            }
+            None => {
+                state.update(&[0]);
+            }
+        }
+
+        state
+            .finalize()
+            .as_bytes()
+            .iter()
+            .fold(C::Scalar::zero(), |acc, byte| {
+                acc * C::Scalar::from(256) + C::Scalar::from(*byte as u64)
+            })
+    }
+}
+
+fn update_usize(state: &mut State, value: usize) {
+    state.update(&(value as u64).to_le_bytes());
+}
+
+fn update_query(state: &mut State, query: &Query) {
+    update_usize(state, query.poly);
+    state.update(&query.rotation.0.to_le_bytes());
+}
+
+/// Update `state` with `expression` in prefix order, where each node is
+/// tagged by its variant.
+fn update_expression<F: PrimeField>(state: &mut State, expression: &Expression<F>) {
+    match expression {
+        Expression::Constant(constant) => {
+            state.update(&[0]).update(constant.to_repr().as_ref());
+        }
+        Expression::CommonPolynomial(CommonPolynomial::Identity) => {
+            state.update(&[1]);
+        }
+        Expression::CommonPolynomial(CommonPolynomial::Lagrange(i)) => {
+            state.update(&[2]).update(&i.to_le_bytes());
+        }
+        Expression::Polynomial(query) => {
+            state.update(&[3]);
+            update_query(state, query);
+        }
+        Expression::Challenge(index) => {
+            state.update(&[4]);
+            update_usize(state, *index);
+        }
+        Expression::Negated(a) => {
+            state.update(&[5]);
+            update_expression(state, a);
+        }
+        Expression::Sum(a, b) => {
+            state.update(&[6]);
+            update_expression(state, a);
+            update_expression(state, b);
+        }
+        Expression::Product(a, b) => {
+            state.update(&[7]);
+            update_expression(state, a);
+            update_expression(state, b);
+        }
+        Expression::Scaled(a, scalar) => {
+            state.update(&[8]);
+            update_expression(state, a);
+            state.update(scalar.to_repr().as_ref());
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Snark<C: Curve> {
     pub protocol: Protocol<C>,
     pub statements: Vec<Vec<<C as Group>::Scalar>>,
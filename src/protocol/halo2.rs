@@ -10,7 +10,7 @@ use halo2_proofs::{
 };
 use std::{io, iter};
 
-mod util;
+pub mod util;
 
 #[cfg(test)]
 mod test;
@@ -22,6 +22,22 @@ pub struct Config {
     accumulator_indices: Option<Vec<(usize, usize)>>,
 }
 
+impl Config {
+    pub fn new(zk: bool, query_instance: bool, num_proof: usize) -> Self {
+        Self {
+            zk,
+            query_instance,
+            num_proof,
+            accumulator_indices: None,
+        }
+    }
+
+    pub fn with_accumulator_indices(mut self, accumulator_indices: Vec<(usize, usize)>) -> Self {
+        self.accumulator_indices = Some(accumulator_indices);
+        self
+    }
+}
+
 pub fn compile<C: CurveExt>(vk: &VerifyingKey<C::AffineExt>, config: Config) -> Protocol<C> {
     let cs = vk.cs();
     let Config {
@@ -31,7 +47,7 @@ pub fn compile<C: CurveExt>(vk: &VerifyingKey<C::AffineExt>, config: Config) ->
         accumulator_indices,
     } = config;
 
-    let k = vk.get_domain().empty_lagrange().len().log2();
+    let k = vk.get_domain().empty_lagrange().len().ilog2();
     let domain = Domain::new(k as usize);
 
     let preprocessed = vk
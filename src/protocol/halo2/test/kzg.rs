@@ -1,8 +1,18 @@
 use crate::{
-    protocol::halo2::test::MainGateWithRange,
-    util::{fe_to_limbs, Field},
+    loader::{Loader, ScalarLoader},
+    protocol::{
+        halo2::test::{MainGateWithRange, StandardPlonk},
+        Protocol,
+    },
+    scheme::kzg::{Accumulator, SameCurveAccumulation, MSM},
+    util::{fe_to_limbs, Curve, Field, PrimeField, TranscriptRead},
+    Error,
+};
+use halo2_curves::{
+    bn256::{Bn256, Fr, G1Affine, G1},
+    pairing::Engine,
+    CurveAffine,
 };
-use halo2_curves::{pairing::Engine, CurveAffine};
 use halo2_proofs::poly::{
     commitment::{CommitmentScheme, Params, ParamsProver},
     kzg::commitment::{KZGCommitmentScheme, ParamsKZG},
@@ -53,6 +63,50 @@ pub fn main_gate_with_range_with_mock_kzg_accumulator<E: Engine + Debug>(
     )
 }
 
+/// Distinct mock accumulators `(i * s_g1, i * g1)` for `i` in `1..=n`, which
+/// are all decided to be valid with srs from [`read_or_create_srs`].
+pub fn mock_kzg_accumulators(n: usize) -> Vec<(G1Affine, G1Affine)> {
+    let g = read_or_create_srs::<Bn256>(3).get_g();
+    let (g1, s_g1) = (g[0], g[1]);
+    (1..=n as u64)
+        .map(|i| {
+            (
+                (s_g1 * Fr::from(i)).to_affine(),
+                (g1 * Fr::from(i)).to_affine(),
+            )
+        })
+        .collect()
+}
+
+/// Statements of `accumulator` in
+/// [`LimbsEncoding`](crate::scheme::kzg::LimbsEncoding).
+pub fn limbs_encoded(accumulator: &(G1Affine, G1Affine)) -> Vec<Fr> {
+    [accumulator.0, accumulator.1]
+        .iter()
+        .flat_map(|ec_point| [ec_point.x, ec_point.y])
+        .flat_map(fe_to_limbs::<_, _, LIMBS, BITS>)
+        .collect()
+}
+
+/// Statements of `accumulator` in
+/// [`CompressedEncoding`](crate::scheme::kzg::CompressedEncoding).
+pub fn compressed_encoded(accumulator: &(G1Affine, G1Affine)) -> Vec<Fr> {
+    [accumulator.0, accumulator.1]
+        .iter()
+        .flat_map(|ec_point| {
+            fe_to_limbs::<_, _, LIMBS, BITS>(ec_point.x)
+                .into_iter()
+                .chain([Fr::from(bool::from(ec_point.y.is_odd()) as u64)])
+        })
+        .collect()
+}
+
+/// Circuit with `statements` as instances right after the one it constrains,
+/// which are at `(0, 1..=statements.len())`.
+pub fn main_gate_with_range_with_statements(statements: Vec<Fr>) -> MainGateWithRange<Fr> {
+    MainGateWithRange::new(iter::once(Fr::zero()).chain(statements).collect())
+}
+
 #[macro_export]
 macro_rules! halo2_kzg_config {
     ($zk:expr, $num_proof:expr) => {
@@ -217,6 +271,48 @@ macro_rules! halo2_kzg_native_verify {
         let mut stretagy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
         halo2_kzg_native_accumulate!($protocol, $statements, $scheme, $transcript, &mut stretagy);
 
-        assert!(stretagy.decide::<Bn256>($params.get_g()[0], $params.g2(), $params.s_g2()));
+        assert!(stretagy
+            .decide::<Bn256>($params.get_g()[0], $params.g2(), $params.s_g2())
+            .unwrap());
     }};
 }
+
+/// Distinct protocols of [`StandardPlonk`] with 1 and 2 proofs.
+pub fn standard_plonk_protocols() -> [Protocol<G1>; 2] {
+    [1, 2].map(|num_proof| {
+        let (_, _, protocol, _) = halo2_kzg_prepare!(
+            9,
+            halo2_kzg_config!(false, num_proof),
+            StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+        );
+        protocol
+    })
+}
+
+/// Binding after combining an accumulator of a point read from `transcript`
+/// for each of `protocols` with protocol binding, where transcript initial
+/// state and a challenge are absorbed and squeezed before each combining like
+/// in accumulation schemes.
+pub fn combine_with_protocol_binding<L, T>(
+    protocols: &[Protocol<G1>],
+    loader: &L,
+    transcript: &mut T,
+) -> Result<L::LoadedScalar, Error>
+where
+    L: Loader<G1>,
+    T: TranscriptRead<G1, L>,
+{
+    let mut strategy =
+        SameCurveAccumulation::<_, _, LIMBS, BITS>::default().with_protocol_binding();
+    for protocol in protocols {
+        transcript.common_scalar(&loader.load_const(&protocol.transcript_initial_state))?;
+        let ec_point = transcript.read_ec_point()?;
+        let challenge = transcript.squeeze_challenge();
+        let accumulator = Accumulator::new(
+            MSM::base(ec_point.clone()) * &challenge,
+            MSM::base(ec_point),
+        );
+        strategy.combine(protocol, loader, transcript, accumulator)?;
+    }
+    Ok(strategy.binding().cloned().unwrap())
+}
@@ -1,22 +1,61 @@
 use crate::{
     halo2_kzg_config, halo2_kzg_create_snark, halo2_kzg_evm_verify, halo2_kzg_native_verify,
     halo2_kzg_prepare,
-    loader::evm::EvmTranscript,
-    protocol::halo2::{
-        test::{
-            kzg::{halo2::Accumulation, main_gate_with_range_with_mock_kzg_accumulator, LIMBS},
-            StandardPlonk,
+    loader::evm::{
+        compile_solidity, deploy_and_call, deploy_with_data_contract_and_call, deployment_code,
+        encode_abi_calldata, encode_calldata, encode_verify_calldata, execute, modulus,
+        verify_selector, CalldataLayout, EcPoint, EvmCurve, EvmLoader, EvmTranscript, Expr, Op,
+        Precompiled, Scalar, CODE_SIZE_LIMIT,
+    },
+    loader::{
+        halo2::AggregationTree, native::NativeLoader, EcPointLoader, LoadedEcPoint, LoadedScalar,
+        ScalarLoader,
+    },
+    protocol::{
+        halo2::{
+            test::{
+                kzg::{
+                    combine_with_protocol_binding, compressed_encoded,
+                    halo2::{create_two_snark, Accumulation},
+                    limbs_encoded, main_gate_with_range_with_mock_kzg_accumulator,
+                    main_gate_with_range_with_statements, mock_kzg_accumulators,
+                    read_or_create_srs, standard_plonk_protocols, BITS, LIMBS,
+                },
+                StandardPlonk,
+            },
+            util::evm::ChallengeEvm,
         },
-        util::evm::ChallengeEvm,
+        Protocol, Snark,
+    },
+    scheme::kzg::{
+        AccumulationScheme, Accumulator, CompressedEncoding, HashCommittedEncoding, LimbsEncoding,
+        PlonkAccumulationScheme, SameCurveAccumulation, ShplonkAccumulationScheme, MSM,
     },
-    scheme::kzg::PlonkAccumulationScheme,
+    util::{
+        fe_to_limbs, Curve, Field, FieldOps, Group, GroupOps, PrimeCurveAffine, Transcript,
+        TranscriptRead, UncompressedEncoding,
+    },
+    Error,
+};
+use ethereum_types::{U256, U512};
+use halo2_curves::{
+    bn256::{Bn256, Fq, Fr, G1Affine, G2Affine, G1},
+    CurveAffine,
 };
-use halo2_proofs::poly::kzg::{
-    multiopen::{ProverGWC, VerifierGWC},
-    strategy::AccumulatorStrategy,
+use halo2_proofs::poly::{
+    commitment::ParamsProver,
+    kzg::{
+        commitment::ParamsKZG,
+        multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC},
+        strategy::AccumulatorStrategy,
+    },
 };
 use paste::paste;
 use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+use std::{iter, rc::Rc};
+
+#[cfg(feature = "simulate")]
+use crate::loader::evm::simulate;
 
 #[macro_export]
 macro_rules! halo2_kzg_evm_verify {
@@ -25,36 +64,94 @@ macro_rules! halo2_kzg_evm_verify {
         use halo2_proofs::poly::commitment::ParamsProver;
         use std::{iter, rc::Rc};
         use $crate::{
-            loader::evm::{encode_calldata, execute, EvmLoader, EvmTranscript},
+            loader::evm::{
+                compile_solidity, encode_calldata, encode_verify_calldata, execute, EvmLoader,
+                EvmTranscript,
+            },
             protocol::halo2::test::kzg::{BITS, LIMBS},
             scheme::kzg::{AccumulationScheme, SameCurveAccumulation},
             util::TranscriptRead,
         };
 
-        let loader = EvmLoader::new::<Fq, Fr>();
-        let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(loader.clone());
-        let statements = $statements
-            .iter()
-            .map(|instance| {
-                iter::repeat_with(|| transcript.read_scalar().unwrap())
-                    .take(instance.len())
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
-        let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
-        <$scheme>::accumulate(
-            $protocol,
-            &loader,
-            statements,
-            &mut transcript,
-            &mut strategy,
-        )
-        .unwrap();
-        let code = strategy.code($params.get_g()[0], $params.g2(), $params.s_g2());
-        let (accept, total_cost, costs) = execute(code, encode_calldata($statements, $proof));
-        loader.print_gas_metering(costs);
+        // Gas profiling costs extra gas for metering, so it's enabled only for
+        // a separate loader to keep the code executed below uninstrumented
+        let verifier = |gas_profiling: bool| {
+            let loader = EvmLoader::new::<Fq, Fr>();
+            if gas_profiling {
+                loader.enable_gas_profiling();
+            }
+            let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(loader.clone());
+            let statements = $statements
+                .iter()
+                .map(|instance| {
+                    iter::repeat_with(|| transcript.read_scalar().unwrap())
+                        .take(instance.len())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
+            <$scheme>::accumulate(
+                $protocol,
+                &loader,
+                statements,
+                &mut transcript,
+                &mut strategy,
+            )
+            .unwrap();
+            let code = strategy
+                .code($params.get_g()[0], $params.g2(), $params.s_g2())
+                .unwrap();
+            (loader, code)
+        };
+
+        let (loader, code) = verifier(false);
+        let calldata = encode_calldata($statements.clone(), $proof);
+        let (accept, total_cost, _) = execute(code, calldata.clone());
         println!("Total: {}", total_cost);
         assert!(accept);
+
+        #[cfg(feature = "simulate")]
+        {
+            let (profiled_loader, _) = verifier(true);
+            let profile = profiled_loader.gas_profile(calldata.clone());
+            println!("{}", profile.to_json());
+            print!("{}", profile.to_folded());
+            assert!(profile.accepted);
+            assert!(["read_proof", "quotient_eval", "msm", "pairing"]
+                .iter()
+                .all(|name| profile
+                    .sections
+                    .iter()
+                    .any(|section| section.path == [*name])));
+            // Proof is read in a single section, where only squeezing
+            // challenges is metered separately
+            assert!(profile
+                .sections
+                .iter()
+                .filter(|section| section.path.len() > 1 && section.path[0] == "read_proof")
+                .all(|section| section.path[1..] == ["squeeze_challenge"]));
+        }
+
+        let memory_report = loader.memory_report();
+        let (accept, total_cost_without_memory_reuse, _) =
+            execute(loader.code_without_memory_reuse(), calldata.clone());
+        println!(
+            "Peak memory: {} (without reuse: {})",
+            memory_report.after, memory_report.before
+        );
+        println!(
+            "Total without memory reuse: {}",
+            total_cost_without_memory_reuse
+        );
+        assert!(accept);
+        assert!(memory_report.after < memory_report.before);
+
+        let (accept, total_cost, _) = execute(
+            compile_solidity(&loader.solidity()),
+            encode_verify_calldata(&calldata),
+        );
+        println!("Total (Solidity): {}", total_cost);
+        assert!(accept);
     }};
 }
 
@@ -157,3 +254,846 @@ test!(
     halo2_kzg_config!(false, 1, (0..4 * LIMBS).map(|idx| (0, idx)).collect()),
     Accumulation::two_snark_with_accumulator(false)
 );
+
+#[test]
+fn test_ec_point_from_limbs_validation() {
+    let (code, solidity_code) = {
+        let loader = EvmLoader::new::<Fq, Fr>();
+        let limbs = (0..2 * LIMBS)
+            .map(|idx| loader.calldataload_scalar(idx * 0x20))
+            .collect::<Vec<_>>();
+        loader.ec_point_from_limbs::<LIMBS, BITS>(&limbs[..LIMBS], &limbs[LIMBS..]);
+        (loader.code(), compile_solidity(&loader.solidity()))
+    };
+    let accept = |x: [Fr; LIMBS], y: [Fr; LIMBS]| {
+        let calldata = encode_calldata(vec![[x, y].concat()], Vec::new());
+        let (accept, _, _) = execute(code.clone(), calldata.clone());
+        let (solidity_accept, _, _) =
+            execute(solidity_code.clone(), encode_verify_calldata(&calldata));
+        assert_eq!(accept, solidity_accept);
+        accept
+    };
+
+    let ec_point = G1::random(ChaCha20Rng::from_seed(Default::default())).to_affine();
+    let x = fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.x);
+    let y = fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.y);
+    assert!(accept(x, y));
+
+    let mut overflowed = x;
+    overflowed[0] += Fr::from(2).pow_vartime(&[BITS as u64]);
+    overflowed[1] -= Fr::one();
+    assert!(!accept(overflowed, y));
+
+    let mut modulus = fe_to_limbs::<_, Fr, LIMBS, BITS>(-Fq::one());
+    modulus[0] += Fr::one();
+    assert!(!accept(modulus, y));
+
+    assert!(!accept(
+        x,
+        fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.y + Fq::one())
+    ));
+
+    assert!(!accept([Fr::zero(); LIMBS], [Fr::zero(); LIMBS]));
+}
+
+#[test]
+fn test_kzg_plonk_abi_calldata_layout() {
+    let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(true, 1),
+        StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let snark = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverGWC<_>,
+        VerifierGWC<_>,
+        AccumulatorStrategy<_>,
+        EvmTranscript<_, _, _, _>,
+        EvmTranscript<_, _, _, _>,
+        ChallengeEvm<_>
+    );
+
+    let (runtime_code, solidity) = {
+        let loader = EvmLoader::new_with_calldata_layout::<Fq, Fr>(CalldataLayout::Abi {
+            selector: Some(verify_selector()),
+            num_instances: snark.statements.iter().map(Vec::len).sum(),
+        });
+        let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(loader.clone());
+        let statements = snark
+            .statements
+            .iter()
+            .map(|instance| {
+                iter::repeat_with(|| transcript.read_scalar().unwrap())
+                    .take(instance.len())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
+        PlonkAccumulationScheme::accumulate(
+            &snark.protocol,
+            &loader,
+            statements,
+            &mut transcript,
+            &mut strategy,
+        )
+        .unwrap();
+        let runtime_code = strategy
+            .code(params.get_g()[0], params.g2(), params.s_g2())
+            .unwrap();
+        (runtime_code, loader.solidity())
+    };
+    let code = deployment_code(runtime_code.clone());
+    let solidity_code = deployment_code(compile_solidity(&solidity));
+
+    let returned = |accept: bool| {
+        let mut returned = vec![0; 32];
+        returned[31] = accept as u8;
+        returned
+    };
+    let assert_returned = |calldata: Vec<u8>, accept: bool| {
+        assert_eq!(
+            deploy_and_call(code.clone(), calldata.clone()),
+            (true, returned(accept))
+        );
+        assert_eq!(
+            deploy_and_call(solidity_code.clone(), calldata.clone()),
+            (true, returned(accept))
+        );
+
+        #[cfg(feature = "simulate")]
+        {
+            let report = simulate(runtime_code.clone(), calldata);
+            assert!(report.success);
+            assert_eq!(report.return_data, returned(accept));
+            assert_eq!(report.revert_reason, None);
+        }
+    };
+
+    assert_returned(
+        encode_abi_calldata(
+            Some(verify_selector()),
+            snark.statements.clone(),
+            snark.proof.clone(),
+        ),
+        true,
+    );
+
+    let mut proof = snark.proof.clone();
+    proof[0x20] ^= 1;
+    assert_returned(
+        encode_abi_calldata(Some(verify_selector()), snark.statements.clone(), proof),
+        false,
+    );
+
+    // Both return `false` on another selector, where Solidity falls back to
+    // return it instead of reverting in its dispatcher
+    assert_returned(
+        encode_abi_calldata(Some([0; 4]), snark.statements, snark.proof),
+        false,
+    );
+}
+
+#[test]
+fn test_kzg_plonk_data_contract() {
+    let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(true, 3),
+        StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let snark = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverGWC<_>,
+        VerifierGWC<_>,
+        AccumulatorStrategy<_>,
+        EvmTranscript<_, _, _, _>,
+        EvmTranscript<_, _, _, _>,
+        ChallengeEvm<_>
+    );
+
+    let loader = EvmLoader::new::<Fq, Fr>();
+    let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(loader.clone());
+    let statements = snark
+        .statements
+        .iter()
+        .map(|instance| {
+            iter::repeat_with(|| transcript.read_scalar().unwrap())
+                .take(instance.len())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
+    PlonkAccumulationScheme::accumulate(
+        &snark.protocol,
+        &loader,
+        statements,
+        &mut transcript,
+        &mut strategy,
+    )
+    .unwrap();
+    let code = strategy
+        .code(params.get_g()[0], params.g2(), params.s_g2())
+        .unwrap();
+    let data_contract_code = loader.data_contract_code().unwrap();
+    println!(
+        "Code size: {} (with data contract: {} + {})",
+        loader.code_size(),
+        loader
+            .code_with_data_contract(Default::default())
+            .unwrap()
+            .len(),
+        data_contract_code.len()
+    );
+    assert!(loader.code_size() > CODE_SIZE_LIMIT);
+    assert!(
+        loader
+            .code_with_data_contract(Default::default())
+            .unwrap()
+            .len()
+            <= CODE_SIZE_LIMIT
+    );
+    assert!(data_contract_code.len() <= CODE_SIZE_LIMIT);
+
+    let calldata = encode_calldata(snark.statements.clone(), snark.proof.clone());
+    let (accept, total_cost, _) = execute(code, calldata.clone());
+    assert!(accept);
+    let (accept, total_cost_with_data_contract) = deploy_with_data_contract_and_call(
+        data_contract_code.clone(),
+        |address| loader.code_with_data_contract(address).unwrap(),
+        calldata,
+    );
+    println!(
+        "Total: {} (with data contract: {})",
+        total_cost, total_cost_with_data_contract
+    );
+    assert!(accept);
+
+    let mut proof = snark.proof;
+    proof[0] ^= 1;
+    let (accept, _) = deploy_with_data_contract_and_call(
+        data_contract_code,
+        |address| loader.code_with_data_contract(address).unwrap(),
+        encode_calldata(snark.statements, proof),
+    );
+    assert!(!accept);
+}
+
+#[test]
+fn test_deferred_inversion() {
+    let code = {
+        let loader = EvmLoader::new::<Fq, Fr>();
+        loader.enable_deferred_inversion();
+        let values = (0..3)
+            .map(|idx| loader.calldataload_scalar(idx * 0x20))
+            .collect::<Vec<_>>();
+        let mut inverses = values
+            .iter()
+            .map(|value| loader.dup_scalar(value))
+            .collect::<Vec<_>>();
+        LoadedScalar::<Fr>::batch_invert(inverses.iter_mut());
+        inverses.push(FieldOps::invert(&values[0]).unwrap());
+        for (value, inverse) in values
+            .iter()
+            .chain(iter::once(&values[0]))
+            .zip(inverses.iter())
+        {
+            loader.assert_scalar_eq(&(value.clone() * inverse), &loader.load_const(&Fr::one()));
+        }
+        loader.code()
+    };
+    let accept = |values: [Fr; 3]| {
+        let (accept, _, _) = execute(
+            code.clone(),
+            encode_calldata(vec![values.to_vec()], Vec::new()),
+        );
+        accept
+    };
+
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let values = [(); 3].map(|_| Fr::random(&mut rng));
+    assert!(accept(values));
+    for idx in 0..3 {
+        let mut values = values;
+        values[idx] = Fr::zero();
+        assert!(!accept(values));
+    }
+}
+
+#[test]
+fn test_kzg_plonk_deferred_inversion() {
+    let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(true, 1),
+        StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let snark = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverGWC<_>,
+        VerifierGWC<_>,
+        AccumulatorStrategy<_>,
+        EvmTranscript<_, _, _, _>,
+        EvmTranscript<_, _, _, _>,
+        ChallengeEvm<_>
+    );
+
+    let loader = EvmLoader::new::<Fq, Fr>();
+    loader.enable_deferred_inversion();
+    let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(loader.clone());
+    let statements = snark
+        .statements
+        .iter()
+        .map(|instance| {
+            iter::repeat_with(|| transcript.read_scalar().unwrap())
+                .take(instance.len())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
+    PlonkAccumulationScheme::accumulate(
+        &snark.protocol,
+        &loader,
+        statements,
+        &mut transcript,
+        &mut strategy,
+    )
+    .unwrap();
+    let code = strategy
+        .code(params.get_g()[0], params.g2(), params.s_g2())
+        .unwrap();
+    let calldata = encode_calldata(snark.statements.clone(), snark.proof.clone());
+    let (accept, _, _) = execute(code, calldata.clone());
+    assert!(accept);
+
+    // All denominators of the accumulation are inverted by a single modexp
+    let program = loader.program();
+    assert!(
+        program
+            .ops()
+            .iter()
+            .filter(|op| matches!(op, Op::Invert { .. }))
+            .count()
+            > 1
+    );
+    assert_eq!(
+        program
+            .resolve_inversions(modulus::<Fr>())
+            .ops()
+            .iter()
+            .filter(|op| matches!(
+                op,
+                Op::Precompile {
+                    precompile: Precompiled::BigModExp,
+                    ..
+                }
+            ))
+            .count(),
+        1
+    );
+
+    // Proof is rejected once any denominator in the batch is zero
+    let mut program = program;
+    for op in program.ops_mut().iter_mut() {
+        if let Op::Invert { src, .. } = op {
+            *src = Expr::constant(0);
+            break;
+        }
+    }
+    let (accept, _, _) = execute(loader.code_with_program(program), calldata);
+    assert!(!accept);
+}
+
+/// Big-endian coordinates of generator of G1 of BLS12-381 in hex.
+const BLS12_381_G1: [&str; 2] = [
+    "17f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb",
+    "08b3f481e3aaa0f1a09e30ed741d8ae4fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1",
+];
+
+#[test]
+fn test_bls12_381_precompile_calls() {
+    let loader = EvmLoader::new_with_curve(EvmCurve::BLS12_381, CalldataLayout::Raw);
+    let lhs = loader.calldataload_ec_point(0);
+    let rhs = loader.calldataload_ec_point(0x80);
+    let sum = lhs.clone() + rhs;
+    let g2 = EvmCurve::BLS12_381.encode_g2([&[1], &[2]], [&[3], &[4]]);
+    assert_eq!(g2, [0, 1, 0, 2, 0, 3, 0, 4].map(U256::from).to_vec(),);
+    loader.pairing_n(&[(&lhs, &g2), (&sum, &g2)]);
+
+    let precompile_calls = loader
+        .program()
+        .ops()
+        .iter()
+        .filter_map(|op| match op {
+            Op::Precompile {
+                precompile,
+                cd_len,
+                rd_len,
+                ..
+            } => Some((*precompile, *cd_len, *rd_len)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        precompile_calls,
+        [
+            (Precompiled::Bls12381G1Add, 0x100, 0x80),
+            (Precompiled::Bls12381G1Add, 0x100, 0x80),
+            (Precompiled::Bls12381G1Add, 0x100, 0x80),
+            (Precompiled::Bls12381Pairing, 0x300, 0x20),
+        ]
+    );
+    assert!(!loader.code().is_empty());
+
+    // Coordinates of constant are padded to the coordinate size of the curve,
+    // which is checked with generator of BLS12-381.
+    let num_ops = loader.program().ops().len();
+    let [x, y] = BLS12_381_G1.map(|hex| U512::from_str_radix(hex, 16).unwrap());
+    let generator = loader.ec_point_load_coordinates(x, y);
+    loader.assert_ec_point_eq(&generator, &lhs);
+    assert_eq!(
+        loader.program().ops()[num_ops..],
+        [x, y]
+            .into_iter()
+            .flat_map(|coordinate| [coordinate >> 256, coordinate & U512::from(U256::MAX)])
+            .enumerate()
+            .map(|(idx, word)| Op::Assert(Expr::eq(
+                Expr::constant(U256::try_from(word).unwrap()),
+                Expr::MLoad(lhs.ptr() + idx * 0x20)
+            )))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+#[cfg(feature = "simulate")]
+fn test_bls12_381_precompiles() {
+    // Big-endian coordinates of generators of BLS12-381, padded to 64 bytes
+    // as input of EIP-2537 precompiles.
+    let coordinate = |hex: &str| {
+        let mut bytes = [0; 0x40];
+        U512::from_str_radix(hex, 16)
+            .unwrap()
+            .to_big_endian(&mut bytes);
+        bytes
+    };
+    let g1 = BLS12_381_G1.map(coordinate).concat();
+    let [x_0, x_1, y_0, y_1] = [
+        "024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8",
+        "13e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e",
+        "0ce5d527727d6e118cc9cdc6da2e351aadfd9baa8cbdd3a76d429a695160d12c923ac9cc3baca289e193548608b82801",
+        "0606c4a02ea734cc32acd2b02bc28b99cb3e287e85a763af267492ab572e99ab3f370d275cec1da1aaa9075ff05f79be",
+    ]
+    .map(coordinate);
+    let g2 = EvmCurve::BLS12_381.encode_g2([&x_0, &x_1], [&y_0, &y_1]);
+
+    // Code checking e(2 * g1, g2) * e(-g1, g2)^num_neg = 1, which holds only
+    // when `num_neg` is 2
+    let code = |num_neg: usize| {
+        let loader = EvmLoader::new_with_curve(EvmCurve::BLS12_381, CalldataLayout::Raw);
+        let g1 = loader.calldataload_ec_point(0);
+        let double = g1.clone() + g1.clone();
+        let neg = -g1.clone();
+        loader.assert_ec_point_eq(&(double.clone() + neg.clone()), &g1);
+        loader.pairing_n(
+            &iter::once((&double, g2.as_slice()))
+                .chain(iter::repeat((&neg, g2.as_slice())).take(num_neg))
+                .collect::<Vec<_>>(),
+        );
+        loader.code()
+    };
+
+    assert!(simulate(code(2), g1.clone()).success);
+    // Wrong pairing fails, so the one above is checked by the precompile
+    // instead of calling an empty account
+    assert!(!simulate(code(1), g1.clone()).success);
+    let mut off_curve = g1;
+    off_curve[0x7f] ^= 1;
+    assert!(!simulate(code(2), off_curve).success);
+    assert!(!simulate(code(2), vec![0; 0x80]).success);
+}
+
+/// Apply each operator of `GroupOps` to `a`, `b` and `c`, where results going
+/// through identity are offset by `b` to be comparable with constants.
+fn group_ops<T: GroupOps + Clone>(a: &T, b: &T, c: &T) -> Vec<T> {
+    let assign = |op: fn(&mut T, &T)| {
+        let mut lhs = a.clone();
+        op(&mut lhs, b);
+        lhs
+    };
+    vec![
+        a.clone() + b.clone(),
+        a.clone() + b,
+        a.clone() - b.clone(),
+        a.clone() - b,
+        -a.clone(),
+        assign(|lhs, rhs| *lhs += rhs.clone()),
+        assign(|lhs, rhs| *lhs += rhs),
+        assign(|lhs, rhs| *lhs -= rhs.clone()),
+        assign(|lhs, rhs| *lhs -= rhs),
+        a.clone() - a.clone() + b.clone(),
+        -(a.clone() - a.clone()) + b.clone(),
+        a.clone() - c.clone(),
+        c.clone() - a.clone(),
+        -c.clone(),
+        -(-a.clone()),
+    ]
+}
+
+#[test]
+fn test_ec_point_group_ops() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    for _ in 0..4 {
+        let [a, b, c] = [(); 3].map(|_| G1::random(&mut rng));
+        let code = {
+            let loader = EvmLoader::new::<Fq, Fr>();
+            let [loaded_a, loaded_b] = [0, 0x40].map(|offset| loader.calldataload_ec_point(offset));
+            let loaded_c = loader.ec_point_load_const(&c);
+            for (lhs, rhs) in group_ops(&loaded_a, &loaded_b, &loaded_c)
+                .iter()
+                .zip(group_ops(&a, &b, &c))
+            {
+                loader.assert_ec_point_eq(lhs, &loader.ec_point_load_const(&rhs));
+            }
+            loader.code()
+        };
+        let accept = |lhs: G1, rhs: G1| {
+            let coordinates = [lhs, rhs]
+                .iter()
+                .flat_map(|ec_point| {
+                    let coordinates = ec_point.to_affine().coordinates().unwrap();
+                    [*coordinates.x(), *coordinates.y()]
+                })
+                .collect();
+            let (accept, _, _) =
+                execute(code.clone(), encode_calldata(vec![coordinates], Vec::new()));
+            accept
+        };
+
+        assert!(accept(a, b));
+        assert!(!accept(b, a));
+    }
+}
+
+#[test]
+fn test_pairing_with_multiple_g2s() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [a, b, s_0, s_1, s_2] = [(); 5].map(|_| Fr::random(&mut rng));
+    // Pairing product is one when a * s_0 + b * s_1 + c * s_2 = 0
+    let c = -(a * s_0 + b * s_1) * Field::invert(&s_2).unwrap();
+    let g1 = G1Affine::generator();
+    let g2s = [s_0, s_1, s_2].map(|s| (G2Affine::generator() * s).to_affine());
+
+    let code = |g2s: &[G2Affine]| {
+        let loader = EvmLoader::new::<Fq, Fr>();
+        let terms = [2, 0, 1].map(|idx| (MSM::base(loader.calldataload_ec_point(idx * 0x40)), idx));
+        SameCurveAccumulation::<G1, Rc<EvmLoader>, LIMBS, BITS>::with_accumulator(
+            Accumulator::from_terms(terms),
+        )
+        .code_with_g2s(g1, g2s)
+    };
+    // Term paired with G2 point out of range fails instead of panicking
+    assert!(matches!(code(&g2s[..2]), Err(Error::MissingG2(2))));
+    let code = code(&g2s).unwrap();
+    let accept = |scalars: [Fr; 3]| {
+        let coordinates = scalars
+            .iter()
+            .flat_map(|scalar| {
+                let coordinates = (g1 * scalar).to_affine().coordinates().unwrap();
+                [*coordinates.x(), *coordinates.y()]
+            })
+            .collect();
+        let (accept, _, _) = execute(code.clone(), encode_calldata(vec![coordinates], Vec::new()));
+        accept
+    };
+
+    assert!(accept([a, b, c]));
+    assert!(!accept([a, b, c + Fr::one()]));
+    assert!(!accept([b, a, c]));
+}
+
+#[test]
+#[ignore = "cause it requires 128GB memory to run"]
+fn test_kzg_shplonk_aggregation_tree_with_evm_root() {
+    let (_, [snark1, snark2]) = create_two_snark(false);
+
+    let params = read_or_create_srs::<Bn256>(22);
+    let mut tree =
+        AggregationTree::<Bn256, ShplonkAccumulationScheme, ProverSHPLONK<_>, LIMBS, BITS>::new(
+            &params, 2,
+        );
+
+    // Chunks of the same layer with protocols in different order
+    let snark = tree
+        .aggregate::<EvmTranscript<_, _, _, _>, ChallengeEvm<_>, _>(
+            [snark1.clone(), snark2.clone(), snark2, snark1],
+            ChaCha20Rng::from_seed(Default::default()),
+        )
+        .unwrap();
+
+    halo2_kzg_native_verify!(
+        params,
+        &snark.protocol,
+        snark.statements.clone(),
+        ShplonkAccumulationScheme,
+        &mut EvmTranscript::<_, NativeLoader, _, _>::new(snark.proof.as_slice())
+    );
+    halo2_kzg_evm_verify!(
+        params,
+        &snark.protocol,
+        snark.statements,
+        snark.proof,
+        ShplonkAccumulationScheme
+    );
+}
+
+/// Keccak256 of uncompressed `lhs || rhs`, which are expected to be loaded
+/// next to each other.
+fn keccak_hash(lhs: &EcPoint, rhs: &EcPoint) -> Result<Scalar, Error> {
+    assert_eq!(lhs.ptr() + 0x40, rhs.ptr());
+    let (_, hash) = LoadedEcPoint::loader(lhs).squeeze_challenge(lhs.ptr(), 0x80);
+    Ok(hash)
+}
+
+fn native_keccak_hash(lhs: &G1, rhs: &G1) -> Result<Fr, Error> {
+    let mut transcript = EvmTranscript::<G1, NativeLoader, _, _>::new(&[] as &[u8]);
+    transcript.common_ec_point(lhs)?;
+    transcript.common_ec_point(rhs)?;
+    Ok(transcript.squeeze_challenge())
+}
+
+/// Snark of a proof of 2 circuits, each with `statements` as an accumulator
+/// at the same indices.
+fn create_encoded_snark(statements: [Vec<Fr>; 2]) -> (ParamsKZG<Bn256>, Snark<G1>) {
+    let accumulator_indices = (1..=statements[0].len())
+        .map(|idx| (0, idx))
+        .collect::<Vec<_>>();
+    let (params, pk, protocol, _) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(true, 2, accumulator_indices.clone()),
+        main_gate_with_range_with_statements(statements[0].clone())
+    );
+    let circuits = statements.map(main_gate_with_range_with_statements);
+    let snark = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverGWC<_>,
+        VerifierGWC<_>,
+        AccumulatorStrategy<_>,
+        EvmTranscript<_, _, _, _>,
+        EvmTranscript<_, _, _, _>,
+        ChallengeEvm<_>
+    );
+    (params, snark)
+}
+
+fn native_accumulate_encoded(
+    params: &ParamsKZG<Bn256>,
+    snark: &Snark<G1>,
+    mut strategy: SameCurveAccumulation<G1, NativeLoader, LIMBS, BITS>,
+) -> bool {
+    PlonkAccumulationScheme::accumulate(
+        &snark.protocol,
+        &NativeLoader,
+        snark.statements.clone(),
+        &mut EvmTranscript::<_, NativeLoader, _, _>::new(snark.proof.as_slice()),
+        &mut strategy,
+    )
+    .is_ok()
+        && matches!(
+            strategy.decide::<Bn256>(params.get_g()[0], params.g2(), params.s_g2()),
+            Ok(true)
+        )
+}
+
+/// Execute code accumulating `snark` by `strategy` with calldata of `snark`
+/// followed by `witness`.
+fn evm_accumulate_encoded(
+    params: &ParamsKZG<Bn256>,
+    snark: &Snark<G1>,
+    witness: Vec<u8>,
+    mut strategy: SameCurveAccumulation<G1, Rc<EvmLoader>, LIMBS, BITS>,
+) -> bool {
+    let loader = EvmLoader::new::<Fq, Fr>();
+    let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(loader.clone());
+    let statements = snark
+        .statements
+        .iter()
+        .map(|instance| {
+            iter::repeat_with(|| transcript.read_scalar().unwrap())
+                .take(instance.len())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    PlonkAccumulationScheme::accumulate(
+        &snark.protocol,
+        &loader,
+        statements,
+        &mut transcript,
+        &mut strategy,
+    )
+    .unwrap();
+    let code = strategy
+        .code(params.get_g()[0], params.g2(), params.s_g2())
+        .unwrap();
+
+    let calldata = iter::empty()
+        .chain(encode_calldata(
+            snark.statements.clone(),
+            snark.proof.clone(),
+        ))
+        .chain(witness)
+        .collect();
+    let (accept, _, _) = execute(code, calldata);
+    accept
+}
+
+#[test]
+fn test_accumulator_encodings() {
+    let [acc1, acc2]: [_; 2] = mock_kzg_accumulators(2).try_into().unwrap();
+
+    let (params, snark) = create_encoded_snark([limbs_encoded(&acc1), limbs_encoded(&acc2)]);
+    assert!(native_accumulate_encoded(
+        &params,
+        &snark,
+        SameCurveAccumulation::with_encoding(LimbsEncoding::<LIMBS, BITS>),
+    ));
+    assert!(evm_accumulate_encoded(
+        &params,
+        &snark,
+        Vec::new(),
+        SameCurveAccumulation::with_encoding(LimbsEncoding::<LIMBS, BITS>),
+    ));
+
+    // Accumulator mixed from valid ones
+    let (params, snark) =
+        create_encoded_snark([limbs_encoded(&acc1), limbs_encoded(&(acc1.0, acc2.1))]);
+    assert!(!native_accumulate_encoded(
+        &params,
+        &snark,
+        SameCurveAccumulation::default()
+    ));
+    assert!(!evm_accumulate_encoded(
+        &params,
+        &snark,
+        Vec::new(),
+        SameCurveAccumulation::default(),
+    ));
+
+    let (params, snark) =
+        create_encoded_snark([compressed_encoded(&acc1), compressed_encoded(&acc2)]);
+    assert!(native_accumulate_encoded(
+        &params,
+        &snark,
+        SameCurveAccumulation::with_encoding(CompressedEncoding::<LIMBS, BITS>),
+    ));
+    assert!(evm_accumulate_encoded(
+        &params,
+        &snark,
+        Vec::new(),
+        SameCurveAccumulation::with_encoding(CompressedEncoding::<LIMBS, BITS>),
+    ));
+
+    let [acc1, acc2] = [acc1, acc2].map(|(lhs, rhs)| (lhs.to_curve(), rhs.to_curve()));
+    let (params, snark) = create_encoded_snark(
+        [acc1, acc2].map(|(lhs, rhs)| vec![native_keccak_hash(&lhs, &rhs).unwrap()]),
+    );
+    // Witnesses are appended to calldata in the order of accumulators
+    let offset = encode_calldata(snark.statements.clone(), snark.proof.clone()).len();
+    let witness = |accumulators: [(G1, G1); 2]| {
+        accumulators
+            .iter()
+            .flat_map(|(lhs, rhs)| [lhs, rhs])
+            .flat_map(|ec_point| {
+                let uncompressed = ec_point.to_uncompressed();
+                iter::empty()
+                    .chain(uncompressed[..32].iter().rev())
+                    .chain(uncompressed[32..].iter().rev())
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+    for (accumulators, valid) in [([acc1, acc2], true), ([acc2, acc1], false)] {
+        assert_eq!(
+            native_accumulate_encoded(
+                &params,
+                &snark,
+                SameCurveAccumulation::with_encoding(HashCommittedEncoding::new(
+                    accumulators.to_vec(),
+                    native_keccak_hash,
+                )),
+            ),
+            valid
+        );
+        assert_eq!(
+            evm_accumulate_encoded(
+                &params,
+                &snark,
+                witness(accumulators),
+                SameCurveAccumulation::with_encoding(HashCommittedEncoding::new(
+                    vec![offset, offset + 0x80],
+                    keccak_hash,
+                )),
+            ),
+            valid
+        );
+    }
+}
+
+#[test]
+fn test_protocol_binding() {
+    let protocols = standard_plonk_protocols();
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let ec_points = [(); 2].map(|_| G1::random(&mut rng).to_affine());
+    let calldata = encode_calldata(
+        vec![ec_points
+            .iter()
+            .flat_map(|ec_point| [ec_point.x, ec_point.y])
+            .collect::<Vec<Fq>>()],
+        Vec::new(),
+    );
+
+    let native_binding = |protocols: &[Protocol<G1>]| {
+        combine_with_protocol_binding(
+            protocols,
+            &NativeLoader,
+            &mut EvmTranscript::<G1, NativeLoader, _, _>::new(calldata.as_slice()),
+        )
+        .unwrap()
+    };
+    let binding = native_binding(&protocols);
+    assert_ne!(
+        binding,
+        native_binding(&[protocols[1].clone(), protocols[0].clone()])
+    );
+
+    // Digests are constants absorbed after squeezing, and bindings are
+    // squeezed away from the transcript buffer, so both have to be copied
+    // next to the buffer to be absorbed
+    let code = {
+        let loader = EvmLoader::new::<Fq, Fr>();
+        let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(loader.clone());
+        let binding = combine_with_protocol_binding(&protocols, &loader, &mut transcript).unwrap();
+        loader.assert_scalar_eq(&binding, &loader.calldataload_scalar(calldata.len()));
+        loader.code()
+    };
+    let accept = |binding: Fr| {
+        let calldata = iter::empty()
+            .chain(calldata.clone())
+            .chain(encode_calldata(vec![vec![binding]], Vec::new()))
+            .collect();
+        let (accept, _, _) = execute(code.clone(), calldata);
+        accept
+    };
+    assert!(accept(binding));
+    assert!(!accept(binding + Fr::one()));
+}
@@ -1,40 +1,69 @@
 use crate::{
     collect_slice, halo2_kzg_config, halo2_kzg_create_snark, halo2_kzg_native_accumulate,
     halo2_kzg_native_verify, halo2_kzg_prepare,
-    loader::{halo2, native::NativeLoader},
+    loader::{
+        halo2::{self, AggregationCircuit, AggregationTree, SnarkWitness},
+        native::NativeLoader,
+        EcPointLoader, LoadedEcPoint, MsmFolding, ScalarLoader,
+    },
     protocol::{
         halo2::{
+            compile,
             test::{
-                kzg::{BITS, LIMBS},
+                kzg::{
+                    combine_with_protocol_binding, compressed_encoded, limbs_encoded,
+                    main_gate_with_range_with_statements, mock_kzg_accumulators,
+                    read_or_create_srs, standard_plonk_protocols, BITS, LIMBS,
+                },
                 MainGateWithRange, MainGateWithRangeConfig, StandardPlonk,
             },
             util::halo2::ChallengeScalar,
         },
         Protocol, Snark,
     },
-    scheme::kzg::{self, AccumulationScheme, ShplonkAccumulationScheme},
-    util::{fe_to_limbs, Curve, Group, PrimeCurveAffine},
+    scheme::kzg::{
+        self, AccumulationScheme, CompressedEncoding, HashCommittedEncoding, LimbsEncoding,
+        ShplonkAccumulationScheme, MSM,
+    },
+    util::{
+        fe_to_limbs, multi_scalar_multiplication, Curve, Field, Group, GroupEncoding,
+        PrimeCurveAffine, Transcript,
+    },
+    Error,
+};
+use halo2_curves::{
+    bn256::{Bn256, Fq, Fr, G1Affine, G2Affine, G2Prepared, G1},
+    pairing::{MillerLoopResult, MultiMillerLoop},
+    CurveAffine,
 };
-use halo2_curves::bn256::{Fr, G1Affine, G1};
 use halo2_proofs::{
-    circuit::{floor_planner::V1, Layouter, Value},
+    circuit::{self, floor_planner::V1, Layouter},
+    dev::MockProver,
     plonk,
-    plonk::Circuit,
+    plonk::{create_proof, keygen_pk, keygen_vk, Circuit},
     poly::{
         commitment::ParamsProver,
         kzg::{
+            commitment::KZGCommitmentScheme,
             multiopen::{ProverSHPLONK, VerifierSHPLONK},
             strategy::AccumulatorStrategy,
         },
     },
-    transcript::{Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer},
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
 };
 use halo2_wrong_ecc;
 use halo2_wrong_maingate::RegionCtx;
 use halo2_wrong_transcript::NativeRepresentation;
 use paste::paste;
 use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
-use std::rc::Rc;
+use std::{
+    cell::RefCell,
+    iter,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+};
 
 const T: usize = 5;
 const RATE: usize = 4;
@@ -47,40 +76,6 @@ type PoseidonTranscript<C, L, S, B> =
     halo2::PoseidonTranscript<C, L, S, B, NativeRepresentation, LIMBS, BITS, T, RATE, R_F, R_P>;
 type SameCurveAccumulation<C, L> = kzg::SameCurveAccumulation<C, L, LIMBS, BITS>;
 
-pub struct SnarkWitness<C: Curve> {
-    protocol: Protocol<C>,
-    statements: Vec<Vec<Value<<C as Group>::Scalar>>>,
-    proof: Value<Vec<u8>>,
-}
-
-impl<C: Curve> From<Snark<C>> for SnarkWitness<C> {
-    fn from(snark: Snark<C>) -> Self {
-        Self {
-            protocol: snark.protocol,
-            statements: snark
-                .statements
-                .into_iter()
-                .map(|statements| statements.into_iter().map(Value::known).collect::<Vec<_>>())
-                .collect(),
-            proof: Value::known(snark.proof),
-        }
-    }
-}
-
-impl<C: Curve> SnarkWitness<C> {
-    pub fn without_witnesses(&self) -> Self {
-        SnarkWitness {
-            protocol: self.protocol.clone(),
-            statements: self
-                .statements
-                .iter()
-                .map(|statements| vec![Value::unknown(); statements.len()])
-                .collect(),
-            proof: Value::unknown(),
-        }
-    }
-}
-
 pub fn accumulate<'a, 'b>(
     loader: &Rc<Halo2Loader<'a, 'b, G1Affine>>,
     stretagy: &mut SameCurveAccumulation<G1, Rc<Halo2Loader<'a, 'b, G1Affine>>>,
@@ -111,10 +106,58 @@ pub fn accumulate<'a, 'b>(
     Ok(())
 }
 
+pub fn create_two_snark(zk: bool) -> (G1Affine, [Snark<G1>; 2]) {
+    const K: u32 = 9;
+
+    let (params, snark1) = {
+        let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+            K,
+            halo2_kzg_config!(zk, 1),
+            StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+        );
+        let snark = halo2_kzg_create_snark!(
+            &params,
+            &pk,
+            &protocol,
+            &circuits,
+            ProverSHPLONK<_>,
+            VerifierSHPLONK<_>,
+            AccumulatorStrategy<_>,
+            PoseidonTranscript<_, _, _, _>,
+            PoseidonTranscript<_, _, _, _>,
+            ChallengeScalar<_>
+        );
+        (params, snark)
+    };
+    let snark2 = {
+        let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+            K,
+            halo2_kzg_config!(zk, 1),
+            MainGateWithRange::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+        );
+        halo2_kzg_create_snark!(
+            &params,
+            &pk,
+            &protocol,
+            &circuits,
+            ProverSHPLONK<_>,
+            VerifierSHPLONK<_>,
+            AccumulatorStrategy<_>,
+            PoseidonTranscript<_, _, _, _>,
+            PoseidonTranscript<_, _, _, _>,
+            ChallengeScalar<_>
+        )
+    };
+
+    (params.get_g()[0], [snark1, snark2])
+}
+
 pub struct Accumulation {
     g1: G1Affine,
     snarks: Vec<SnarkWitness<G1>>,
     instances: Vec<Fr>,
+    fixed_base_tables: bool,
+    rows: RefCell<usize>,
 }
 
 impl Accumulation {
@@ -123,47 +166,7 @@ impl Accumulation {
     }
 
     pub fn two_snark(zk: bool) -> Self {
-        const K: u32 = 9;
-
-        let (params, snark1) = {
-            let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
-                K,
-                halo2_kzg_config!(zk, 1),
-                StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
-            );
-            let snark = halo2_kzg_create_snark!(
-                &params,
-                &pk,
-                &protocol,
-                &circuits,
-                ProverSHPLONK<_>,
-                VerifierSHPLONK<_>,
-                AccumulatorStrategy<_>,
-                PoseidonTranscript<_, _, _, _>,
-                PoseidonTranscript<_, _, _, _>,
-                ChallengeScalar<_>
-            );
-            (params, snark)
-        };
-        let snark2 = {
-            let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
-                K,
-                halo2_kzg_config!(zk, 1),
-                MainGateWithRange::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
-            );
-            halo2_kzg_create_snark!(
-                &params,
-                &pk,
-                &protocol,
-                &circuits,
-                ProverSHPLONK<_>,
-                VerifierSHPLONK<_>,
-                AccumulatorStrategy<_>,
-                PoseidonTranscript<_, _, _, _>,
-                PoseidonTranscript<_, _, _, _>,
-                ChallengeScalar<_>
-            )
-        };
+        let (g1, [snark1, snark2]) = create_two_snark(zk);
 
         let mut strategy = SameCurveAccumulation::<G1, NativeLoader>::default();
         halo2_kzg_native_accumulate!(
@@ -181,8 +184,7 @@ impl Accumulation {
             &mut strategy
         );
 
-        let g1 = params.get_g()[0];
-        let accumulator = strategy.finalize(g1.to_curve());
+        let accumulator = strategy.finalize(g1.to_curve()).unwrap();
         let instances = [
             accumulator.0.to_affine().x,
             accumulator.0.to_affine().y,
@@ -196,6 +198,8 @@ impl Accumulation {
             g1,
             snarks: vec![snark1.into(), snark2.into()],
             instances,
+            fixed_base_tables: false,
+            rows: Default::default(),
         }
     }
 
@@ -230,7 +234,7 @@ impl Accumulation {
         );
 
         let g1 = params.get_g()[0];
-        let accumulator = strategy.finalize(g1.to_curve());
+        let accumulator = strategy.finalize(g1.to_curve()).unwrap();
         let instances = [
             accumulator.0.to_affine().x,
             accumulator.0.to_affine().y,
@@ -244,9 +248,18 @@ impl Accumulation {
             g1,
             snarks: vec![snark.into()],
             instances,
+            fixed_base_tables: false,
+            rows: Default::default(),
         }
     }
 
+    /// Multiply the generator and preprocessed commitments of snarks with
+    /// window tables, as [`halo2::AggregationCircuit`] does.
+    pub fn with_fixed_base_tables(mut self) -> Self {
+        self.fixed_base_tables = true;
+        self
+    }
+
     pub fn instances(&self) -> Vec<Vec<Fr>> {
         vec![self.instances.clone()]
     }
@@ -265,6 +278,8 @@ impl Circuit<Fr> for Accumulation {
                 .map(SnarkWitness::without_witnesses)
                 .collect(),
             instances: Vec::new(),
+            fixed_base_tables: self.fixed_base_tables,
+            rows: Default::default(),
         }
     }
 
@@ -290,14 +305,28 @@ impl Circuit<Fr> for Accumulation {
                 let ctx = RegionCtx::new(&mut region, &mut offset);
 
                 let loader = Halo2Loader::<G1Affine>::new(config.ecc_config(), ctx);
+                if self.fixed_base_tables {
+                    loader.enable_fixed_base_tables(iter::once(self.g1).chain(
+                        self.snarks.iter().flat_map(|snark| {
+                            snark
+                                .protocol
+                                .preprocessed
+                                .iter()
+                                .map(|ec_point| ec_point.to_affine())
+                        }),
+                    ));
+                }
                 let mut stretagy = SameCurveAccumulation::default();
                 for snark in self.snarks.iter() {
                     accumulate(&loader, &mut stretagy, snark)?;
                 }
-                let (lhs, rhs) = stretagy.finalize(self.g1);
+                let (lhs, rhs) = stretagy
+                    .finalize(self.g1)
+                    .map_err(|_| plonk::Error::Synthesis)?;
 
                 loader.print_row_metering();
                 println!("Total: {}", offset);
+                *self.rows.borrow_mut() = offset;
 
                 Ok((lhs, rhs))
             },
@@ -311,6 +340,474 @@ impl Circuit<Fr> for Accumulation {
     }
 }
 
+/// Rows of the region, and msm folding recorded by loader with its estimated
+/// saved rows.
+#[derive(Clone, Copy, Debug, Default)]
+struct MsmStats {
+    rows: usize,
+    folding: MsmFolding,
+    folding_rows: usize,
+}
+
+/// Circuit evaluating msm of `terms` with halo2 loader `repeat` times and
+/// exposing the last output as instances, where each scalar and base is either
+/// loaded as constant or assigned as witness, and constant bases in
+/// `fixed_bases` are multiplied with window tables.
+#[derive(Clone)]
+struct MsmCircuit {
+    terms: Vec<(halo2::Value<Fr, Fr>, halo2::Value<G1Affine, G1Affine>)>,
+    fixed_bases: Vec<G1Affine>,
+    repeat: usize,
+    stats: RefCell<MsmStats>,
+}
+
+impl MsmCircuit {
+    const K: u32 = 18;
+
+    fn new(terms: Vec<(halo2::Value<Fr, Fr>, halo2::Value<G1Affine, G1Affine>)>) -> Self {
+        Self {
+            terms,
+            fixed_bases: Vec::new(),
+            repeat: 1,
+            stats: Default::default(),
+        }
+    }
+
+    fn with_fixed_bases(mut self, fixed_bases: impl IntoIterator<Item = G1Affine>) -> Self {
+        self.fixed_bases = fixed_bases.into_iter().collect();
+        self
+    }
+
+    fn repeated(mut self, repeat: usize) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    fn instances(&self) -> Vec<Vec<Fr>> {
+        let (scalars, bases) = self
+            .terms
+            .iter()
+            .map(|(scalar, base)| match (scalar, base) {
+                (
+                    halo2::Value::Constant(scalar) | halo2::Value::Assigned(scalar),
+                    halo2::Value::Constant(base) | halo2::Value::Assigned(base),
+                ) => (*scalar, base.to_curve()),
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        let output = multi_scalar_multiplication(&scalars, &bases).to_affine();
+        vec![[output.x, output.y]
+            .map(fe_to_limbs::<_, _, LIMBS, BITS>)
+            .concat()]
+    }
+
+    fn assert_satisfied(&self) -> MsmStats {
+        MockProver::run::<_, false>(Self::K, self, self.instances())
+            .unwrap()
+            .assert_satisfied();
+        *self.stats.borrow()
+    }
+}
+
+impl Circuit<Fr> for MsmCircuit {
+    type Config = MainGateWithRangeConfig;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut plonk::ConstraintSystem<Fr>) -> Self::Config {
+        MainGateWithRangeConfig::configure::<Fr>(
+            meta,
+            vec![BITS / LIMBS],
+            BaseFieldEccChip::<G1Affine>::rns().overflow_lengths(),
+        )
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), plonk::Error> {
+        config.load_table(&mut layouter)?;
+
+        let output = layouter.assign_region(
+            || "",
+            |mut region| {
+                let mut offset = 0;
+                let ctx = RegionCtx::new(&mut region, &mut offset);
+
+                let loader = Halo2Loader::<G1Affine>::new(config.ecc_config(), ctx);
+                loader.enable_fixed_base_tables(self.fixed_bases.iter().cloned());
+                let output = iter::repeat_with(|| {
+                    let msm = self
+                        .terms
+                        .iter()
+                        .map(|(scalar, base)| {
+                            let scalar = match scalar {
+                                halo2::Value::Constant(scalar) => loader.load_const(scalar),
+                                halo2::Value::Assigned(scalar) => {
+                                    loader.assign_scalar(circuit::Value::known(*scalar))
+                                }
+                            };
+                            let base = match base {
+                                halo2::Value::Constant(base) => {
+                                    loader.ec_point_load_const(&base.to_curve())
+                                }
+                                halo2::Value::Assigned(base) => {
+                                    loader.assign_ec_point(circuit::Value::known(*base))
+                                }
+                            };
+                            MSM::base(base) * &scalar
+                        })
+                        .sum::<MSM<_, _>>();
+                    msm.evaluate(G1::generator()).assigned()
+                })
+                .take(self.repeat)
+                .last()
+                .unwrap();
+
+                let (folding, folding_rows) = (loader.msm_folding(), loader.msm_folding_rows());
+                *self.stats.borrow_mut() = MsmStats {
+                    rows: offset,
+                    folding,
+                    folding_rows,
+                };
+
+                Ok(output)
+            },
+        )?;
+
+        let ecc_chip = BaseFieldEccChip::<G1Affine>::new(config.ecc_config());
+        ecc_chip.expose_public(layouter.namespace(|| ""), output, 0)?;
+
+        Ok(())
+    }
+}
+
+/// Circuit evaluating msm of random assigned pairs once for each number in
+/// `num_pairs`, and recording rows spent on each msm.
+#[derive(Clone)]
+struct RepeatedMsmCircuit {
+    num_pairs: Vec<usize>,
+    rows: RefCell<Vec<usize>>,
+}
+
+impl Circuit<Fr> for RepeatedMsmCircuit {
+    type Config = MainGateWithRangeConfig;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut plonk::ConstraintSystem<Fr>) -> Self::Config {
+        <MsmCircuit as Circuit<Fr>>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), plonk::Error> {
+        config.load_table(&mut layouter)?;
+
+        layouter.assign_region(
+            || "",
+            |mut region| {
+                let mut offset = 0;
+                let ctx = RegionCtx::new(&mut region, &mut offset);
+
+                let mut rng = ChaCha20Rng::from_seed(Default::default());
+                let loader = Halo2Loader::<G1Affine>::new(config.ecc_config(), ctx);
+                for num_pairs in self.num_pairs.iter() {
+                    let msm = iter::repeat_with(|| {
+                        let scalar =
+                            loader.assign_scalar(circuit::Value::known(Fr::random(&mut rng)));
+                        let base = loader.assign_ec_point(circuit::Value::known(
+                            G1::random(&mut rng).to_affine(),
+                        ));
+                        MSM::base(base) * &scalar
+                    })
+                    .take(*num_pairs)
+                    .sum::<MSM<_, _>>();
+                    msm.evaluate(G1::generator());
+                }
+
+                *self.rows.borrow_mut() = loader
+                    .row_meterings()
+                    .into_iter()
+                    .map(|(_, rows)| rows)
+                    .collect();
+
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Circuit assigning point from limbs in [`LimbsEncoding`] or
+/// [`CompressedEncoding`], which is only satisfied by a valid point.
+#[derive(Clone)]
+enum EcPointFromLimbsCircuit {
+    Limbs([Fr; LIMBS], [Fr; LIMBS]),
+    Compressed([Fr; LIMBS], Fr),
+}
+
+impl EcPointFromLimbsCircuit {
+    /// Returns whether the circuit is satisfied, where witness generation
+    /// failing on invalid limbs is considered as rejected.
+    fn is_satisfied(&self) -> bool {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            MockProver::run::<_, false>(MsmCircuit::K, self, vec![Vec::new()])
+                .unwrap()
+                .verify()
+                .is_ok()
+        }))
+        .unwrap_or(false)
+    }
+}
+
+impl Circuit<Fr> for EcPointFromLimbsCircuit {
+    type Config = MainGateWithRangeConfig;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut plonk::ConstraintSystem<Fr>) -> Self::Config {
+        <MsmCircuit as Circuit<Fr>>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), plonk::Error> {
+        config.load_table(&mut layouter)?;
+
+        layouter.assign_region(
+            || "",
+            |mut region| {
+                let mut offset = 0;
+                let ctx = RegionCtx::new(&mut region, &mut offset);
+
+                let loader = Halo2Loader::<G1Affine>::new(config.ecc_config(), ctx);
+                let assign =
+                    |limb: Fr| loader.assign_scalar(circuit::Value::known(limb)).assigned();
+                match self {
+                    Self::Limbs(x, y) => {
+                        loader.assign_ec_point_from_limbs(x.map(assign), y.map(assign));
+                    }
+                    Self::Compressed(x, sign) => {
+                        loader.assign_ec_point_from_compressed(x.map(assign), assign(*sign));
+                    }
+                }
+
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Poseidon hash of `(lhs, rhs)` absorbed by a fresh transcript.
+fn poseidon_hash<'a, 'b>(
+    lhs: &halo2::EcPoint<'a, 'b, G1Affine, LIMBS, BITS>,
+    rhs: &halo2::EcPoint<'a, 'b, G1Affine, LIMBS, BITS>,
+) -> Result<halo2::Scalar<'a, 'b, G1Affine, LIMBS, BITS>, Error> {
+    let mut transcript = PoseidonTranscript::<_, Rc<Halo2Loader<G1Affine>>, _, _>::new(
+        LoadedEcPoint::loader(lhs),
+        circuit::Value::known(&[] as &[u8]),
+    );
+    transcript.common_ec_point(lhs)?;
+    transcript.common_ec_point(rhs)?;
+    Ok(transcript.squeeze_challenge())
+}
+
+fn native_poseidon_hash(lhs: &G1, rhs: &G1) -> Result<Fr, Error> {
+    let mut transcript = PoseidonTranscript::<G1Affine, NativeLoader, _, _>::new(&[] as &[u8]);
+    transcript.common_ec_point(lhs)?;
+    transcript.common_ec_point(rhs)?;
+    Ok(transcript.squeeze_challenge())
+}
+
+/// Encoding of accumulators in statements of snarks accumulated by
+/// [`EncodedAccumulation`], with witnesses of hash committed ones.
+#[derive(Clone)]
+enum Encoding {
+    Limbs,
+    Compressed,
+    HashCommitted(Vec<circuit::Value<(G1Affine, G1Affine)>>),
+}
+
+/// Circuit accumulating `snarks` with accumulators in `encoding`, and exposing
+/// the combined accumulator as instances.
+#[derive(Clone)]
+struct EncodedAccumulation {
+    g1: G1Affine,
+    snarks: Vec<SnarkWitness<G1>>,
+    encoding: Encoding,
+    instances: Vec<Fr>,
+}
+
+impl EncodedAccumulation {
+    const K: u32 = 21;
+
+    /// Create circuit with instances of `snarks` accumulated natively, which
+    /// fails if any accumulator is rejected.
+    fn new(g1: G1Affine, snarks: &[Snark<G1>], encoding: Encoding) -> Result<Self, Error> {
+        let mut strategy = match &encoding {
+            Encoding::Limbs => SameCurveAccumulation::with_encoding(LimbsEncoding::<LIMBS, BITS>),
+            Encoding::Compressed => {
+                SameCurveAccumulation::with_encoding(CompressedEncoding::<LIMBS, BITS>)
+            }
+            Encoding::HashCommitted(witnesses) => {
+                let witnesses = witnesses
+                    .iter()
+                    .map(|witness| {
+                        let mut accumulator = None;
+                        witness
+                            .map(|(lhs, rhs)| accumulator = Some((lhs.to_curve(), rhs.to_curve())));
+                        accumulator.unwrap()
+                    })
+                    .collect();
+                SameCurveAccumulation::with_encoding(HashCommittedEncoding::new(
+                    witnesses,
+                    native_poseidon_hash,
+                ))
+            }
+        };
+        for snark in snarks {
+            ShplonkAccumulationScheme::accumulate(
+                &snark.protocol,
+                &NativeLoader,
+                snark.statements.clone(),
+                &mut PoseidonTranscript::<G1Affine, _, _, _>::init(snark.proof.as_slice()),
+                &mut strategy,
+            )?;
+        }
+
+        let accumulator = strategy.finalize(g1.to_curve()).unwrap();
+        let instances = [
+            accumulator.0.to_affine().x,
+            accumulator.0.to_affine().y,
+            accumulator.1.to_affine().x,
+            accumulator.1.to_affine().y,
+        ]
+        .map(fe_to_limbs::<_, _, LIMBS, BITS>)
+        .concat();
+
+        Ok(Self {
+            g1,
+            snarks: snarks.iter().cloned().map(SnarkWitness::from).collect(),
+            encoding,
+            instances,
+        })
+    }
+
+    fn instances(&self) -> Vec<Vec<Fr>> {
+        vec![self.instances.clone()]
+    }
+}
+
+impl Circuit<Fr> for EncodedAccumulation {
+    type Config = MainGateWithRangeConfig;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            g1: self.g1,
+            snarks: self
+                .snarks
+                .iter()
+                .map(SnarkWitness::without_witnesses)
+                .collect(),
+            encoding: match &self.encoding {
+                Encoding::HashCommitted(witnesses) => {
+                    Encoding::HashCommitted(vec![circuit::Value::unknown(); witnesses.len()])
+                }
+                encoding => encoding.clone(),
+            },
+            instances: Vec::new(),
+        }
+    }
+
+    fn configure(meta: &mut plonk::ConstraintSystem<Fr>) -> Self::Config {
+        <Accumulation as Circuit<Fr>>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), plonk::Error> {
+        config.load_table(&mut layouter)?;
+
+        let (lhs, rhs) = layouter.assign_region(
+            || "",
+            |mut region| {
+                let mut offset = 0;
+                let ctx = RegionCtx::new(&mut region, &mut offset);
+
+                let loader = Halo2Loader::<G1Affine>::new(config.ecc_config(), ctx);
+                let mut stretagy = match &self.encoding {
+                    Encoding::Limbs => {
+                        SameCurveAccumulation::with_encoding(LimbsEncoding::<LIMBS, BITS>)
+                    }
+                    Encoding::Compressed => {
+                        SameCurveAccumulation::with_encoding(CompressedEncoding::<LIMBS, BITS>)
+                    }
+                    Encoding::HashCommitted(witnesses) => SameCurveAccumulation::with_encoding(
+                        HashCommittedEncoding::new(witnesses.clone(), poseidon_hash),
+                    ),
+                };
+                for snark in self.snarks.iter() {
+                    accumulate(&loader, &mut stretagy, snark)?;
+                }
+                stretagy
+                    .finalize(self.g1)
+                    .map_err(|_| plonk::Error::Synthesis)
+            },
+        )?;
+
+        let ecc_chip = BaseFieldEccChip::<G1Affine>::new(config.ecc_config());
+        ecc_chip.expose_public(layouter.namespace(|| ""), lhs, 0)?;
+        ecc_chip.expose_public(layouter.namespace(|| ""), rhs, 2 * LIMBS)?;
+
+        Ok(())
+    }
+}
+
+/// Separate snarks of the same circuit, each with one of `statements` as an
+/// accumulator at the same indices.
+fn create_encoded_snarks(statements: [Vec<Fr>; 2]) -> (G1Affine, [Snark<G1>; 2]) {
+    let accumulator_indices = (1..=statements[0].len())
+        .map(|idx| (0, idx))
+        .collect::<Vec<_>>();
+    let (params, pk, protocol, _) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(false, 1, accumulator_indices.clone()),
+        main_gate_with_range_with_statements(statements[0].clone())
+    );
+    let snarks = statements.map(|statements| {
+        let circuits = [main_gate_with_range_with_statements(statements)];
+        halo2_kzg_create_snark!(
+            &params,
+            &pk,
+            &protocol,
+            &circuits,
+            ProverSHPLONK<_>,
+            VerifierSHPLONK<_>,
+            AccumulatorStrategy<_>,
+            PoseidonTranscript<_, _, _, _>,
+            PoseidonTranscript<_, _, _, _>,
+            ChallengeScalar<_>
+        )
+    });
+    (params.get_g()[0], snarks)
+}
+
 macro_rules! test {
     (@ #[$($attr:meta),*], $name:ident, $k:expr, $config:expr, $create_circuit:expr) => {
         paste! {
@@ -365,6 +862,19 @@ test!(
     halo2_kzg_config!(true, 1, Accumulation::accumulator_indices()),
     Accumulation::two_snark_with_accumulator(true)
 );
+test!(
+    #[ignore = "cause it requires 64GB memory to run"],
+    aggregation_circuit_two_snark,
+    21,
+    AggregationCircuit::<G1Affine, ShplonkAccumulationScheme, LIMBS, BITS>::config(false),
+    {
+        let (g1, snarks) = create_two_snark(false);
+        AggregationCircuit::<G1Affine, ShplonkAccumulationScheme, LIMBS, BITS>::new(
+            g1, snarks, None,
+        )
+        .unwrap()
+    }
+);
 test!(
     #[ignore = "cause it requires 64GB memory to run"],
     accumulation_two_snark,
@@ -379,3 +889,746 @@ test!(
     halo2_kzg_config!(false, 1, Accumulation::accumulator_indices()),
     Accumulation::two_snark_with_accumulator(false)
 );
+
+#[test]
+fn test_hash_to_aux_generator() {
+    let aux_generator = halo2::hash_to_aux_generator::<G1Affine>(b"domain");
+    assert_eq!(
+        aux_generator,
+        halo2::hash_to_aux_generator::<G1Affine>(b"domain")
+    );
+    assert_ne!(
+        aux_generator,
+        halo2::hash_to_aux_generator::<G1Affine>(b"another domain")
+    );
+    assert!(!bool::from(aux_generator.is_identity()));
+}
+
+#[test]
+#[ignore = "cause it requires 128GB memory to run"]
+fn test_kzg_shplonk_aggregation_tree() {
+    let (_, [snark1, snark2]) = create_two_snark(false);
+
+    let params = read_or_create_srs::<Bn256>(22);
+    let mut tree =
+        AggregationTree::<Bn256, ShplonkAccumulationScheme, ProverSHPLONK<_>, LIMBS, BITS>::new(
+            &params, 2,
+        );
+    assert_eq!(tree.num_layers(4), 2);
+
+    // Chunks of the same layer with protocols in different order
+    let protocols = [snark1.protocol.clone(), snark2.protocol.clone()];
+    let snark = tree
+        .aggregate::<PoseidonTranscript<_, _, _, _>, ChallengeScalar<_>, _>(
+            [snark1.clone(), snark2.clone(), snark2, snark1],
+            ChaCha20Rng::from_seed(Default::default()),
+        )
+        .unwrap();
+    assert!(tree.proving_key(&protocols).is_some());
+    assert!(tree
+        .proving_key(&[protocols[1].clone(), protocols[0].clone()])
+        .is_some());
+
+    halo2_kzg_native_verify!(
+        params,
+        &snark.protocol,
+        snark.statements,
+        ShplonkAccumulationScheme,
+        &mut PoseidonTranscript::<G1Affine, _, _, _>::init(snark.proof.as_slice())
+    );
+}
+
+#[test]
+fn test_msm_with_vanishing_constant_terms() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [p, q, r] = [(); 3].map(|_| G1::random(&mut rng).to_affine());
+    let [a, b, s] = [(); 3].map(|_| Fr::random(&mut rng));
+    let cancelled = (-(p * a + q * b)).to_affine();
+
+    // Constant terms cancel each other out
+    MsmCircuit::new(vec![
+        (halo2::Value::Constant(a), halo2::Value::Constant(p)),
+        (halo2::Value::Constant(b), halo2::Value::Constant(q)),
+        (
+            halo2::Value::Constant(Fr::one()),
+            halo2::Value::Constant(cancelled),
+        ),
+        (halo2::Value::Assigned(s), halo2::Value::Assigned(r)),
+    ])
+    .assert_satisfied();
+
+    // Single constant term with identity base or zero scalar
+    MsmCircuit::new(vec![
+        (
+            halo2::Value::Constant(Fr::one()),
+            halo2::Value::Constant(G1Affine::identity()),
+        ),
+        (halo2::Value::Assigned(s), halo2::Value::Assigned(r)),
+    ])
+    .assert_satisfied();
+    MsmCircuit::new(vec![
+        (
+            halo2::Value::Constant(Fr::zero()),
+            halo2::Value::Constant(p),
+        ),
+        (halo2::Value::Assigned(s), halo2::Value::Assigned(r)),
+    ])
+    .assert_satisfied();
+}
+
+#[test]
+fn test_msm_folding_rows() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [p, q, r] = [(); 3].map(|_| G1::random(&mut rng).to_affine());
+    let [a, b, s] = [(); 3].map(|_| Fr::random(&mut rng));
+
+    let folded = MsmCircuit::new(vec![
+        (halo2::Value::Constant(a), halo2::Value::Constant(p)),
+        (halo2::Value::Constant(b), halo2::Value::Constant(q)),
+        (halo2::Value::Assigned(s), halo2::Value::Constant(r)),
+    ])
+    .with_fixed_bases([p, q, r])
+    .assert_satisfied();
+    let unfolded = MsmCircuit::new(vec![
+        (halo2::Value::Assigned(a), halo2::Value::Constant(p)),
+        (halo2::Value::Assigned(b), halo2::Value::Constant(q)),
+        (halo2::Value::Assigned(s), halo2::Value::Constant(r)),
+    ])
+    .with_fixed_bases([p, q, r])
+    .assert_satisfied();
+
+    assert_eq!(
+        folded.folding,
+        MsmFolding {
+            scalar_muls: 2,
+            additions: 1,
+        }
+    );
+    assert_eq!(unfolded.folding, MsmFolding::default());
+    assert_eq!(unfolded.folding_rows, 0);
+
+    // Estimate includes rows of assigning the folded constant point, as the
+    // tables of folded bases take no rows, so it's an upper bound
+    assert!(folded.rows < unfolded.rows);
+    assert!(unfolded.rows - folded.rows <= folded.folding_rows);
+}
+
+#[test]
+fn test_fixed_base_msm() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [p, q, r] = [(); 3].map(|_| G1::random(&mut rng).to_affine());
+    let s = Fr::random(&mut rng);
+
+    // Scalar with zero digits in every window but one, or in all windows
+    for scalar in
+        iter::once(Fr::zero()).chain([0, 1, 4, 127, 253].map(|k| Fr::from(2).pow_vartime(&[k])))
+    {
+        MsmCircuit::new(vec![
+            (halo2::Value::Assigned(scalar), halo2::Value::Constant(p)),
+            (halo2::Value::Assigned(s), halo2::Value::Assigned(r)),
+        ])
+        .with_fixed_bases([p])
+        .assert_satisfied();
+    }
+    // Zero scalars for different fixed bases
+    MsmCircuit::new(vec![
+        (
+            halo2::Value::Assigned(Fr::zero()),
+            halo2::Value::Constant(p),
+        ),
+        (
+            halo2::Value::Assigned(Fr::zero()),
+            halo2::Value::Constant(q),
+        ),
+        (halo2::Value::Assigned(s), halo2::Value::Assigned(r)),
+    ])
+    .with_fixed_bases([p, q])
+    .assert_satisfied();
+
+    // Constant term of msm of each snark aggregated one by one, which
+    // multiplies the generator by an assigned scalar once per snark
+    let num_snarks = 4;
+    let g1 = G1Affine::generator();
+    let aggregation = MsmCircuit::new(vec![(
+        halo2::Value::Assigned(Fr::random(&mut rng)),
+        halo2::Value::Constant(g1),
+    )])
+    .repeated(num_snarks);
+    let fixed_base = aggregation
+        .clone()
+        .with_fixed_bases([g1])
+        .assert_satisfied();
+    let variable_base = aggregation.assert_satisfied();
+    assert!(fixed_base.rows < variable_base.rows);
+}
+
+#[test]
+fn test_fixed_base_msm_of_preprocessed() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [protocol, _] = standard_plonk_protocols();
+    let preprocessed = protocol
+        .preprocessed
+        .iter()
+        .filter(|ec_point| !bool::from(ec_point.is_identity()))
+        .map(|ec_point| ec_point.to_affine())
+        .collect::<Vec<_>>();
+
+    // Preprocessed commitments of a known protocol with assigned scalars, as
+    // in msm of accumulating its proofs
+    let msm = MsmCircuit::new(
+        preprocessed
+            .iter()
+            .map(|base| {
+                (
+                    halo2::Value::Assigned(Fr::random(&mut rng)),
+                    halo2::Value::Constant(*base),
+                )
+            })
+            .chain([(
+                halo2::Value::Assigned(Fr::random(&mut rng)),
+                halo2::Value::Assigned(G1::random(&mut rng).to_affine()),
+            )])
+            .collect(),
+    );
+    let fixed_base = msm
+        .clone()
+        .with_fixed_bases(preprocessed)
+        .assert_satisfied();
+    let variable_base = msm.assert_satisfied();
+    assert!(fixed_base.rows < variable_base.rows);
+}
+
+#[test]
+#[ignore = "cause it requires 64GB memory to run"]
+fn test_fixed_base_tables_for_preprocessed() {
+    const K: u32 = 21;
+
+    let rows = |circuit: Accumulation| {
+        MockProver::run::<_, false>(K, &circuit, circuit.instances())
+            .unwrap()
+            .assert_satisfied();
+        let rows = *circuit.rows.borrow();
+        rows
+    };
+
+    // Accumulating proofs of known protocols as AggregationCircuit does
+    assert!(
+        rows(Accumulation::two_snark(false).with_fixed_base_tables())
+            < rows(Accumulation::two_snark(false))
+    );
+}
+
+#[test]
+fn test_deterministic_keygen_and_proof() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [p, q] = [(); 2].map(|_| G1::random(&mut rng).to_affine());
+    let [a, b] = [(); 2].map(|_| Fr::random(&mut rng));
+    let circuit = MsmCircuit::new(vec![
+        (halo2::Value::Assigned(a), halo2::Value::Constant(p)),
+        (halo2::Value::Assigned(b), halo2::Value::Assigned(q)),
+    ]);
+    let instances = circuit.instances();
+
+    // Same fixed columns and same advice columns (by same proof with same rng)
+    // across keygens and proofs
+    let params = read_or_create_srs::<Bn256>(MsmCircuit::K);
+    let [(protocol1, proof1), (protocol2, proof2)] = [(); 2].map(|_| {
+        let vk = keygen_vk::<KZGCommitmentScheme<_>, _, false>(&params, &circuit).unwrap();
+        let pk = keygen_pk::<KZGCommitmentScheme<_>, _, false>(&params, vk, &circuit).unwrap();
+        let protocol = compile::<G1>(pk.get_vk(), halo2_kzg_config!(false, 1));
+
+        let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(Vec::new());
+        create_proof::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _, _, _, _, false>(
+            &params,
+            &pk,
+            &[circuit.clone()],
+            &[&[instances[0].as_slice()]],
+            ChaCha20Rng::from_seed(Default::default()),
+            &mut transcript,
+        )
+        .unwrap();
+        (protocol, transcript.finalize())
+    });
+    assert_eq!(protocol1.preprocessed, protocol2.preprocessed);
+    assert_eq!(proof1, proof2);
+}
+
+#[test]
+fn test_assign_aux_once_per_num_pairs() {
+    let circuit = RepeatedMsmCircuit {
+        num_pairs: vec![2, 2, 3, 2, 3],
+        rows: Default::default(),
+    };
+    MockProver::run::<_, false>(MsmCircuit::K, &circuit, Vec::new())
+        .unwrap()
+        .assert_satisfied();
+
+    // Only the first msm of each number of pairs spends rows on aux
+    let rows = circuit.rows.borrow();
+    assert_eq!(rows.len(), 5);
+    assert!(rows[0] > rows[1]);
+    assert_eq!(rows[1], rows[3]);
+    assert!(rows[2] > rows[4]);
+}
+
+#[test]
+fn test_aggregation_circuit_instances() {
+    type AggregationCircuit =
+        halo2::AggregationCircuit<G1Affine, ShplonkAccumulationScheme, LIMBS, BITS>;
+
+    let (g1, snarks) = create_two_snark(false);
+    assert!(matches!(
+        AggregationCircuit::new(g1, Vec::new(), None),
+        Err(Error::MissingSnark)
+    ));
+
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let previous = (
+        G1::random(&mut rng).to_affine(),
+        G1::random(&mut rng).to_affine(),
+    );
+    let [without_previous, with_previous] = [None, Some(previous)].map(|previous| {
+        AggregationCircuit::new(g1, snarks.clone(), previous)
+            .unwrap()
+            .instances()
+            .remove(0)
+    });
+
+    // Previous accumulator is exposed right after the combined one
+    assert_eq!(without_previous.len(), 4 * LIMBS);
+    assert_eq!(with_previous.len(), 8 * LIMBS);
+    assert_eq!(
+        with_previous[4 * LIMBS..],
+        [previous.0.x, previous.0.y, previous.1.x, previous.1.y]
+            .map(fe_to_limbs::<_, _, LIMBS, BITS>)
+            .concat()
+    );
+    assert_ne!(with_previous[..4 * LIMBS], without_previous[..]);
+
+    assert!(matches!(
+        AggregationCircuit::new(g1, snarks, Some((G1Affine::identity(), previous.1))),
+        Err(Error::AccumulatorIsIdentity)
+    ));
+}
+
+#[test]
+#[ignore = "cause it requires 64GB memory to run"]
+fn test_aggregation_circuit_with_previous() {
+    type AggregationCircuit =
+        halo2::AggregationCircuit<G1Affine, ShplonkAccumulationScheme, LIMBS, BITS>;
+    const K: u32 = 21;
+
+    let (g1, snarks) = create_two_snark(false);
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let previous = (
+        G1::random(&mut rng).to_affine(),
+        G1::random(&mut rng).to_affine(),
+    );
+    let circuit = AggregationCircuit::new(g1, snarks, Some(previous)).unwrap();
+    MockProver::run::<_, false>(K, &circuit, circuit.instances())
+        .unwrap()
+        .assert_satisfied();
+
+    // Both the combined and the previous accumulator exposed are constrained
+    for idx in [0, 4 * LIMBS] {
+        let mut instances = circuit.instances();
+        instances[0][idx] += Fr::one();
+        assert!(MockProver::run::<_, false>(K, &circuit, instances)
+            .unwrap()
+            .verify()
+            .is_err());
+    }
+}
+
+#[test]
+fn test_aggregation_tree_proving_key_by_protocols() {
+    let (_, [snark1, snark2]) = create_two_snark(false);
+    let (p1, p2) = (snark1.protocol, snark2.protocol);
+
+    // Proving keys of any circuit work for the cache itself
+    let (params, pk1, _, _) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(false, 1),
+        StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let (_, pk2, _, _) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(false, 1),
+        MainGateWithRange::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let preprocessed = |pk: &plonk::ProvingKey<G1Affine>| {
+        compile::<G1>(pk.get_vk(), halo2_kzg_config!(false, 1)).preprocessed
+    };
+    let (preprocessed1, preprocessed2) = (preprocessed(&pk1), preprocessed(&pk2));
+
+    let mut tree =
+        AggregationTree::<Bn256, ShplonkAccumulationScheme, ProverSHPLONK<_>, LIMBS, BITS>::new(
+            &params, 2,
+        );
+    assert!(matches!(
+        tree.aggregate::<PoseidonTranscript<_, _, _, _>, ChallengeScalar<_>, _>(
+            Vec::new(),
+            ChaCha20Rng::from_seed(Default::default()),
+        ),
+        Err(Error::MissingSnark)
+    ));
+
+    // Mixed protocols in one chunk are keyed in order
+    tree.insert_proving_key(&[p1.clone(), p2.clone()], pk1);
+    assert!(tree.proving_key(&[p2.clone(), p1.clone()]).is_none());
+    assert!(tree.proving_key(&[p1.clone(), p1.clone()]).is_none());
+    tree.insert_proving_key(&[p2.clone(), p1.clone()], pk2);
+    assert_eq!(
+        preprocessed(tree.proving_key(&[p1.clone(), p2.clone()]).unwrap()),
+        preprocessed1
+    );
+    assert_eq!(
+        preprocessed(tree.proving_key(&[p2, p1]).unwrap()),
+        preprocessed2
+    );
+}
+
+#[test]
+fn test_hash_committed_encoding_across_snarks() {
+    let accumulators = mock_kzg_accumulators(2)
+        .into_iter()
+        .map(|(lhs, rhs)| (lhs.to_curve(), rhs.to_curve()))
+        .collect::<Vec<_>>();
+    let (_, snarks) = create_encoded_snarks(
+        [accumulators[0], accumulators[1]]
+            .map(|(lhs, rhs)| vec![native_poseidon_hash(&lhs, &rhs).unwrap()]),
+    );
+
+    let accumulate_with = |witnesses: Vec<(G1, G1)>| {
+        let mut strategy = SameCurveAccumulation::<G1, NativeLoader>::with_encoding(
+            HashCommittedEncoding::new(witnesses, native_poseidon_hash),
+        );
+        snarks.iter().try_for_each(|snark| {
+            ShplonkAccumulationScheme::accumulate(
+                &snark.protocol,
+                &NativeLoader,
+                snark.statements.clone(),
+                &mut PoseidonTranscript::<G1Affine, _, _, _>::init(snark.proof.as_slice()),
+                &mut strategy,
+            )
+            .map(|_| ())
+        })
+    };
+
+    // Each snark's accumulator is indexed after ones of the previous snarks
+    assert!(accumulate_with(accumulators.clone()).is_ok());
+    assert!(matches!(
+        accumulate_with(vec![accumulators[1], accumulators[0]]),
+        Err(Error::InvalidInstances)
+    ));
+    assert!(matches!(
+        accumulate_with(vec![accumulators[0]]),
+        Err(Error::InvalidInstances)
+    ));
+}
+
+#[test]
+#[ignore = "cause it requires 64GB memory to run"]
+fn test_accumulator_encodings() {
+    let accumulators = mock_kzg_accumulators(2);
+    let assert_satisfied = |circuit: EncodedAccumulation| {
+        MockProver::run::<_, false>(EncodedAccumulation::K, &circuit, circuit.instances())
+            .unwrap()
+            .assert_satisfied()
+    };
+
+    let (g1, snarks) = create_encoded_snarks([
+        limbs_encoded(&accumulators[0]),
+        limbs_encoded(&accumulators[1]),
+    ]);
+    assert_satisfied(EncodedAccumulation::new(g1, &snarks, Encoding::Limbs).unwrap());
+
+    let (g1, snarks) = create_encoded_snarks([
+        compressed_encoded(&accumulators[0]),
+        compressed_encoded(&accumulators[1]),
+    ]);
+    assert_satisfied(EncodedAccumulation::new(g1, &snarks, Encoding::Compressed).unwrap());
+
+    let (g1, snarks) =
+        create_encoded_snarks([accumulators[0], accumulators[1]].map(|(lhs, rhs)| {
+            vec![native_poseidon_hash(&lhs.to_curve(), &rhs.to_curve()).unwrap()]
+        }));
+    let witnesses = |accumulators: [(G1Affine, G1Affine); 2]| {
+        Encoding::HashCommitted(accumulators.map(circuit::Value::known).to_vec())
+    };
+    let circuit =
+        EncodedAccumulation::new(g1, &snarks, witnesses([accumulators[0], accumulators[1]]))
+            .unwrap();
+    assert_satisfied(circuit.clone());
+
+    // Witnesses in order of snarks swapped
+    let swapped = EncodedAccumulation {
+        encoding: witnesses([accumulators[1], accumulators[0]]),
+        ..circuit
+    };
+    assert!(
+        MockProver::run::<_, false>(EncodedAccumulation::K, &swapped, swapped.instances())
+            .unwrap()
+            .verify()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_assign_ec_point_from_limbs_validation() {
+    use EcPointFromLimbsCircuit::{Compressed, Limbs};
+
+    let ec_point = G1::random(ChaCha20Rng::from_seed(Default::default())).to_affine();
+    let x = fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.x);
+    let y = fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.y);
+    let sign = Fr::from(bool::from(ec_point.y.is_odd()) as u64);
+    assert!(Limbs(x, y).is_satisfied());
+    assert!(Compressed(x, sign).is_satisfied());
+
+    // Same value with the carry of the first limb moved into itself
+    let mut overflowed = x;
+    overflowed[0] += Fr::from(2).pow_vartime(&[BITS as u64]);
+    overflowed[1] -= Fr::one();
+    assert!(!Limbs(overflowed, y).is_satisfied());
+    assert!(!Compressed(overflowed, sign).is_satisfied());
+
+    // Limbs of base field modulus
+    let mut modulus = fe_to_limbs::<_, Fr, LIMBS, BITS>(-Fq::one());
+    modulus[0] += Fr::one();
+    assert!(!Limbs(modulus, y).is_satisfied());
+    assert!(!Compressed(modulus, Fr::zero()).is_satisfied());
+
+    // Off curve
+    assert!(!Limbs(x, fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.y + Fq::one())).is_satisfied());
+    let off_curve_x = (1u64..)
+        .map(Fq::from)
+        .find(|x| bool::from((x.square() * x + G1Affine::b()).sqrt().is_none()))
+        .unwrap();
+    assert!(!Compressed(fe_to_limbs::<_, Fr, LIMBS, BITS>(off_curve_x), Fr::zero()).is_satisfied());
+
+    // Sign not a bit
+    assert!(!Compressed(x, Fr::from(2)).is_satisfied());
+
+    // Identity
+    assert!(!Limbs([Fr::zero(); LIMBS], [Fr::zero(); LIMBS]).is_satisfied());
+}
+
+/// Circuit combining accumulators of points read from `stream` for each of
+/// `protocols` with protocol binding, and constraining the binding to be
+/// `binding`.
+#[derive(Clone)]
+struct ProtocolBindingCircuit {
+    protocols: Vec<Protocol<G1>>,
+    stream: Vec<u8>,
+    binding: Fr,
+}
+
+impl ProtocolBindingCircuit {
+    fn is_satisfied(&self) -> bool {
+        MockProver::run::<_, false>(MsmCircuit::K, self, vec![Vec::new()])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+}
+
+impl Circuit<Fr> for ProtocolBindingCircuit {
+    type Config = MainGateWithRangeConfig;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut plonk::ConstraintSystem<Fr>) -> Self::Config {
+        <MsmCircuit as Circuit<Fr>>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), plonk::Error> {
+        config.load_table(&mut layouter)?;
+
+        layouter.assign_region(
+            || "",
+            |mut region| {
+                let mut offset = 0;
+                let ctx = RegionCtx::new(&mut region, &mut offset);
+
+                let loader = Halo2Loader::<G1Affine>::new(config.ecc_config(), ctx);
+                let mut transcript = PoseidonTranscript::<_, Rc<Halo2Loader<G1Affine>>, _, _>::new(
+                    &loader,
+                    circuit::Value::known(self.stream.as_slice()),
+                );
+                let binding =
+                    combine_with_protocol_binding(&self.protocols, &loader, &mut transcript)
+                        .map_err(|_| plonk::Error::Synthesis)?;
+                loader.assert_scalar_eq(
+                    &binding,
+                    &loader.assign_scalar(circuit::Value::known(self.binding)),
+                );
+
+                Ok(())
+            },
+        )
+    }
+}
+
+#[test]
+fn test_protocol_binding() {
+    let protocols = standard_plonk_protocols().to_vec();
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let stream = iter::repeat_with(|| G1::random(&mut rng).to_affine().to_bytes())
+        .take(2)
+        .flat_map(|bytes| bytes.as_ref().to_vec())
+        .collect::<Vec<_>>();
+
+    let binding = combine_with_protocol_binding(
+        &protocols,
+        &NativeLoader,
+        &mut PoseidonTranscript::<G1Affine, NativeLoader, _, _>::new(stream.as_slice()),
+    )
+    .unwrap();
+    let circuit = |binding: Fr| ProtocolBindingCircuit {
+        protocols: protocols.clone(),
+        stream: stream.clone(),
+        binding,
+    };
+    assert!(circuit(binding).is_satisfied());
+    assert!(!circuit(binding + Fr::one()).is_satisfied());
+}
+
+/// Circuit finalizing an accumulator with terms paired with 3 G2 points by
+/// `finalize_terms`, and exposing each term in the order of G2 points.
+#[derive(Clone)]
+struct MultipleG2sCircuit {
+    g1: G1Affine,
+    scalars: [Fr; 3],
+}
+
+impl MultipleG2sCircuit {
+    /// Terms of the same accumulator evaluated natively, where terms are
+    /// pushed out of the order of G2 points, and the one of `scalars[0]` is
+    /// scalar of generator.
+    fn terms(&self) -> Vec<(G1, usize)> {
+        kzg::Accumulator::<G1, NativeLoader>::from_terms([
+            (MSM::base(self.g1 * self.scalars[2]), 2),
+            (MSM::scalar(self.scalars[0]), 0),
+            (MSM::base(self.g1 * self.scalars[1]), 1),
+        ])
+        .evaluate_terms(self.g1.to_curve())
+    }
+
+    fn instances(&self) -> Vec<Vec<Fr>> {
+        vec![self
+            .terms()
+            .into_iter()
+            .flat_map(|(ec_point, _)| {
+                let ec_point = ec_point.to_affine();
+                [ec_point.x, ec_point.y]
+            })
+            .flat_map(fe_to_limbs::<_, _, LIMBS, BITS>)
+            .collect()]
+    }
+}
+
+impl Circuit<Fr> for MultipleG2sCircuit {
+    type Config = MainGateWithRangeConfig;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut plonk::ConstraintSystem<Fr>) -> Self::Config {
+        <MsmCircuit as Circuit<Fr>>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), plonk::Error> {
+        config.load_table(&mut layouter)?;
+
+        let terms = layouter.assign_region(
+            || "",
+            |mut region| {
+                let mut offset = 0;
+                let ctx = RegionCtx::new(&mut region, &mut offset);
+
+                let loader = Halo2Loader::<G1Affine>::new(config.ecc_config(), ctx);
+                let assign_ec_point = |scalar: Fr| {
+                    loader.assign_ec_point(circuit::Value::known((self.g1 * scalar).to_affine()))
+                };
+                let accumulator = kzg::Accumulator::from_terms([
+                    (MSM::base(assign_ec_point(self.scalars[2])), 2),
+                    (
+                        MSM::scalar(loader.assign_scalar(circuit::Value::known(self.scalars[0]))),
+                        0,
+                    ),
+                    (MSM::base(assign_ec_point(self.scalars[1])), 1),
+                ]);
+                kzg::SameCurveAccumulation::<_, _, LIMBS, BITS>::with_accumulator(accumulator)
+                    .finalize_terms(self.g1)
+                    .map_err(|_| plonk::Error::Synthesis)
+            },
+        )?;
+
+        let ecc_chip = BaseFieldEccChip::<G1Affine>::new(config.ecc_config());
+        for (idx, (ec_point, g2_index)) in terms.into_iter().enumerate() {
+            assert_eq!(g2_index, idx);
+            ecc_chip.expose_public(layouter.namespace(|| ""), ec_point, idx * 2 * LIMBS)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_finalize_terms_with_multiple_g2s() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [a, b, s_0, s_1, s_2] = [(); 5].map(|_| Fr::random(&mut rng));
+    // Pairing product is one when a * s_0 + b * s_1 + c * s_2 = 0
+    let c = -(a * s_0 + b * s_1) * Field::invert(&s_2).unwrap();
+    let g1 = G1Affine::generator();
+    let g2s = [s_0, s_1, s_2].map(|s| G2Prepared::from((G2Affine::generator() * s).to_affine()));
+
+    let circuit = |scalars: [Fr; 3]| MultipleG2sCircuit { g1, scalars };
+    // Decide terms exposed by the circuit, which are checked to be the ones
+    // evaluated natively
+    let decide = |circuit: MultipleG2sCircuit| {
+        MockProver::run::<_, false>(MsmCircuit::K, &circuit, circuit.instances())
+            .unwrap()
+            .assert_satisfied();
+        let terms = circuit
+            .terms()
+            .into_iter()
+            .map(|(ec_point, g2_index)| (ec_point.to_affine(), &g2s[g2_index]))
+            .collect::<Vec<_>>();
+        bool::from(
+            Bn256::multi_miller_loop(
+                &terms
+                    .iter()
+                    .map(|(ec_point, g2)| (ec_point, *g2))
+                    .collect::<Vec<_>>(),
+            )
+            .final_exponentiation()
+            .is_identity(),
+        )
+    };
+    assert!(decide(circuit([a, b, c])));
+    assert!(!decide(circuit([a, b, c + Fr::one()])));
+    assert!(!decide(circuit([b, a, c])));
+
+    assert!(MockProver::run::<_, false>(
+        MsmCircuit::K,
+        &circuit([a, b, c]),
+        circuit([b, a, c]).instances()
+    )
+    .unwrap()
+    .verify()
+    .is_err());
+}
@@ -1,17 +1,40 @@
 use crate::{
-    collect_slice, halo2_kzg_config, halo2_kzg_create_snark, halo2_kzg_native_verify,
-    halo2_kzg_prepare,
-    protocol::halo2::test::{
-        kzg::{main_gate_with_range_with_mock_kzg_accumulator, LIMBS},
-        Plookuper, StandardPlonk,
+    collect_slice, halo2_kzg_config, halo2_kzg_create_snark, halo2_kzg_native_accumulate,
+    halo2_kzg_native_verify, halo2_kzg_prepare,
+    loader::native::{batch_verify, AccumulatorCheckpoint, CycleAccumulator, NativeLoader},
+    protocol::{
+        halo2::test::{
+            kzg::{
+                main_gate_with_range_with_mock_kzg_accumulator, standard_plonk_protocols, BITS,
+                LIMBS,
+            },
+            Plookuper, StandardPlonk,
+        },
+        Snark,
     },
-    scheme::kzg::{PlonkAccumulationScheme, ShplonkAccumulationScheme},
+    scheme::kzg::{
+        AccumulationStrategy, Accumulator, AccumulatorEncoding, CompressedEncoding,
+        CycleCurveAccumulation, HashCommittedEncoding, LimbsEncoding, PlonkAccumulationScheme,
+        SameCurveAccumulation, ShplonkAccumulationScheme, MSM,
+    },
+    util::{
+        fe_from_limbs, fe_to_limbs, multi_scalar_multiplication, Curve, Field, Group,
+        PrimeCurveAffine, PrimeField,
+    },
+    Error,
+};
+use halo2_curves::{
+    bn256::{Bn256, Fq, Fr, G1Affine, G2Affine, G1},
+    pasta::{pallas, vesta},
+    CurveAffine,
 };
-use halo2_curves::bn256::G1Affine;
 use halo2_proofs::{
-    poly::kzg::{
-        multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC, VerifierSHPLONK},
-        strategy::AccumulatorStrategy,
+    poly::{
+        commitment::ParamsProver,
+        kzg::{
+            multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC, VerifierSHPLONK},
+            strategy::AccumulatorStrategy,
+        },
     },
     transcript::{Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer},
 };
@@ -86,3 +109,737 @@ test!(
     halo2_kzg_config!(false, 2),
     Plookuper::<_, 2, 5, false>::rand(ChaCha20Rng::from_seed(Default::default()), 1 << 9)
 );
+
+#[test]
+fn test_kzg_shplonk_batch_verify() {
+    let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(false, 1),
+        StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let snark = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverSHPLONK<_>,
+        VerifierSHPLONK<_>,
+        AccumulatorStrategy<_>,
+        Blake2bWrite<_, _, _>,
+        Blake2bRead<_, _, _>,
+        Challenge255<_>
+    );
+    let other_circuits = [StandardPlonk::<_>::rand(ChaCha20Rng::from_seed([1; 32]))];
+    let other = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &other_circuits,
+        ProverSHPLONK<_>,
+        VerifierSHPLONK<_>,
+        AccumulatorStrategy<_>,
+        Blake2bWrite<_, _, _>,
+        Blake2bRead<_, _, _>,
+        Challenge255<_>
+    );
+    assert_ne!(snark.statements, other.statements);
+    // Valid encodings with statements swapped, which are only rejected by the
+    // pairing check
+    let invalid = [
+        Snark::new(
+            snark.protocol.clone(),
+            other.statements.clone(),
+            snark.proof.clone(),
+        ),
+        Snark::new(
+            other.protocol.clone(),
+            snark.statements.clone(),
+            other.proof.clone(),
+        ),
+    ];
+
+    let verify = |snarks: &[Snark<G1>]| {
+        batch_verify::<Bn256, _, ShplonkAccumulationScheme, _, _, _, LIMBS, BITS>(
+            params.get_g()[0],
+            params.g2(),
+            params.s_g2(),
+            snarks,
+            |proof| Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof),
+            ChaCha20Rng::from_seed(Default::default()),
+        )
+    };
+
+    assert!(verify(&[snark.clone(), other.clone(), snark.clone()]).is_ok());
+    assert!(matches!(
+        verify(&[snark.clone(), snark.clone(), invalid[0].clone(), other.clone()]),
+        Err(Error::InvalidProofs(indices)) if indices == vec![2]
+    ));
+    assert!(matches!(
+        verify(&[
+            invalid[0].clone(),
+            snark.clone(),
+            other.clone(),
+            snark,
+            invalid[1].clone(),
+            other,
+        ]),
+        Err(Error::InvalidProofs(indices)) if indices == vec![0, 4]
+    ));
+}
+
+#[test]
+fn test_kzg_shplonk_accumulator_checkpoint() {
+    let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(false, 1),
+        StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let snark = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverSHPLONK<_>,
+        VerifierSHPLONK<_>,
+        AccumulatorStrategy<_>,
+        Blake2bWrite<_, _, _>,
+        Blake2bRead<_, _, _>,
+        Challenge255<_>
+    );
+    let g1 = params.get_g()[0];
+
+    let checkpoint = {
+        let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
+        let mut transcript = Blake2bRead::<_, G1Affine, _>::init(snark.proof.as_slice());
+        halo2_kzg_native_accumulate!(
+            &snark.protocol,
+            snark.statements.clone(),
+            ShplonkAccumulationScheme,
+            &mut transcript,
+            &mut strategy
+        );
+        let checkpoint = strategy.checkpoint(g1.to_curve(), &mut transcript).unwrap();
+
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        let restored = AccumulatorCheckpoint::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(checkpoint, restored);
+        assert!(restored.decide::<Bn256>(params.g2(), params.s_g2()));
+        restored
+    };
+
+    let resume_and_accumulate = |checkpoint: AccumulatorCheckpoint<G1>| {
+        let mut strategy = SameCurveAccumulation::<_, _, LIMBS, BITS>::resume(checkpoint);
+        halo2_kzg_native_accumulate!(
+            &snark.protocol,
+            snark.statements.clone(),
+            ShplonkAccumulationScheme,
+            &mut Blake2bRead::<_, G1Affine, _>::init(snark.proof.as_slice()),
+            &mut strategy
+        );
+        strategy
+    };
+    let strategy = resume_and_accumulate(checkpoint.clone());
+    let (lhs, _) = strategy
+        .accumulator
+        .clone()
+        .unwrap()
+        .evaluate(g1.to_curve())
+        .unwrap();
+    assert!(strategy
+        .decide::<Bn256>(g1, params.g2(), params.s_g2())
+        .unwrap());
+
+    // Binding of checkpoint, which is squeezed after absorbing the checkpointed
+    // points, is seeded on resume, so the combining challenge changes with it
+    assert_eq!(
+        SameCurveAccumulation::<G1, NativeLoader, LIMBS, BITS>::resume(checkpoint.clone())
+            .binding(),
+        Some(&checkpoint.binding)
+    );
+    let (rebound_lhs, _) = resume_and_accumulate(AccumulatorCheckpoint {
+        binding: checkpoint.binding.double(),
+        ..checkpoint.clone()
+    })
+    .finalize(g1.to_curve())
+    .unwrap();
+    assert_ne!(rebound_lhs, lhs);
+
+    assert!(matches!(
+        SameCurveAccumulation::<G1, NativeLoader, LIMBS, BITS>::default().checkpoint(
+            g1.to_curve(),
+            &mut Blake2bRead::<_, G1Affine, _>::init(snark.proof.as_slice())
+        ),
+        Err(Error::MissingAccumulator)
+    ));
+
+    // Protocol binding is persisted and carried over when resuming
+    let bound = AccumulatorCheckpoint {
+        bind_protocols: true,
+        ..checkpoint
+    };
+    let mut bytes = Vec::new();
+    bound.write(&mut bytes).unwrap();
+    assert_eq!(
+        AccumulatorCheckpoint::<G1>::read(&mut bytes.as_slice()).unwrap(),
+        bound
+    );
+    let resumed = SameCurveAccumulation::<G1, NativeLoader, LIMBS, BITS>::resume(bound);
+    assert!(resumed.bind_protocols());
+    *bytes.last_mut().unwrap() = 2;
+    assert!(AccumulatorCheckpoint::<G1>::read(&mut bytes.as_slice()).is_err());
+}
+
+#[test]
+fn test_kzg_shplonk_cycle_curve_accumulation() {
+    let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(false, 1),
+        StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let snark = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverSHPLONK<_>,
+        VerifierSHPLONK<_>,
+        AccumulatorStrategy<_>,
+        Blake2bWrite<_, _, _>,
+        Blake2bRead<_, _, _>,
+        Challenge255<_>
+    );
+    let g1 = params.get_g()[0];
+
+    let mut same_curve = SameCurveAccumulation::<_, _, LIMBS, BITS>::default();
+    let mut cycle_curve = CycleCurveAccumulation::default();
+    for _ in 0..2 {
+        halo2_kzg_native_accumulate!(
+            &snark.protocol,
+            snark.statements.clone(),
+            ShplonkAccumulationScheme,
+            &mut Blake2bRead::<_, G1Affine, _>::init(snark.proof.as_slice()),
+            &mut same_curve
+        );
+        halo2_kzg_native_accumulate!(
+            &snark.protocol,
+            snark.statements.clone(),
+            ShplonkAccumulationScheme,
+            &mut Blake2bRead::<_, G1Affine, _>::init(snark.proof.as_slice()),
+            &mut cycle_curve
+        );
+    }
+
+    // Combined the same way, while pairs of the msm are deferred instead of
+    // being encoded in limbs
+    let accumulator = cycle_curve.finalize(g1.to_curve()).unwrap();
+    assert_eq!(
+        (accumulator.lhs, accumulator.rhs),
+        same_curve.finalize(g1.to_curve()).unwrap()
+    );
+    assert!(accumulator.decide::<Bn256>(params.g2(), params.s_g2()));
+
+    // Deferred pairs recompute the accumulator
+    assert!(accumulator.carried.is_empty());
+    let recomputed = accumulator
+        .deferred
+        .iter()
+        .map(|pairs| {
+            let (scalars, bases) = pairs.iter().cloned().unzip::<_, _, Vec<_>, Vec<_>>();
+            multi_scalar_multiplication(&scalars, &bases)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(recomputed, vec![accumulator.lhs, accumulator.rhs]);
+
+    let [lhs, rhs] = [accumulator.lhs, accumulator.rhs]
+        .map(|ec_point| ec_point.to_affine().coordinates().unwrap());
+    assert_eq!(
+        accumulator.base_instances()[..4],
+        [*lhs.x(), *lhs.y(), *rhs.x(), *rhs.y()]
+    );
+
+    let invalid = CycleAccumulator {
+        lhs: accumulator.rhs,
+        ..accumulator
+    };
+    assert!(!invalid.decide::<Bn256>(params.g2(), params.s_g2()));
+
+    assert!(matches!(
+        CycleCurveAccumulation::<G1, NativeLoader>::default().finalize(g1.to_curve()),
+        Err(Error::MissingAccumulator)
+    ));
+}
+
+#[test]
+fn test_cycle_accumulator_over_pasta() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [s_0, s_1, s_2, s_3] = [(); 4].map(|_| pallas::Scalar::random(&mut rng));
+    let [b_0, b_1] = [(); 2].map(|_| pallas::Point::random(&mut rng));
+    let g1 = pallas::Point::generator();
+    // Accumulator of vesta embedded in statements of a proof on pallas has
+    // coordinates native in scalar field of pallas
+    let carried = vesta::Point::random(&mut rng)
+        .to_affine()
+        .coordinates()
+        .unwrap();
+    let carried = [*carried.x(), *carried.y()];
+
+    let accumulator = CycleCurveAccumulation::<pallas::Point, NativeLoader> {
+        accumulator: Some(Accumulator::new(
+            MSM::scalar(s_0) + MSM::base(b_0) * &s_1,
+            MSM::base(b_0) * &s_2 + MSM::base(b_1) * &s_3,
+        )),
+        carried_accumulators: vec![[carried[0], carried[1], carried[0], carried[1]]],
+    }
+    .finalize(g1)
+    .unwrap();
+    assert_eq!(accumulator.lhs, g1 * s_0 + b_0 * s_1);
+    assert_eq!(accumulator.rhs, b_0 * s_2 + b_1 * s_3);
+
+    // Circuit on vesta recomputes the accumulator with native points of
+    // pallas and deferred scalars in limbs
+    let base_instances: Vec<vesta::Scalar> = accumulator.base_instances();
+    let ec_point = |xy: &[vesta::Scalar]| pallas::Affine::from_xy(xy[0], xy[1]).unwrap().to_curve();
+    let (lhs_rhs, pairs) = base_instances.split_at(4);
+    assert_eq!(
+        [ec_point(&lhs_rhs[..2]), ec_point(&lhs_rhs[2..])],
+        [accumulator.lhs, accumulator.rhs]
+    );
+    let pairs = pairs
+        .chunks(4)
+        .map(|pair| {
+            (
+                fe_from_limbs::<_, pallas::Scalar, 2, 128>([pair[2], pair[3]]),
+                ec_point(&pair[..2]),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(pairs.len(), 4);
+    let mut pairs_iter = pairs.iter().cloned();
+    let recomputed = accumulator
+        .deferred
+        .iter()
+        .map(|term| {
+            let (scalars, bases) = pairs_iter
+                .by_ref()
+                .take(term.len())
+                .unzip::<_, _, Vec<_>, Vec<_>>();
+            multi_scalar_multiplication(&scalars, &bases)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(recomputed, [accumulator.lhs, accumulator.rhs]);
+
+    // Circuit on pallas computes the same scalars natively, followed by the
+    // carried accumulator of vesta
+    assert_eq!(
+        accumulator.scalar_instances(),
+        pairs
+            .iter()
+            .map(|(scalar, _)| *scalar)
+            .chain([carried[0], carried[1], carried[0], carried[1]])
+            .collect::<Vec<_>>()
+    );
+    assert!(bool::from(
+        vesta::Affine::from_xy(carried[0], carried[1]).is_some()
+    ));
+}
+
+#[test]
+fn test_cycle_accumulators() {
+    let [mut protocol, _] = standard_plonk_protocols();
+    let statements = vec![(0..6u64).map(Fr::from).collect::<Vec<_>>()];
+
+    protocol.accumulator_indices = Some(vec![(1..5).map(|idx| (0, idx)).collect()]);
+    assert_eq!(
+        CycleCurveAccumulation::<G1, NativeLoader>::cycle_accumulators(&protocol, &statements)
+            .unwrap(),
+        vec![[1, 2, 3, 4u64].map(Fr::from)]
+    );
+
+    // Carried forward in order of accumulation instead of being discarded
+    let mut strategy = CycleCurveAccumulation::<G1, NativeLoader>::default();
+    for _ in 0..2 {
+        let accumulator = AccumulationStrategy::<_, _, _, ()>::extract_accumulator(
+            &mut strategy,
+            &protocol,
+            &NativeLoader,
+            &mut Blake2bRead::<_, G1Affine, Challenge255<_>>::init([0u8; 0].as_slice()),
+            &statements,
+        )
+        .unwrap();
+        assert!(accumulator.is_none());
+    }
+    assert_eq!(
+        strategy.carried_accumulators,
+        vec![[1, 2, 3, 4u64].map(Fr::from); 2]
+    );
+
+    // Coordinates of the other curve are in a single statement each
+    for accumulator_indices in [
+        vec![(1..6).map(|idx| (0, idx)).collect()],
+        vec![(3..7).map(|idx| (0, idx)).collect()],
+    ] {
+        protocol.accumulator_indices = Some(accumulator_indices);
+        assert!(matches!(
+            CycleCurveAccumulation::<G1, NativeLoader>::cycle_accumulators(&protocol, &statements),
+            Err(Error::InvalidInstances)
+        ));
+    }
+}
+
+#[test]
+fn test_accumulator_encoding() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let (lhs, rhs) = (G1::random(&mut rng), G1::random(&mut rng));
+    let [lhs_coordinates, rhs_coordinates] =
+        [lhs, rhs].map(|ec_point| ec_point.to_affine().coordinates().unwrap());
+
+    let limbs = [lhs_coordinates, rhs_coordinates]
+        .iter()
+        .flat_map(|coordinates| [*coordinates.x(), *coordinates.y()])
+        .flat_map(fe_to_limbs::<_, Fr, LIMBS, BITS>)
+        .collect::<Vec<_>>();
+    let decoded = AccumulatorEncoding::<G1, _>::decode(
+        &LimbsEncoding::<LIMBS, BITS>,
+        &NativeLoader,
+        0,
+        &limbs,
+    )
+    .unwrap();
+    assert_eq!(decoded, (lhs, rhs));
+
+    let compressed = [lhs_coordinates, rhs_coordinates]
+        .iter()
+        .flat_map(|coordinates| {
+            fe_to_limbs::<_, Fr, LIMBS, BITS>(*coordinates.x())
+                .into_iter()
+                .chain([Fr::from(bool::from(coordinates.y().is_odd()) as u64)])
+        })
+        .collect::<Vec<_>>();
+    let decoded = AccumulatorEncoding::<G1, _>::decode(
+        &CompressedEncoding::<LIMBS, BITS>,
+        &NativeLoader,
+        0,
+        &compressed,
+    )
+    .unwrap();
+    assert_eq!(decoded, (lhs, rhs));
+
+    let mut invalid_sign = compressed.clone();
+    invalid_sign[LIMBS] = Fr::from(2);
+    assert!(matches!(
+        AccumulatorEncoding::<G1, _>::decode(
+            &CompressedEncoding::<LIMBS, BITS>,
+            &NativeLoader,
+            0,
+            &invalid_sign,
+        ),
+        Err(Error::AccumulatorLimbOutOfRange)
+    ));
+
+    let hash = |lhs: &G1, rhs: &G1| -> Result<Fr, Error> {
+        Ok([lhs, rhs]
+            .iter()
+            .map(|ec_point| {
+                fe_to_limbs::<_, Fr, LIMBS, BITS>(*ec_point.to_affine().coordinates().unwrap().x())
+                    [0]
+            })
+            .sum())
+    };
+    let encoding = HashCommittedEncoding::new(vec![(lhs, rhs)], hash);
+    let decoded = encoding
+        .decode(&NativeLoader, 0, &[hash(&lhs, &rhs).unwrap()])
+        .unwrap();
+    assert_eq!(decoded, (lhs, rhs));
+    assert!(matches!(
+        encoding.decode(&NativeLoader, 0, &[Fr::zero()]),
+        Err(Error::InvalidInstances)
+    ));
+}
+
+#[test]
+fn test_accumulator_encoding_validation() {
+    let decode_limbs = |x: [Fr; LIMBS], y: [Fr; LIMBS]| {
+        let valid = G1::generator().to_affine();
+        let statements = [x, y]
+            .into_iter()
+            .flatten()
+            .chain(fe_to_limbs::<_, Fr, LIMBS, BITS>(valid.x))
+            .chain(fe_to_limbs::<_, Fr, LIMBS, BITS>(valid.y))
+            .collect::<Vec<_>>();
+        AccumulatorEncoding::<G1, _>::decode(
+            &LimbsEncoding::<LIMBS, BITS>,
+            &NativeLoader,
+            0,
+            &statements,
+        )
+    };
+    let decode_compressed = |x: [Fr; LIMBS], sign: Fr| {
+        let valid = G1::generator().to_affine();
+        let statements = x
+            .into_iter()
+            .chain([sign])
+            .chain(fe_to_limbs::<_, Fr, LIMBS, BITS>(valid.x))
+            .chain([Fr::from(bool::from(valid.y.is_odd()) as u64)])
+            .collect::<Vec<_>>();
+        AccumulatorEncoding::<G1, _>::decode(
+            &CompressedEncoding::<LIMBS, BITS>,
+            &NativeLoader,
+            0,
+            &statements,
+        )
+    };
+
+    let ec_point = G1::random(ChaCha20Rng::from_seed(Default::default())).to_affine();
+    let x = fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.x);
+    let y = fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.y);
+    assert!(decode_limbs(x, y).is_ok());
+
+    // Same value with the carry of the first limb moved into itself
+    let mut overflowed = x;
+    overflowed[0] += Fr::from(2).pow_vartime(&[BITS as u64]);
+    overflowed[1] -= Fr::one();
+    assert!(matches!(
+        decode_limbs(overflowed, y),
+        Err(Error::AccumulatorLimbOutOfRange)
+    ));
+
+    // Limbs of base field modulus
+    let mut modulus = fe_to_limbs::<_, Fr, LIMBS, BITS>(-Fq::one());
+    modulus[0] += Fr::one();
+    assert!(matches!(
+        decode_limbs(modulus, y),
+        Err(Error::AccumulatorCoordinateOutOfRange)
+    ));
+    assert!(matches!(
+        decode_compressed(modulus, Fr::zero()),
+        Err(Error::AccumulatorCoordinateOutOfRange)
+    ));
+
+    let off_curve_y = fe_to_limbs::<_, Fr, LIMBS, BITS>(ec_point.y + Fq::one());
+    assert!(matches!(
+        decode_limbs(x, off_curve_y),
+        Err(Error::AccumulatorNotOnCurve)
+    ));
+
+    let zero = [Fr::zero(); LIMBS];
+    assert!(matches!(
+        decode_limbs(zero, zero),
+        Err(Error::AccumulatorIsIdentity)
+    ));
+
+    assert!(matches!(
+        decode_compressed(x, Fr::from(2)),
+        Err(Error::AccumulatorLimbOutOfRange)
+    ));
+
+    let x_without_y = (1u64..)
+        .map(Fq::from)
+        .find(|x| bool::from((x.square() * x + G1Affine::b()).sqrt().is_none()))
+        .unwrap();
+    assert!(matches!(
+        decode_compressed(fe_to_limbs::<_, Fr, LIMBS, BITS>(x_without_y), Fr::zero()),
+        Err(Error::AccumulatorNotOnCurve)
+    ));
+
+    let encoding = HashCommittedEncoding::new(
+        vec![(G1::identity(), G1::generator())],
+        |_: &G1, _: &G1| -> Result<Fr, Error> { Ok(Fr::zero()) },
+    );
+    assert!(matches!(
+        encoding.decode(&NativeLoader, 0, &[Fr::zero()]),
+        Err(Error::AccumulatorIsIdentity)
+    ));
+}
+
+#[test]
+fn test_kzg_shplonk_protocol_binding() {
+    let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(false, 1),
+        StandardPlonk::<_>::rand(ChaCha20Rng::from_seed(Default::default()))
+    );
+    let standard_plonk = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverSHPLONK<_>,
+        VerifierSHPLONK<_>,
+        AccumulatorStrategy<_>,
+        Blake2bWrite<_, _, _>,
+        Blake2bRead<_, _, _>,
+        Challenge255<_>
+    );
+    let (params, pk, protocol, circuits) = halo2_kzg_prepare!(
+        9,
+        halo2_kzg_config!(false, 1),
+        Plookuper::<_, 2, 5, false>::rand(ChaCha20Rng::from_seed(Default::default()), 1 << 9)
+    );
+    let plookup = halo2_kzg_create_snark!(
+        &params,
+        &pk,
+        &protocol,
+        &circuits,
+        ProverSHPLONK<_>,
+        VerifierSHPLONK<_>,
+        AccumulatorStrategy<_>,
+        Blake2bWrite<_, _, _>,
+        Blake2bRead<_, _, _>,
+        Challenge255<_>
+    );
+    assert_ne!(standard_plonk.protocol.digest(), plookup.protocol.digest());
+    let g1 = params.get_g()[0];
+
+    let accumulate = |bind_protocols: bool| {
+        let mut strategy = if bind_protocols {
+            SameCurveAccumulation::<_, _, LIMBS, BITS>::default().with_protocol_binding()
+        } else {
+            SameCurveAccumulation::<_, _, LIMBS, BITS>::default()
+        };
+        for snark in [&standard_plonk, &plookup] {
+            halo2_kzg_native_accumulate!(
+                &snark.protocol,
+                snark.statements.clone(),
+                ShplonkAccumulationScheme,
+                &mut Blake2bRead::<_, G1Affine, _>::init(snark.proof.as_slice()),
+                &mut strategy
+            );
+        }
+        strategy
+    };
+    let bound = accumulate(true);
+    let unbound = accumulate(false);
+    assert_ne!(
+        bound
+            .accumulator
+            .clone()
+            .unwrap()
+            .evaluate(g1.to_curve())
+            .unwrap(),
+        unbound
+            .accumulator
+            .clone()
+            .unwrap()
+            .evaluate(g1.to_curve())
+            .unwrap()
+    );
+    assert!(bound
+        .decide::<Bn256>(g1, params.g2(), params.s_g2())
+        .unwrap());
+    assert!(unbound
+        .decide::<Bn256>(g1, params.g2(), params.s_g2())
+        .unwrap());
+}
+
+#[test]
+fn test_protocol_digest() {
+    let [protocol, other_protocol] = standard_plonk_protocols();
+    assert_eq!(protocol.digest(), protocol.clone().digest());
+    assert_ne!(protocol.digest(), other_protocol.digest());
+
+    let mut with_accumulator_indices = protocol.clone();
+    with_accumulator_indices.accumulator_indices = Some(vec![vec![(0, 0)]]);
+    assert_ne!(protocol.digest(), with_accumulator_indices.digest());
+    let mut with_empty_accumulator_indices = protocol.clone();
+    with_empty_accumulator_indices.accumulator_indices = Some(Vec::new());
+    assert_ne!(protocol.digest(), with_empty_accumulator_indices.digest());
+
+    let mut with_swapped_queries = protocol.clone();
+    with_swapped_queries.queries.swap(0, 1);
+    assert_ne!(protocol.digest(), with_swapped_queries.digest());
+    let mut without_query = protocol.clone();
+    without_query.queries.pop();
+    assert_ne!(protocol.digest(), without_query.digest());
+}
+
+#[test]
+fn test_decide_with_multiple_g2s() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [a, b, s_0, s_1, s_2] = [(); 5].map(|_| Fr::random(&mut rng));
+    // Pairing product is one when a * s_0 + b * s_1 + c * s_2 = 0
+    let c = -(a * s_0 + b * s_1) * Field::invert(&s_2).unwrap();
+    let g1 = G1Affine::generator();
+    let g2s = [s_0, s_1, s_2].map(|s| (G2Affine::generator() * s).to_affine());
+
+    let decide = |scalars: [Fr; 3], g2s: &[G2Affine]| {
+        // Terms are pushed out of the order of G2 points, and the one of `a` is
+        // scalar of generator, to check they are sorted and evaluated properly
+        let accumulator = Accumulator::from_terms([
+            (MSM::base(g1 * scalars[2]), 2),
+            (MSM::scalar(scalars[0]), 0),
+            (MSM::base(g1 * scalars[1]), 1),
+        ]);
+        SameCurveAccumulation::<G1, NativeLoader, LIMBS, BITS>::with_accumulator(accumulator)
+            .decide_with_g2s::<Bn256>(g1, g2s)
+    };
+    assert!(decide([a, b, c], &g2s).unwrap());
+    assert!(!decide([a, b, c + Fr::one()], &g2s).unwrap());
+    assert!(!decide([b, a, c], &g2s).unwrap());
+
+    // Term paired with G2 point out of range or no accumulator fails instead
+    // of panicking
+    assert!(matches!(
+        decide([a, b, c], &g2s[..2]),
+        Err(Error::MissingG2(2))
+    ));
+    assert!(matches!(
+        SameCurveAccumulation::<G1, NativeLoader, LIMBS, BITS>::default()
+            .decide_with_g2s::<Bn256>(g1, &g2s),
+        Err(Error::MissingAccumulator)
+    ));
+
+    // Accumulator paired with more than 2 G2 points can't be finalized as a pair
+    let strategy = SameCurveAccumulation::<G1, NativeLoader, LIMBS, BITS>::with_accumulator(
+        Accumulator::from_terms([
+            (MSM::base(g1 * c), 2),
+            (MSM::scalar(a), 0),
+            (MSM::base(g1 * b), 1),
+        ]),
+    );
+    assert!(matches!(
+        strategy.checkpoint(
+            g1.to_curve(),
+            &mut Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&[] as &[u8])
+        ),
+        Err(Error::AccumulatorNotPair)
+    ));
+    assert!(matches!(
+        strategy.finalize(g1.to_curve()),
+        Err(Error::AccumulatorNotPair)
+    ));
+}
+
+#[test]
+fn test_msm_merge_equal_bases() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [a, b, c] = [(); 3].map(|_| Fr::random(&mut rng));
+    let base = G1::random(&mut rng);
+    // Same point in another projective representation
+    let same_base = base.double() - base;
+    let other_base = G1::random(&mut rng);
+
+    let msm = MSM::<G1, NativeLoader>::base(base) * &a
+        + MSM::base(other_base) * &b
+        + MSM::base(same_base) * &c;
+    assert_eq!(msm.bases(), [base, other_base]);
+    assert_eq!(msm.scalars(), [a + c, b]);
+    assert_eq!(
+        msm.evaluate(G1::generator()),
+        base * (a + c) + other_base * b
+    );
+}
+
+#[test]
+fn test_msm_scalar_only() {
+    let mut rng = ChaCha20Rng::from_seed(Default::default());
+    let [a, b] = [(); 2].map(|_| Fr::random(&mut rng));
+    let gen = G1::random(&mut rng);
+
+    // Msm with only the scalar of generator takes loader from the scalar
+    assert_eq!(MSM::<G1, NativeLoader>::scalar(a).evaluate(gen), gen * a);
+    assert_eq!(
+        (MSM::<G1, NativeLoader>::scalar(a) + MSM::scalar(b)).evaluate(gen),
+        gen * (a + b)
+    );
+}
@@ -7,8 +7,11 @@ mod accumulation;
 mod msm;
 
 pub use accumulation::{
-    plonk::PlonkAccumulationScheme, shplonk::ShplonkAccumulationScheme, AccumulationScheme,
-    AccumulationStrategy, Accumulator, SameCurveAccumulation,
+    encoding::{AccumulatorEncoding, CompressedEncoding, HashCommittedEncoding, LimbsEncoding},
+    plonk::PlonkAccumulationScheme,
+    shplonk::ShplonkAccumulationScheme,
+    AccumulationScheme, AccumulationStrategy, Accumulator, CycleCurveAccumulation,
+    SameCurveAccumulation,
 };
 pub use msm::MSM;
 
@@ -1,15 +1,18 @@
 use crate::{
-    loader::Loader,
+    loader::{EcPointLoader, Loader},
     protocol::Protocol,
     scheme::kzg::msm::MSM,
-    util::{Curve, Transcript},
+    util::{Curve, GroupEncoding, Transcript},
     Error,
 };
 use std::ops::{Add, AddAssign, Mul, MulAssign};
 
+pub mod encoding;
 pub mod plonk;
 pub mod shplonk;
 
+use encoding::{AccumulatorEncoding, LimbsEncoding};
+
 pub trait AccumulationScheme<C, L, T, S>
 where
     C: Curve,
@@ -37,17 +40,18 @@ where
     type Output;
 
     fn extract_accumulator(
-        &self,
+        &mut self,
         _: &Protocol<C>,
         _: &L,
         _: &mut T,
         _: &[Vec<L::LoadedScalar>],
-    ) -> Option<Accumulator<C, L>> {
-        None
+    ) -> Result<Option<Accumulator<C, L>>, Error> {
+        Ok(None)
     }
 
     fn process(
         &mut self,
+        protocol: &Protocol<C>,
         loader: &L,
         transcript: &mut T,
         proof: P,
@@ -55,15 +59,17 @@ where
     ) -> Result<Self::Output, Error>;
 }
 
+/// Terms of G1 msm to be checked with pairing, each paired with the index of
+/// G2 point it should be paired with. The ones created by [`Accumulator::new`]
+/// have `lhs` paired with G2 point `0` (`g2`) and `rhs` paired with G2 point
+/// `1` (`-s_g2`), which is the case of single trusted setup.
 #[derive(Clone, Debug)]
 pub struct Accumulator<C, L>
 where
     C: Curve,
     L: Loader<C>,
 {
-    lhs: MSM<C, L>,
-    /// chao: plonk paper has two pairings, accumulate these two pairings.
-    rhs: MSM<C, L>,
+    terms: Vec<(MSM<C, L>, usize)>,
 }
 
 impl<C, L> Accumulator<C, L>
@@ -72,21 +78,69 @@ where
     L: Loader<C>,
 {
     pub fn new(lhs: MSM<C, L>, rhs: MSM<C, L>) -> Self {
-        Self { lhs, rhs }
+        Self::from_terms([(lhs, 0), (rhs, 1)])
+    }
+
+    pub fn from_terms(terms: impl IntoIterator<Item = (MSM<C, L>, usize)>) -> Self {
+        let mut accumulator = Self::default();
+        for term in terms {
+            accumulator.push(term);
+        }
+        accumulator
+    }
+
+    pub fn terms(&self) -> &[(MSM<C, L>, usize)] {
+        &self.terms
+    }
+
+    fn push(&mut self, (msm, g2_index): (MSM<C, L>, usize)) {
+        if let Some(pos) = self.terms.iter().position(|(_, idx)| *idx == g2_index) {
+            self.terms[pos].0 += msm;
+        } else {
+            self.terms.push((msm, g2_index));
+        }
     }
 
     pub fn scale(&mut self, scalar: &L::LoadedScalar) {
-        self.lhs *= scalar;
-        self.rhs *= scalar;
+        for (msm, _) in self.terms.iter_mut() {
+            *msm *= scalar;
+        }
     }
 
     pub fn extend(&mut self, other: Self) {
-        self.lhs += other.lhs;
-        self.rhs += other.rhs;
+        for term in other.terms {
+            self.push(term);
+        }
+    }
+
+    /// Evaluate terms paired with `g2` and `-s_g2` as `(lhs, rhs)`, which
+    /// fails with [`Error::AccumulatorNotPair`] when paired with other G2
+    /// points, then [`Accumulator::evaluate_terms`] should be used instead.
+    pub fn evaluate(self, g1: C) -> Result<(L::LoadedEcPoint, L::LoadedEcPoint), Error> {
+        if self.terms.len() != 2 || self.terms.iter().any(|(_, g2_index)| *g2_index > 1) {
+            return Err(Error::AccumulatorNotPair);
+        }
+        let [(lhs, _), (rhs, _)]: [_; 2] = self.evaluate_terms(g1).try_into().unwrap();
+        Ok((lhs, rhs))
+    }
+
+    /// Fails with [`Error::MissingG2`] if any term is paired with G2 point of
+    /// index not less than `num_g2s`.
+    pub fn check_g2_indices(&self, num_g2s: usize) -> Result<(), Error> {
+        match self.terms.iter().find(|(_, g2_index)| *g2_index >= num_g2s) {
+            Some((_, g2_index)) => Err(Error::MissingG2(*g2_index)),
+            None => Ok(()),
+        }
     }
 
-    pub fn evaluate(self, g1: C) -> (L::LoadedEcPoint, L::LoadedEcPoint) {
-        (self.lhs.evaluate(g1), self.rhs.evaluate(g1))
+    /// Evaluate each term, sorted by the index of G2 point.
+    pub fn evaluate_terms(self, g1: C) -> Vec<(L::LoadedEcPoint, usize)> {
+        let mut terms = self.terms;
+        terms.sort_by_key(|(_, g2_index)| *g2_index);
+        terms
+            .into_iter()
+            .map(|(msm, g2_index)| (msm.evaluate(g1), g2_index))
+            .collect()
     }
 
     pub fn random_linear_combine(
@@ -106,10 +160,7 @@ where
     L: Loader<C>,
 {
     fn default() -> Self {
-        Self {
-            lhs: MSM::default(),
-            rhs: MSM::default(),
-        }
+        Self { terms: Vec::new() }
     }
 }
 
@@ -161,12 +212,279 @@ where
 
 pub struct SameCurveAccumulation<C: Curve, L: Loader<C>, const LIMBS: usize, const BITS: usize> {
     pub accumulator: Option<Accumulator<C, L>>,
+    /// Absorbed into transcript before squeezing the next combining challenge,
+    /// which is set when binding protocols or resuming from a checkpoint.
+    binding: Option<L::LoadedScalar>,
+    /// Encoding of accumulators in statements, which is [`LimbsEncoding`] by
+    /// default.
+    encoding: Box<dyn AccumulatorEncoding<C, L>>,
+    /// Number of accumulators decoded from statements of proofs processed so
+    /// far, so each accumulator is passed to `encoding` with an index running
+    /// across proofs.
+    num_decoded: usize,
+    /// Whether to bind protocols and proofs, which is only enabled by
+    /// [`SameCurveAccumulation::with_protocol_binding`].
+    bind_protocols: bool,
 }
 
-impl<C: Curve, L: Loader<C>, const LIMBS: usize, const BITS: usize> Default
+impl<C, L, const LIMBS: usize, const BITS: usize> Default
     for SameCurveAccumulation<C, L, LIMBS, BITS>
+where
+    C: Curve,
+    L: Loader<C>,
+    LimbsEncoding<LIMBS, BITS>: AccumulatorEncoding<C, L>,
 {
     fn default() -> Self {
-        Self { accumulator: None }
+        Self::with_encoding(LimbsEncoding::<LIMBS, BITS>)
+    }
+}
+
+impl<C, L, const LIMBS: usize, const BITS: usize> SameCurveAccumulation<C, L, LIMBS, BITS>
+where
+    C: Curve,
+    L: Loader<C>,
+    LimbsEncoding<LIMBS, BITS>: AccumulatorEncoding<C, L>,
+{
+    pub fn with_accumulator(accumulator: Accumulator<C, L>) -> Self {
+        Self {
+            accumulator: Some(accumulator),
+            ..Default::default()
+        }
+    }
+}
+
+impl<C: Curve, L: Loader<C>, const LIMBS: usize, const BITS: usize>
+    SameCurveAccumulation<C, L, LIMBS, BITS>
+{
+    pub fn with_encoding(encoding: impl AccumulatorEncoding<C, L> + 'static) -> Self {
+        Self {
+            accumulator: None,
+            binding: None,
+            encoding: Box::new(encoding),
+            num_decoded: 0,
+            bind_protocols: false,
+        }
+    }
+
+    /// Enable binding accumulated proofs together for proofs of different
+    /// protocols. Each proof's transcript absorbs the digest of its protocol
+    /// before squeezing the combining challenge, then a binding is squeezed
+    /// and absorbed by the next proof's transcript, so the chain of bindings
+    /// acts as a top-level transcript over all protocols and proofs.
+    pub fn with_protocol_binding(mut self) -> Self {
+        self.bind_protocols = true;
+        self
+    }
+
+    /// Seed binding to be absorbed before combining the next proof, which is
+    /// the one of a checkpoint when resuming from it.
+    pub(crate) fn with_binding(mut self, binding: L::LoadedScalar) -> Self {
+        self.binding = Some(binding);
+        self
+    }
+
+    /// Binding to be absorbed before squeezing the next combining challenge,
+    /// see [`SameCurveAccumulation::with_protocol_binding`].
+    pub fn binding(&self) -> Option<&L::LoadedScalar> {
+        self.binding.as_ref()
+    }
+
+    /// Whether protocols and proofs are bound, see
+    /// [`SameCurveAccumulation::with_protocol_binding`].
+    pub fn bind_protocols(&self) -> bool {
+        self.bind_protocols
+    }
+
+    /// Number of accumulators decoded from statements so far.
+    pub fn num_decoded(&self) -> usize {
+        self.num_decoded
+    }
+
+    /// Combine `accumulator` of a proof of `protocol` with the current one by
+    /// a challenge squeezed from the proof's `transcript`.
+    pub(crate) fn combine<T: Transcript<C, L>>(
+        &mut self,
+        protocol: &Protocol<C>,
+        loader: &L,
+        transcript: &mut T,
+        accumulator: Accumulator<C, L>,
+    ) -> Result<(), Error>
+    where
+        C: GroupEncoding,
+    {
+        if self.bind_protocols {
+            transcript.common_scalar(&loader.load_const(&protocol.digest()))?;
+        }
+
+        self.accumulator = Some(match self.accumulator.take() {
+            Some(curr_accumulator) => {
+                if let Some(binding) = self.binding.take() {
+                    transcript.common_scalar(&binding)?;
+                }
+                accumulator + curr_accumulator * &transcript.squeeze_challenge()
+            }
+            None => accumulator,
+        });
+
+        if self.bind_protocols {
+            self.binding = Some(transcript.squeeze_challenge());
+        }
+
+        self.num_decoded += protocol
+            .accumulator_indices
+            .as_ref()
+            .map(Vec::len)
+            .unwrap_or_default();
+
+        Ok(())
+    }
+
+    /// Decode accumulators at each of `protocol.accumulator_indices` and
+    /// combine them with challenges squeezed from `transcript`, which fails if
+    /// any accumulator is rejected by the encoding. Accumulators are passed to
+    /// the encoding with index offset by [`SameCurveAccumulation::num_decoded()`].
+    pub(crate) fn decode_accumulators<T: Transcript<C, L>>(
+        &self,
+        protocol: &Protocol<C>,
+        loader: &L,
+        transcript: &mut T,
+        statements: &[Vec<L::LoadedScalar>],
+    ) -> Result<Option<Accumulator<C, L>>, Error> {
+        let accumulator_indices = match protocol.accumulator_indices.as_ref() {
+            Some(accumulator_indices) => accumulator_indices,
+            None => return Ok(None),
+        };
+
+        let challenges = transcript.squeeze_n_challenges(accumulator_indices.len());
+        let accumulators = accumulator_indices
+            .iter()
+            .enumerate()
+            .map(|(idx, indices)| {
+                let statements = indices
+                    .iter()
+                    .map(|index| statements[index.0][index.1].clone())
+                    .collect::<Vec<_>>();
+                let idx = self.num_decoded + idx;
+                let (lhs, rhs) = self.encoding.decode(loader, idx, &statements)?;
+                Ok(Accumulator::new(MSM::base(lhs), MSM::base(rhs)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Some(Accumulator::random_linear_combine(
+            challenges.into_iter().zip(accumulators),
+        )))
+    }
+}
+
+/// Accumulation over a 2-cycle of curves `(C, C')` with `C::Base` being the
+/// scalar field of `C'` and vice versa, e.g. BN254/Grumpkin or Pasta.
+///
+/// Verifying a proof on `C` takes elliptic curve operations over `C::Base`
+/// and scalar-field work over `C::Scalar`, which are native in circuits of
+/// `C'` and `C` respectively. So instead of encoding anything in `LIMBS`
+/// limbs, the accumulator msm is deferred: each pair of scalar and base of it
+/// is exported with the accumulator `(lhs, rhs)`, so the circuit of `C'` can
+/// recompute `(lhs, rhs)` with native points, while the circuit on `C`
+/// computes the same scalars natively. Likewise
+/// accumulators embedded in statements of a proof on `C` are of `C'`, so they
+/// are not decoded but carried forward to be accumulated on `C'`, see
+/// [`CycleCurveAccumulation::cycle_accumulators`].
+///
+/// Only `NativeLoader` is supported, which computes both. `Halo2Loader<C>` is
+/// over `C::Scalar` with points of `C` being non-native, so it could only
+/// constrain the scalar-field work, leaving the msm unchecked by any circuit.
+pub struct CycleCurveAccumulation<C: Curve, L: Loader<C>> {
+    pub accumulator: Option<Accumulator<C, L>>,
+    /// Accumulators of `C'` embedded in statements of accumulated proofs, in
+    /// order of accumulation.
+    pub carried_accumulators: Vec<[L::LoadedScalar; 4]>,
+}
+
+impl<C: Curve, L: Loader<C>> Default for CycleCurveAccumulation<C, L> {
+    fn default() -> Self {
+        Self {
+            accumulator: None,
+            carried_accumulators: Vec::new(),
+        }
+    }
+}
+
+impl<C: Curve, L: Loader<C>> CycleCurveAccumulation<C, L> {
+    /// Returns accumulators of the other curve embedded in `statements` at
+    /// each of `protocol.accumulator_indices`, each as `lhs.x, lhs.y, rhs.x,
+    /// rhs.y` in a single statement since they are native in scalar field of
+    /// `C`. It fails if any of them is not in 4 statements.
+    pub fn cycle_accumulators(
+        protocol: &Protocol<C>,
+        statements: &[Vec<L::LoadedScalar>],
+    ) -> Result<Vec<[L::LoadedScalar; 4]>, Error> {
+        protocol
+            .accumulator_indices
+            .iter()
+            .flatten()
+            .map(|indices| {
+                indices
+                    .iter()
+                    .map(|index| {
+                        statements
+                            .get(index.0)
+                            .and_then(|statements| statements.get(index.1))
+                            .cloned()
+                            .ok_or(Error::InvalidInstances)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .try_into()
+                    .map_err(|_| Error::InvalidInstances)
+            })
+            .collect()
+    }
+
+    /// Pairs of scalar and base of the accumulator msm deferred to circuits of
+    /// both curves, grouped by terms sorted by the index of G2 point. Each
+    /// term starts with [`MSM::scalar`] paired with generator `g1` if any,
+    /// followed by pairs in order of [`MSM::bases`].
+    pub fn deferred_terms(
+        &self,
+        loader: &L,
+        g1: &C,
+    ) -> Result<Vec<Vec<(L::LoadedScalar, L::LoadedEcPoint)>>, Error> {
+        let mut terms = self
+            .accumulator
+            .as_ref()
+            .ok_or(Error::MissingAccumulator)?
+            .terms()
+            .iter()
+            .collect::<Vec<_>>();
+        terms.sort_by_key(|(_, g2_index)| *g2_index);
+        Ok(terms
+            .into_iter()
+            .map(|(msm, _)| {
+                msm.scalar
+                    .iter()
+                    .map(|scalar| (scalar.clone(), loader.ec_point_load_const(g1)))
+                    .chain(
+                        msm.scalars()
+                            .iter()
+                            .cloned()
+                            .zip(msm.bases().iter().cloned()),
+                    )
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Combine `accumulator` of a proof with the current one by a challenge
+    /// squeezed from the proof's `transcript`.
+    pub(crate) fn combine<T: Transcript<C, L>>(
+        &mut self,
+        transcript: &mut T,
+        accumulator: Accumulator<C, L>,
+    ) {
+        self.accumulator = Some(match self.accumulator.take() {
+            Some(curr_accumulator) => {
+                accumulator + curr_accumulator * &transcript.squeeze_challenge()
+            }
+            None => accumulator,
+        });
     }
 }
@@ -0,0 +1,57 @@
+use crate::{loader::Loader, util::Curve, Error};
+
+/// Encoding of accumulator `(lhs, rhs)` in statements of a proof, which is
+/// decoded by [`SameCurveAccumulation`](super::SameCurveAccumulation) from
+/// statements at each of `Protocol::accumulator_indices`.
+pub trait AccumulatorEncoding<C: Curve, L: Loader<C>> {
+    /// Decode the `idx`-th accumulator from `statements` at its indices, where
+    /// `idx` counts accumulators of all proofs accumulated by the strategy in
+    /// order.
+    fn decode(
+        &self,
+        loader: &L,
+        idx: usize,
+        statements: &[L::LoadedScalar],
+    ) -> Result<(L::LoadedEcPoint, L::LoadedEcPoint), Error>;
+}
+
+/// Accumulator as `lhs.x, lhs.y, rhs.x, rhs.y`, each in `LIMBS` limbs of
+/// `BITS` bits.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LimbsEncoding<const LIMBS: usize, const BITS: usize>;
+
+/// Accumulator as `lhs.x, lhs.sign, rhs.x, rhs.sign`, where `x` is in `LIMBS`
+/// limbs of `BITS` bits and `sign` is the parity of `y`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompressedEncoding<const LIMBS: usize, const BITS: usize>;
+
+/// Accumulator committed by a single statement as its `hash`, while the
+/// accumulator itself is passed as witness, which is loader specific:
+/// - `NativeLoader` takes points `(lhs, rhs)`.
+/// - `Rc<EvmLoader>` takes calldata offset of uncompressed `(lhs, rhs)`.
+/// - `Rc<Halo2Loader>` takes `Value` of affine points `(lhs, rhs)`.
+///
+/// The `hash` takes loaded `(lhs, rhs)` and should match the one used by the
+/// circuit committing to the accumulator.
+#[derive(Clone, Debug)]
+pub struct HashCommittedEncoding<W, H> {
+    witnesses: Vec<W>,
+    hash: H,
+}
+
+impl<W, H> HashCommittedEncoding<W, H> {
+    /// Create an encoding with witness of each accumulator in the order they
+    /// are accumulated, i.e. by proofs in order then by
+    /// `Protocol::accumulator_indices` of each proof.
+    pub fn new(witnesses: Vec<W>, hash: H) -> Self {
+        Self { witnesses, hash }
+    }
+
+    pub fn witness(&self, idx: usize) -> Result<&W, Error> {
+        self.witnesses.get(idx).ok_or(Error::InvalidInstances)
+    }
+
+    pub fn hash(&self) -> &H {
+        &self.hash
+    }
+}
@@ -35,10 +35,13 @@ where
     ) -> Result<S::Output, Error> {
         transcript.common_scalar(&loader.load_const(&protocol.transcript_initial_state))?;
 
+        loader.start_cost_metering("read_proof");
         let proof = PlonkProof::read(protocol, statements, transcript)?;
+        loader.end_cost_metering();
         let old_accumulator =
-            strategy.extract_accumulator(protocol, loader, transcript, &proof.statements);
+            strategy.extract_accumulator(protocol, loader, transcript, &proof.statements)?;
 
+        loader.start_cost_metering("common_poly_eval");
         let common_poly_eval = {
             let mut common_poly_eval = CommonPolynomialEvaluation::new(
                 &protocol.domain,
@@ -51,9 +54,12 @@ where
 
             common_poly_eval
         };
+        loader.end_cost_metering();
 
         let commitments = proof.commitments(protocol, loader, &common_poly_eval);
+        loader.start_cost_metering("quotient_eval");
         let evaluations = proof.evaluations(protocol, loader, &common_poly_eval)?;
+        loader.end_cost_metering();
 
         let sets = rotation_sets(protocol);
         let powers_of_u = &proof.u.powers(sets.len());
@@ -92,7 +98,7 @@ where
         if let Some(old_accumulator) = old_accumulator {
             accumulator += old_accumulator;
         }
-        strategy.process(loader, transcript, proof, accumulator)
+        strategy.process(protocol, loader, transcript, proof, accumulator)
     }
 }
 
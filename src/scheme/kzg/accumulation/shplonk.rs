@@ -38,10 +38,13 @@ where
     ) -> Result<S::Output, Error> {
         transcript.common_scalar(&loader.load_const(&protocol.transcript_initial_state))?;
 
+        loader.start_cost_metering("read_proof");
         let proof = ShplonkProof::read(protocol, statements, transcript)?;
+        loader.end_cost_metering();
         let old_accumulator =
-            strategy.extract_accumulator(protocol, loader, transcript, &proof.statements);
+            strategy.extract_accumulator(protocol, loader, transcript, &proof.statements)?;
 
+        loader.start_cost_metering("common_poly_eval");
         let (common_poly_eval, sets) = {
             let mut common_poly_eval = CommonPolynomialEvaluation::new(
                 &protocol.domain,
@@ -60,9 +63,12 @@ where
 
             (common_poly_eval, sets)
         };
+        loader.end_cost_metering();
 
         let commitments = proof.commitments(protocol, loader, &common_poly_eval);
+        loader.start_cost_metering("quotient_eval");
         let evaluations = proof.evaluations(protocol, loader, &common_poly_eval)?;
+        loader.end_cost_metering();
 
         let f = {
             let powers_of_mu = proof
@@ -85,7 +91,7 @@ where
         if let Some(old_accumulator) = old_accumulator {
             accumulator += old_accumulator;
         }
-        strategy.process(loader, transcript, proof, accumulator)
+        strategy.process(protocol, loader, transcript, proof, accumulator)
     }
 }
 
@@ -497,7 +503,7 @@ fn intermediate_sets<C: Curve, L: Loader<C>>(
             .unwrap()
             - 1)
         .next_power_of_two()
-        .log2() as usize
+        .ilog2() as usize
             + 1,
     );
     let powers_of_z = z.powers(size);
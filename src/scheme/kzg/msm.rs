@@ -1,8 +1,9 @@
 use crate::{
-    loader::{LoadedEcPoint, Loader},
-    util::Curve,
+    loader::{EcPointLoader, LoadedEcPoint, LoadedScalar, Loader, MsmFolding, ScalarLoader},
+    util::{multi_scalar_multiplication, Curve, Field, Group},
 };
 use std::{
+    collections::{hash_map::Entry, HashMap},
     default::Default,
     iter::{self, Sum},
     ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
@@ -13,6 +14,7 @@ pub struct MSM<C: Curve, L: Loader<C>> {
     pub scalar: Option<L::LoadedScalar>,
     bases: Vec<L::LoadedEcPoint>,
     scalars: Vec<L::LoadedScalar>,
+    indices: HashMap<<L::LoadedEcPoint as LoadedEcPoint<C>>::Key, usize>,
 }
 
 impl<C: Curve, L: Loader<C>> Default for MSM<C, L> {
@@ -21,6 +23,7 @@ impl<C: Curve, L: Loader<C>> Default for MSM<C, L> {
             scalar: None,
             scalars: Vec::new(),
             bases: Vec::new(),
+            indices: HashMap::new(),
         }
     }
 }
@@ -33,27 +36,70 @@ impl<C: Curve, L: Loader<C>> MSM<C, L> {
         }
     }
 
+    /// Returns scalars of the msm, each paired with base of the same index in
+    /// [`MSM::bases`], excluding `scalar` which is paired with the generator.
+    pub fn scalars(&self) -> &[L::LoadedScalar] {
+        &self.scalars
+    }
+
+    pub fn bases(&self) -> &[L::LoadedEcPoint] {
+        &self.bases
+    }
+
     pub fn base(base: L::LoadedEcPoint) -> Self {
         let one = base.loader().load_one();
-        MSM {
-            scalars: vec![one],
-            bases: vec![base],
-            ..Default::default()
-        }
+        let mut msm = MSM::default();
+        msm.push(one, base);
+        msm
     }
 
+    /// Evaluate the msm, where terms known at code generation time are folded
+    /// into a single constant point first, and the saved operations are
+    /// recorded to loader.
+    ///
+    /// Terms that vanish are skipped, so identity is loaded only when the whole
+    /// msm vanishes, which loaders that can't represent identity reject. When
+    /// all terms are known, the folded constant is loaded as the output
+    /// without another msm.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the msm has neither `scalar` nor any base to get loader from.
     pub fn evaluate(self, gen: C) -> L::LoadedEcPoint {
-        let gen = self
-            .bases
-            .first()
-            .unwrap()
-            .loader()
-            .ec_point_load_const(&gen);
-        L::LoadedEcPoint::multi_scalar_multiplication(
-            iter::empty()
-                .chain(self.scalar.map(|scalar| (scalar, gen)))
-                .chain(self.scalars.into_iter().zip(self.bases.into_iter())),
-        )
+        let loader = match (self.scalar.as_ref(), self.bases.first()) {
+            (Some(scalar), _) => scalar.loader().clone(),
+            (None, Some(base)) => base.loader().clone(),
+            (None, None) => panic!("Empty msm has no loader to evaluate"),
+        };
+        loader.start_cost_metering("msm");
+        let gen = loader.ec_point_load_const(&gen);
+
+        let pairs = iter::empty()
+            .chain(self.scalar.map(|scalar| (scalar, gen)))
+            .chain(self.scalars.into_iter().zip(self.bases.into_iter()))
+            .collect::<Vec<_>>();
+        let (constant, pairs, folding) = fold_constants(pairs);
+        if folding.scalar_muls + folding.additions > 0 {
+            loader.record_msm_folding(folding);
+        }
+        if pairs.is_empty() {
+            let output = loader.ec_point_load_const(&constant.unwrap_or_else(C::identity));
+            loader.end_cost_metering();
+            return output;
+        }
+
+        let pairs = iter::empty()
+            .chain(constant.map(|constant| {
+                (
+                    ScalarLoader::<C::Scalar>::load_one(&loader),
+                    loader.ec_point_load_const(&constant),
+                )
+            }))
+            .chain(pairs)
+            .collect::<Vec<_>>();
+        let output = L::LoadedEcPoint::multi_scalar_multiplication(pairs);
+        loader.end_cost_metering();
+        output
     }
 
     pub fn scale(&mut self, factor: &L::LoadedScalar) {
@@ -66,11 +112,19 @@ impl<C: Curve, L: Loader<C>> MSM<C, L> {
     }
 
     pub fn push(&mut self, scalar: L::LoadedScalar, base: L::LoadedEcPoint) {
-        if let Some(pos) = self.bases.iter().position(|exist| exist.eq(&base)) {
-            self.scalars[pos] += scalar;
-        } else {
-            self.scalars.push(scalar);
-            self.bases.push(base);
+        match base.key().map(|key| self.indices.entry(key)) {
+            Some(Entry::Occupied(entry)) => {
+                self.scalars[*entry.get()] += scalar;
+            }
+            Some(Entry::Vacant(entry)) => {
+                entry.insert(self.bases.len());
+                self.scalars.push(scalar);
+                self.bases.push(base);
+            }
+            None => {
+                self.scalars.push(scalar);
+                self.bases.push(base);
+            }
         }
     }
 
@@ -86,6 +140,74 @@ impl<C: Curve, L: Loader<C>> MSM<C, L> {
     }
 }
 
+/// Fold pairs with both scalar and base known at code generation time into a
+/// single constant point, which is `None` if there is nothing to fold or it
+/// turns out to be identity.
+///
+/// Pairs with zero scalar or identity base are skipped, since they contribute
+/// nothing and identity is not representable by some loaders (e.g. assigned
+/// affine point in halo2).
+fn fold_constants<C: Curve, L: Loader<C>>(
+    pairs: Vec<(L::LoadedScalar, L::LoadedEcPoint)>,
+) -> (
+    Option<C>,
+    Vec<(L::LoadedScalar, L::LoadedEcPoint)>,
+    MsmFolding,
+) {
+    let (skipped, pairs) = pairs.into_iter().partition::<Vec<_>, _>(|(scalar, base)| {
+        is_zero::<C, L>(scalar) || is_identity::<C, L>(base)
+    });
+    let skipped = MsmFolding {
+        scalar_muls: skipped
+            .iter()
+            .filter(|(scalar, _)| !is_one::<C, L>(scalar))
+            .count(),
+        additions: skipped.len(),
+    };
+
+    let (constants, pairs) = pairs.into_iter().partition::<Vec<_>, _>(|(scalar, base)| {
+        scalar.as_const().is_some() && base.as_const().is_some()
+    });
+    if constants.len() < 2 && constants.iter().all(|(scalar, _)| is_one::<C, L>(scalar)) {
+        return (
+            None,
+            iter::empty().chain(constants).chain(pairs).collect(),
+            skipped,
+        );
+    }
+
+    let (scalars, bases) = constants
+        .iter()
+        .map(|(scalar, base)| (scalar.as_const().unwrap(), base.as_const().unwrap()))
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+    let constant = Some(multi_scalar_multiplication(&scalars, &bases))
+        .filter(|constant| !bool::from(constant.is_identity()));
+
+    let mut folding = MsmFolding {
+        scalar_muls: constants
+            .iter()
+            .filter(|(scalar, _)| !is_one::<C, L>(scalar))
+            .count(),
+        additions: constants.len() - usize::from(constant.is_some()),
+    };
+    folding.merge(skipped);
+    (constant, pairs, folding)
+}
+
+fn is_zero<C: Curve, L: Loader<C>>(scalar: &L::LoadedScalar) -> bool {
+    scalar.as_const() == Some(C::Scalar::zero())
+}
+
+fn is_identity<C: Curve, L: Loader<C>>(base: &L::LoadedEcPoint) -> bool {
+    base.as_const()
+        .map(|base| bool::from(base.is_identity()))
+        .unwrap_or(false)
+}
+
+fn is_one<C: Curve, L: Loader<C>>(scalar: &L::LoadedScalar) -> bool {
+    scalar.as_const() == Some(C::Scalar::one())
+}
+
 impl<C: Curve, L: Loader<C>> Add<MSM<C, L>> for MSM<C, L> {
     type Output = MSM<C, L>;
 
@@ -1,3 +1,4 @@
+use crate::Error;
 use num_bigint::BigUint;
 use num_traits::One;
 use std::{
@@ -77,6 +78,59 @@ pub fn batch_invert<F: PrimeField>(values: &mut [F]) {
     batch_invert_and_mul(values, &F::one())
 }
 
+/// Bucketed (Pippenger) multi-scalar multiplication, assuming `F::Repr` in
+/// little-endian.
+pub fn multi_scalar_multiplication<C: Curve>(scalars: &[C::Scalar], bases: &[C]) -> C {
+    assert_eq!(scalars.len(), bases.len());
+
+    let window_size = msm_window_size(scalars.len());
+    let num_windows = (C::Scalar::NUM_BITS as usize).div_ceil(window_size);
+    let scalars = scalars
+        .iter()
+        .map(|scalar| scalar.to_repr())
+        .collect::<Vec<_>>();
+
+    let mut acc = C::identity();
+    for idx in (0..num_windows).rev() {
+        for _ in 0..window_size {
+            acc = acc.double();
+        }
+
+        let mut buckets = vec![C::identity(); (1 << window_size) - 1];
+        for (scalar, base) in scalars.iter().zip(bases.iter()) {
+            let digit = msm_window_digit(scalar.as_ref(), idx * window_size, window_size);
+            if digit != 0 {
+                buckets[digit - 1] += *base;
+            }
+        }
+
+        let mut running_sum = C::identity();
+        for bucket in buckets.into_iter().rev() {
+            running_sum += bucket;
+            acc += running_sum;
+        }
+    }
+    acc
+}
+
+fn msm_window_size(n: usize) -> usize {
+    if n < 4 {
+        1
+    } else if n < 32 {
+        3
+    } else {
+        (n as f64).ln().ceil() as usize
+    }
+}
+
+fn msm_window_digit(bytes: &[u8], offset: usize, size: usize) -> usize {
+    (offset..(offset + size).min(bytes.len() * 8))
+        .rev()
+        .fold(0, |acc, bit| {
+            (acc << 1) | ((bytes[bit / 8] >> (bit % 8)) & 1) as usize
+        })
+}
+
 pub trait UncompressedEncoding: Sized {
     type Uncompressed: AsRef<[u8]> + AsMut<[u8]>;
 
@@ -225,6 +279,41 @@ pub fn fe_from_limbs<F1: PrimeField, F2: PrimeField, const LIMBS: usize, const B
     )
 }
 
+/// Same as [`fe_from_limbs`] but for untrusted limbs, which returns
+/// [`Error::AccumulatorLimbOutOfRange`] if any limb exceeds `BITS` bits, and
+/// [`Error::AccumulatorCoordinateOutOfRange`] if the composed value is not
+/// less than the modulus of `F2`.
+pub fn fe_from_limbs_checked<
+    F1: PrimeField,
+    F2: PrimeField,
+    const LIMBS: usize,
+    const BITS: usize,
+>(
+    limbs: &[F1],
+) -> Result<F2, Error> {
+    assert_eq!(limbs.len(), LIMBS);
+
+    let limbs = limbs
+        .iter()
+        .map(|limb| BigUint::from_bytes_le(limb.to_repr().as_ref()))
+        .collect::<Vec<_>>();
+    if limbs.iter().any(|limb| limb.bits() > BITS as u64) {
+        return Err(Error::AccumulatorLimbOutOfRange);
+    }
+
+    let big = limbs
+        .into_iter()
+        .zip((0usize..).step_by(BITS))
+        .map(|(limb, shift)| limb << shift)
+        .fold(BigUint::default(), |acc, shifted| acc + shifted);
+    let modulus = BigUint::from_bytes_le((-F2::one()).to_repr().as_ref()) + 1usize;
+    if big >= modulus {
+        return Err(Error::AccumulatorCoordinateOutOfRange);
+    }
+
+    Ok(big_to_fe(big))
+}
+
 pub fn fe_to_limbs<F1: PrimeField, F2: PrimeField, const LIMBS: usize, const BITS: usize>(
     fe: F1,
 ) -> [F2; LIMBS] {
@@ -238,3 +327,37 @@ pub fn fe_to_limbs<F1: PrimeField, F2: PrimeField, const LIMBS: usize, const BIT
         .try_into()
         .unwrap()
 }
+
+#[cfg(test)]
+mod test {
+    use super::{multi_scalar_multiplication, Field, Group};
+    use halo2_curves::bn256::{Fr, G1};
+    use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+    #[test]
+    fn test_multi_scalar_multiplication() {
+        let naive = |scalars: &[Fr], bases: &[G1]| {
+            scalars
+                .iter()
+                .zip(bases)
+                .fold(G1::identity(), |acc, (scalar, base)| acc + base * scalar)
+        };
+
+        let mut rng = ChaCha20Rng::from_seed(Default::default());
+        for size in [0, 1, 2, 3, 4, 31, 32, 100] {
+            let bases = (0..size).map(|_| G1::random(&mut rng)).collect::<Vec<_>>();
+            for scalars in [
+                (0..size).map(|_| Fr::random(&mut rng)).collect::<Vec<_>>(),
+                vec![Fr::zero(); size],
+                (0..size)
+                    .map(|idx| if idx % 2 == 0 { Fr::zero() } else { -Fr::one() })
+                    .collect(),
+            ] {
+                assert_eq!(
+                    multi_scalar_multiplication(&scalars, &bases),
+                    naive(&scalars, &bases)
+                );
+            }
+        }
+    }
+}